@@ -0,0 +1,266 @@
+use std::collections::HashMap;
+use std::io::Write;
+
+use anyhow::{Context, Result};
+use log::{debug, warn};
+
+/// How review comments (annotations) are represented in the document text
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AnnotationMode {
+    /// Leave annotations out of the document text entirely
+    #[default]
+    Omit,
+    /// Emit each annotation as `[comment by AUTHOR: TEXT]` at its anchor
+    Inline,
+}
+
+impl std::str::FromStr for AnnotationMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "omit" => Ok(AnnotationMode::Omit),
+            "inline" => Ok(AnnotationMode::Inline),
+            _ => Err(anyhow::anyhow!("Unrecognized annotation mode '{}'", s)),
+        }
+    }
+}
+
+/// A review comment, along with the span of document text it applies to
+#[derive(Clone, Debug, Default)]
+pub struct Annotation {
+    pub initials: Option<String>,
+    pub author: Option<String>,
+    pub date: Option<String>,
+    pub text: String,
+    /// Offset into the document text where the annotation mark appeared
+    pub anchor: usize,
+    /// Byte range of the document text the comment was attached to, if the
+    /// writer emitted `\atrfstart`/`\atrfend` bookmarks for it
+    pub range: Option<(usize, usize)>,
+    /// The document text covered by `range`, filled in once the document is
+    /// complete
+    pub anchored_text: Option<String>,
+    reference: Option<String>,
+}
+
+/// Gathers the pieces of each annotation as their destinations are closed
+///
+/// The author and initials precede the `\chatn` mark in the body text, while
+/// the reference and date are nested inside the `\annotation` destination
+/// itself, so they're held as pending until the annotation text completes.
+#[derive(Clone, Default)]
+pub struct AnnotationCollector {
+    annotations: Vec<Annotation>,
+    pending: Annotation,
+    range_starts: HashMap<String, usize>,
+    range_ends: HashMap<String, usize>,
+}
+
+impl AnnotationCollector {
+    /// Destinations whose contents are consumed by the collector
+    pub fn handles_destination(name: &str) -> bool {
+        matches!(
+            name,
+            "annotation"
+                | "atnauthor"
+                | "atnid"
+                | "atndate"
+                | "atntime"
+                | "atnref"
+                | "atrfstart"
+                | "atrfend"
+        )
+    }
+
+    /// Handle the contents of a completed annotation-related destination
+    ///
+    /// `position` is the current length of the document text.  If the
+    /// completed destination produces text to be written into the document,
+    /// it is returned.
+    pub fn fold(
+        &mut self,
+        name: &str,
+        contents: &str,
+        position: usize,
+        mode: AnnotationMode,
+    ) -> Option<String> {
+        let value = contents.trim();
+        match name {
+            "atnid" => self.pending.initials = Some(value.to_owned()),
+            "atnauthor" => self.pending.author = Some(value.to_owned()),
+            "atnref" => self.pending.reference = Some(value.to_owned()),
+            "atndate" | "atntime" => {
+                // Dates with the top bit of the weekday set are written as negative numbers
+                self.pending.date = value
+                    .parse::<i32>()
+                    .ok()
+                    .and_then(|dttm| format_dttm(dttm as u32));
+                if self.pending.date.is_none() {
+                    warn!("Unable to decode annotation date '{}'", value);
+                }
+            }
+            "atrfstart" => {
+                self.range_starts.insert(value.to_owned(), position);
+            }
+            "atrfend" => {
+                self.range_ends.insert(value.to_owned(), position);
+            }
+            "annotation" => {
+                let mut annotation = std::mem::take(&mut self.pending);
+                annotation.text = contents.trim().to_owned();
+                annotation.anchor = position;
+                debug!(
+                    "Completed annotation by {:?}: {:?}",
+                    annotation.author, annotation.text
+                );
+                let inline = match mode {
+                    AnnotationMode::Omit => None,
+                    AnnotationMode::Inline => Some(format!(
+                        "[comment by {}: {}]",
+                        annotation.display_author(),
+                        annotation.text
+                    )),
+                };
+                self.annotations.push(annotation);
+                return inline;
+            }
+            _ => panic!(
+                "Programmer error: {} was routed to the annotation collector without a handler",
+                name
+            ),
+        }
+        None
+    }
+
+    /// Resolve the anchored text ranges against the finished document text
+    pub fn finish(mut self, text: &str) -> Vec<Annotation> {
+        for annotation in self.annotations.iter_mut() {
+            let reference = match annotation.reference.as_ref() {
+                Some(reference) => reference,
+                None => continue,
+            };
+            if let (Some(start), Some(end)) = (
+                self.range_starts.get(reference),
+                self.range_ends.get(reference),
+            ) {
+                annotation.range = Some((*start, *end));
+                annotation.anchored_text = text.get(*start..*end).map(str::to_owned);
+            } else {
+                warn!(
+                    "Annotation reference {} has no matching \\atrfstart/\\atrfend pair",
+                    reference
+                );
+            }
+        }
+        self.annotations
+    }
+}
+
+impl Annotation {
+    fn display_author(&self) -> &str {
+        self.author
+            .as_deref()
+            .or(self.initials.as_deref())
+            .unwrap_or("unknown")
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "author": self.author,
+            "initials": self.initials,
+            "date": self.date,
+            "text": self.text,
+            "anchor": self.anchor,
+            "range": self.range.map(|(start, end)| serde_json::json!({ "start": start, "end": end })),
+            "anchored_text": self.anchored_text,
+        })
+    }
+}
+
+/// Write the list of annotations as a JSON array
+pub fn write_json<W: Write>(annotations: &[Annotation], writer: W) -> Result<()> {
+    let list: Vec<serde_json::Value> = annotations.iter().map(Annotation::to_json).collect();
+    serde_json::to_writer_pretty(writer, &list).context("Error writing annotations")
+}
+
+/// Decode a packed Word DTTM date/time value into `YYYY-MM-DD HH:MM` form
+///
+/// The bit layout is minutes (6 bits), hours (5), day of month (5), month (4),
+/// years since 1900 (9), and day of week (3), from least to most significant.
+pub fn format_dttm(dttm: u32) -> Option<String> {
+    let minute = dttm & 0x3F;
+    let hour = (dttm >> 6) & 0x1F;
+    let day = (dttm >> 11) & 0x1F;
+    let month = (dttm >> 16) & 0x0F;
+    let year = ((dttm >> 20) & 0x1FF) + 1900;
+    if day == 0 || month == 0 || month > 12 || hour > 23 || minute > 59 {
+        return None;
+    }
+    Some(format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}",
+        year, month, day, hour, minute
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_dttm_dates() {
+        // Thursday 16 May 2024, written as a negative number
+        assert_eq!(
+            format_dttm(-2017099170i32 as u32).unwrap(),
+            "2024-05-16 09:30"
+        );
+        assert_eq!(format_dttm(0), None);
+        // 30 o'clock
+        assert_eq!(format_dttm(30 << 6 | 1 << 11 | 1 << 16), None);
+    }
+
+    #[test]
+    fn collects_annotations_with_their_ranges() {
+        let mut collector = AnnotationCollector::default();
+        let mode = AnnotationMode::Inline;
+        assert_eq!(collector.fold("atrfstart", "7", 4, mode), None);
+        assert_eq!(collector.fold("atrfend", "7", 9, mode), None);
+        collector.fold("atnid", "JD", 9, mode);
+        collector.fold("atnauthor", "Jane Doe", 9, mode);
+        collector.fold("atnref", "7", 9, mode);
+        collector.fold("atndate", "-2017099170", 9, mode);
+        assert_eq!(
+            collector
+                .fold("annotation", " Check this ", 9, mode)
+                .unwrap(),
+            "[comment by Jane Doe: Check this]"
+        );
+        // A second comment with no author, no date and no range
+        collector.fold("atnid", "", 12, mode);
+        collector.fold("atndate", "not a date", 12, mode);
+        collector.fold("atnref", "8", 12, mode);
+        assert_eq!(
+            collector.fold("annotation", "Why?", 12, AnnotationMode::Omit),
+            None
+        );
+
+        let annotations = collector.finish("The quick fox");
+        assert_eq!(annotations.len(), 2);
+        let first = &annotations[0];
+        assert_eq!(first.initials.as_deref(), Some("JD"));
+        assert_eq!(first.date.as_deref(), Some("2024-05-16 09:30"));
+        assert_eq!(first.range, Some((4, 9)));
+        assert_eq!(first.anchored_text.as_deref(), Some("quick"));
+        let second = &annotations[1];
+        assert_eq!(second.date, None);
+        assert_eq!(second.range, None);
+        assert_eq!(second.anchor, 12);
+
+        let mut json = Vec::new();
+        write_json(&annotations, &mut json).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        assert_eq!(json[0]["date"], "2024-05-16 09:30");
+        assert_eq!(json[0]["range"]["end"], 9);
+        assert_eq!(json[1]["date"], serde_json::Value::Null);
+    }
+}
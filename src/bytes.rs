@@ -0,0 +1,88 @@
+/// A little-endian reader over a byte buffer, for the OLE1 stream formats
+/// and other binary containers
+pub struct ByteReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    /// The number of bytes left to read
+    pub fn remaining(&self) -> usize {
+        self.data.len().saturating_sub(self.pos)
+    }
+
+    pub fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        let bytes = self.data.get(self.pos..self.pos.checked_add(len)?)?;
+        self.pos += len;
+        Some(bytes)
+    }
+
+    pub fn u16(&mut self) -> Option<u16> {
+        self.bytes(2).map(|b| u16::from_le_bytes([b[0], b[1]]))
+    }
+
+    pub fn u32(&mut self) -> Option<u32> {
+        self.bytes(4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    /// A string prefixed with its length, including the terminating null
+    pub fn length_prefixed_string(&mut self) -> Option<String> {
+        let len = self.u32()? as usize;
+        let bytes = self.bytes(len)?;
+        Some(ansi_string(bytes))
+    }
+
+    pub fn null_terminated_string(&mut self) -> Option<String> {
+        let rest = self.data.get(self.pos..)?;
+        let len = rest.iter().position(|b| *b == 0)?;
+        self.pos += len + 1;
+        Some(ansi_string(&rest[..len]))
+    }
+}
+
+/// Decode a Windows-1252 string, up to any terminating null
+pub fn ansi_string(bytes: &[u8]) -> String {
+    let bytes = bytes.split(|b| *b == 0).next().unwrap_or_default();
+    encoding_rs::WINDOWS_1252.decode(bytes).0.into_owned()
+}
+
+/// Decode a UTF-16LE string, dropping any terminating nulls
+pub fn utf16_string(bytes: &[u8]) -> String {
+    let units: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+        .collect();
+    String::from_utf16_lossy(&units)
+        .trim_end_matches('\0')
+        .to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_within_bounds() {
+        let mut reader = ByteReader::new(b"\x02\x00\x00\x00ab\x00cd");
+        assert_eq!(reader.u32(), Some(2));
+        assert_eq!(reader.bytes(3), Some(&b"ab\x00"[..]));
+        assert_eq!(reader.u32(), None);
+        assert_eq!(reader.remaining(), 2);
+        assert_eq!(reader.bytes(usize::MAX), None);
+        assert_eq!(reader.null_terminated_string(), None);
+        assert_eq!(reader.u16(), Some(0x6463));
+    }
+
+    #[test]
+    fn decodes_strings() {
+        assert_eq!(ansi_string(b"caf\xe9\0junk"), "caf\u{e9}");
+        assert_eq!(utf16_string(b"c\0a\0f\0\xe9\0\0\0"), "caf\u{e9}");
+        // An odd trailing byte and unpaired surrogates
+        assert_eq!(utf16_string(b"a\0\x00\xd8b"), "a\u{fffd}");
+    }
+}
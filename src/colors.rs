@@ -0,0 +1,165 @@
+use log::{debug, warn};
+
+/// The scale of Cocoa's `\c` color components, which are fractions of 100000
+const COMPONENT_SCALE: f64 = 100_000.0;
+
+/// A color from the document's color table
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Color {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+}
+
+impl Color {
+    /// The color as a CSS hex color, like `#fb0007`
+    pub fn hex(&self) -> String {
+        format!("#{:02x}{:02x}{:02x}", self.red, self.green, self.blue)
+    }
+
+    pub fn is_black(&self) -> bool {
+        *self
+            == Color {
+                red: 0,
+                green: 0,
+                blue: 0,
+            }
+    }
+}
+
+/// One `;`-terminated entry of a color table, as it's read
+#[derive(Clone, Default)]
+struct Entry {
+    red: Option<i32>,
+    green: Option<i32>,
+    blue: Option<i32>,
+    /// The color space of an `\expandedcolortbl` entry, such as `cssrgb`
+    color_space: Option<String>,
+    /// The `\c` components of an `\expandedcolortbl` entry
+    components: Vec<i32>,
+}
+
+impl Entry {
+    /// The color of a `\colortbl` entry, or `None` for the automatic color
+    fn color(&self) -> Option<Color> {
+        if self.red.is_none() && self.green.is_none() && self.blue.is_none() {
+            return None;
+        }
+        let channel = |value: Option<i32>| value.unwrap_or(0).clamp(0, 255) as u8;
+        Some(Color {
+            red: channel(self.red),
+            green: channel(self.green),
+            blue: channel(self.blue),
+        })
+    }
+
+    /// The color of an `\expandedcolortbl` entry, or `None` if it's empty,
+    /// leaving the `\colortbl` entry to stand
+    fn expanded_color(&self) -> Option<Color> {
+        let component = |index: usize| {
+            let value = f64::from(self.components.get(index).copied().unwrap_or(0));
+            (value / COMPONENT_SCALE).clamp(0.0, 1.0)
+        };
+        let channel = |fraction: f64| (fraction * 255.0).round() as u8;
+        match self.color_space.as_deref()? {
+            "cssrgb" | "csgenericrgb" => Some(Color {
+                red: channel(component(0)),
+                green: channel(component(1)),
+                blue: channel(component(2)),
+            }),
+            "csgray" | "csgenericgray" => {
+                let gray = channel(component(0));
+                Some(Color {
+                    red: gray,
+                    green: gray,
+                    blue: gray,
+                })
+            }
+            "cscmyk" => {
+                let key = 1.0 - component(3);
+                Some(Color {
+                    red: channel((1.0 - component(0)) * key),
+                    green: channel((1.0 - component(1)) * key),
+                    blue: channel((1.0 - component(2)) * key),
+                })
+            }
+            other => {
+                warn!(
+                    "Unsupported color space \\{} in expanded color table",
+                    other
+                );
+                None
+            }
+        }
+    }
+}
+
+/// Gathers the entries of the `\colortbl` and Cocoa's `\expandedcolortbl`
+/// as their control words and `;` separators are written
+#[derive(Clone, Default)]
+pub struct ColorCollector {
+    entry: Entry,
+    entries: Vec<Entry>,
+    colors: Vec<Option<Color>>,
+    expanded: Vec<Option<Color>>,
+}
+
+impl ColorCollector {
+    pub fn handles_destination(name: &str) -> bool {
+        matches!(name, "colortbl" | "expandedcolortbl")
+    }
+
+    /// Note a control word in a color table
+    pub fn control_word(&mut self, name: &str, arg: Option<i32>) {
+        match name {
+            "red" => self.entry.red = arg,
+            "green" => self.entry.green = arg,
+            "blue" => self.entry.blue = arg,
+            "c" => self.entry.components.push(arg.unwrap_or(0)),
+            "cssrgb" | "csgenericrgb" | "csgray" | "csgenericgray" | "cscmyk" => {
+                self.entry.color_space = Some(name.to_owned())
+            }
+            _ => (),
+        }
+    }
+
+    /// Note text in a color table, where each `;` ends an entry
+    pub fn text(&mut self, text: &[u8]) {
+        for _ in text.iter().filter(|b| **b == b';') {
+            self.entries.push(std::mem::take(&mut self.entry));
+        }
+    }
+
+    /// Handle a closed color table
+    pub fn fold(&mut self, name: &str) {
+        let entries = std::mem::take(&mut self.entries);
+        self.entry = Entry::default();
+        match name {
+            "colortbl" if self.colors.is_empty() => {
+                self.colors = entries.iter().map(Entry::color).collect()
+            }
+            "expandedcolortbl" => {
+                self.expanded = entries.iter().map(Entry::expanded_color).collect()
+            }
+            _ => (),
+        }
+    }
+
+    /// The document's color table, using the colors from the
+    /// `\expandedcolortbl` in place of any entries it gives, as they're in
+    /// the color space the author picked them in
+    pub fn finish(self) -> Vec<Option<Color>> {
+        let mut colors = self.colors;
+        debug!(
+            "Read color table of {} colors, {} with expanded colors",
+            colors.len(),
+            self.expanded.iter().flatten().count()
+        );
+        for (color, expanded) in colors.iter_mut().zip(self.expanded) {
+            if expanded.is_some() {
+                *color = expanded;
+            }
+        }
+        colors
+    }
+}
@@ -0,0 +1,206 @@
+use anyhow::{bail, Result};
+use log::{debug, warn};
+
+/// Compression type of a compressed RTF stream with LZ77 compressed contents
+const COMPRESSED: &[u8; 4] = b"LZFu";
+/// Compression type of a compressed RTF stream with its contents stored as-is
+const UNCOMPRESSED: &[u8; 4] = b"MELA";
+
+const HEADER_SIZE: usize = 16;
+const DICTIONARY_SIZE: usize = 4096;
+
+/// The initial contents of the LZFu dictionary (MS-OXRTFCP 2.1.3.1.1)
+const DICTIONARY_PRELOAD: &[u8] = b"{\\rtf1\\ansi\\mac\\deff0\\deftab720{\\fonttbl;}\
+{\\f0\\fnil \\froman \\fswiss \\fmodern \\fscript \\fdecor MS Sans SerifSymbolArial\
+Times New RomanCourier{\\colortbl\\red0\\green0\\blue0\r\n\\par \
+\\pard\\plain\\f0\\fs20\\b\\i\\u\\tab\\tx";
+
+/// Whether `data` starts with a compressed RTF header (MS-OXRTFCP), as
+/// stored in Outlook's `PR_RTF_COMPRESSED` property
+pub fn is_compressed_rtf(data: &[u8]) -> bool {
+    data.len() >= HEADER_SIZE && matches!(&data[8..12], b"LZFu" | b"MELA")
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        data[offset],
+        data[offset + 1],
+        data[offset + 2],
+        data[offset + 3],
+    ])
+}
+
+/// Decompress a compressed RTF stream, checking its CRC
+pub fn decompress(data: &[u8]) -> Result<Vec<u8>> {
+    if !is_compressed_rtf(data) {
+        bail!("Not a compressed RTF stream");
+    }
+    let compressed_size = read_u32(data, 0) as usize;
+    let raw_size = read_u32(data, 4) as usize;
+    let crc = read_u32(data, 12);
+    // The compressed size counts everything after its own field
+    let end = compressed_size.saturating_add(4);
+    let contents = if end > data.len() {
+        warn!(
+            "Compressed RTF stream is truncated: expected {} bytes, found {}",
+            end,
+            data.len()
+        );
+        &data[HEADER_SIZE..]
+    } else {
+        &data[HEADER_SIZE..end.max(HEADER_SIZE)]
+    };
+    debug!(
+        "Compressed RTF stream of type {}, {} bytes decompressing to {}",
+        String::from_utf8_lossy(&data[8..12]),
+        contents.len(),
+        raw_size
+    );
+
+    if &data[8..12] == UNCOMPRESSED {
+        return Ok(contents[..raw_size.min(contents.len())].to_vec());
+    }
+    debug_assert_eq!(&data[8..12], COMPRESSED);
+    let actual_crc = crc32(contents);
+    if actual_crc != crc {
+        bail!(
+            "Compressed RTF CRC mismatch: header has {:08x}, contents have {:08x}",
+            crc,
+            actual_crc
+        );
+    }
+    let output = decompress_lzfu(contents, raw_size);
+    if output.len() != raw_size {
+        bail!(
+            "Compressed RTF decompressed to {} bytes, but the header gives {}",
+            output.len(),
+            raw_size
+        );
+    }
+    Ok(output)
+}
+
+/// Expand LZFu data: each control byte flags the next eight items, least
+/// significant bit first, as either a literal byte or a 12-bit offset and
+/// 4-bit length reference into a 4096 byte circular dictionary
+fn decompress_lzfu(contents: &[u8], raw_size: usize) -> Vec<u8> {
+    let mut dictionary = [0u8; DICTIONARY_SIZE];
+    dictionary[..DICTIONARY_PRELOAD.len()].copy_from_slice(DICTIONARY_PRELOAD);
+    let mut write_pos = DICTIONARY_PRELOAD.len();
+    // The header's size can't be trusted, but each byte of input expands to
+    // at most eight bytes of output
+    let mut output = Vec::with_capacity(raw_size.min(contents.len().saturating_mul(8)));
+    let mut input = contents.iter().copied();
+
+    while let Some(control) = input.next() {
+        for bit in 0..8 {
+            if control & (1 << bit) == 0 {
+                let byte = match input.next() {
+                    Some(byte) => byte,
+                    None => return output,
+                };
+                output.push(byte);
+                dictionary[write_pos] = byte;
+                write_pos = (write_pos + 1) % DICTIONARY_SIZE;
+            } else {
+                let reference = match (input.next(), input.next()) {
+                    (Some(high), Some(low)) => u16::from_be_bytes([high, low]),
+                    _ => {
+                        warn!("Compressed RTF ends in the middle of a dictionary reference");
+                        return output;
+                    }
+                };
+                let offset = usize::from(reference >> 4);
+                let length = usize::from(reference & 0xF) + 2;
+                // A reference to the current write position marks the end
+                if offset == write_pos {
+                    return output;
+                }
+                for i in 0..length {
+                    let byte = dictionary[(offset + i) % DICTIONARY_SIZE];
+                    output.push(byte);
+                    dictionary[write_pos] = byte;
+                    write_pos = (write_pos + 1) % DICTIONARY_SIZE;
+                }
+            }
+        }
+    }
+    warn!("Compressed RTF has no end marker");
+    output
+}
+
+/// The CRC-32 used by compressed RTF, which unlike the usual CRC-32 starts
+/// from 0 and isn't inverted at the end (MS-OXRTFCP 2.1.3.2)
+fn crc32(data: &[u8]) -> u32 {
+    data.iter().fold(0u32, |crc, byte| {
+        let mut value = (crc ^ u32::from(*byte)) & 0xFF;
+        for _ in 0..8 {
+            value = if value & 1 == 1 {
+                (value >> 1) ^ 0xEDB8_8320
+            } else {
+                value >> 1
+            };
+        }
+        value ^ (crc >> 8)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The first example from MS-OXRTFCP 3.1.1
+    const SAMPLE: &[u8] = &[
+        0x2d, 0x00, 0x00, 0x00, 0x2b, 0x00, 0x00, 0x00, 0x4c, 0x5a, 0x46, 0x75, 0xf1, 0xc5, 0xc7,
+        0xa7, 0x03, 0x00, 0x0a, 0x00, 0x72, 0x63, 0x70, 0x67, 0x31, 0x32, 0x35, 0x42, 0x32, 0x0a,
+        0xf3, 0x20, 0x68, 0x65, 0x6c, 0x09, 0x00, 0x20, 0x62, 0x77, 0x05, 0xb0, 0x6c, 0x64, 0x7d,
+        0x0a, 0x80, 0x0f, 0xa0,
+    ];
+
+    /// The second example from MS-OXRTFCP 3.1.2, with a reference that
+    /// overlaps the bytes it's writing
+    const RUN_SAMPLE: &[u8] = &[
+        0x1a, 0x00, 0x00, 0x00, 0x1c, 0x00, 0x00, 0x00, 0x4c, 0x5a, 0x46, 0x75, 0xe2, 0xd4, 0x4b,
+        0x51, 0x41, 0x00, 0x04, 0x20, 0x57, 0x58, 0x59, 0x5a, 0x0d, 0x6e, 0x7d, 0x01, 0x0e, 0xb0,
+    ];
+
+    #[test]
+    fn decompresses_specification_samples() {
+        assert!(is_compressed_rtf(SAMPLE));
+        assert_eq!(
+            decompress(SAMPLE).unwrap(),
+            b"{\\rtf1\\ansi\\ansicpg1252\\pard hello world}\r\n"
+        );
+        assert_eq!(
+            decompress(RUN_SAMPLE).unwrap(),
+            b"{\\rtf1 WXYZWXYZWXYZWXYZWXYZ}"
+        );
+    }
+
+    #[test]
+    fn reads_uncompressed_contents() {
+        let mut data = vec![0x0f, 0, 0, 0, 0x03, 0, 0, 0];
+        data.extend(b"MELA");
+        data.extend([0; 4]);
+        data.extend(b"{x}");
+        assert_eq!(decompress(&data).unwrap(), b"{x}");
+    }
+
+    #[test]
+    fn rejects_oversized_header() {
+        // No contents, claiming to decompress to 4 GiB
+        let mut data = vec![0x0c, 0, 0, 0, 0xff, 0xff, 0xff, 0xff];
+        data.extend(b"LZFu");
+        data.extend([0; 4]);
+        assert!(decompress(&data).is_err());
+    }
+
+    #[test]
+    fn rejects_truncated_or_corrupt_contents() {
+        assert!(decompress(&SAMPLE[..30]).is_err());
+        let mut corrupt = SAMPLE.to_vec();
+        corrupt[20] ^= 0xff;
+        assert!(decompress(&corrupt).is_err());
+        assert!(decompress(b"LZFu").is_err());
+    }
+}
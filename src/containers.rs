@@ -0,0 +1,113 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::{bail, Result};
+use log::debug;
+
+use crate::{compressed, mime, msg, rtfd, tnef};
+
+/// An RTF document from the input, which may be one of several held in a
+/// container format such as an Outlook message
+pub struct SourceDocument {
+    /// Where the document came from within the container, such as "body"
+    pub label: String,
+    pub data: Vec<u8>,
+    /// Files stored alongside the document, such as RTFD attachments
+    pub files: HashMap<String, Vec<u8>>,
+}
+
+impl SourceDocument {
+    pub fn new(label: &str, data: Vec<u8>) -> Self {
+        SourceDocument {
+            label: label.to_owned(),
+            data,
+            files: HashMap::new(),
+        }
+    }
+
+    fn from_bundle(bundle: rtfd::Bundle) -> Self {
+        SourceDocument {
+            label: rtfd::BUNDLE_DOCUMENT.to_owned(),
+            data: bundle.document,
+            files: bundle.files,
+        }
+    }
+}
+
+/// Whether an attachment's contents are RTF, either plain or compressed
+pub fn is_rtf(data: &[u8]) -> bool {
+    data.starts_with(b"{\\rtf") || compressed::is_compressed_rtf(data)
+}
+
+/// Whether an attachment's filename or MIME type claims it's RTF
+pub fn is_named_rtf(filename: Option<&str>, mime_type: Option<&str>) -> bool {
+    filename
+        .map(|name| name.to_lowercase().ends_with(".rtf"))
+        .unwrap_or(false)
+        || matches!(mime_type, Some("text/rtf" | "application/rtf"))
+}
+
+/// The label of an attachment, numbered from 1 and with its filename if it has one
+pub fn attachment_label(prefix: &str, index: usize, filename: Option<&str>) -> String {
+    match filename {
+        Some(name) => format!("{}attachment {} ({})", prefix, index + 1, name),
+        None => format!("{}attachment {}", prefix, index + 1),
+    }
+}
+
+/// Add an attachment to `documents` if it's RTF, or the RTF documents
+/// within it if it's a message container, returning whether it was either
+pub fn extract_attachment(
+    label: &str,
+    data: &[u8],
+    documents: &mut Vec<SourceDocument>,
+) -> Result<bool> {
+    let nested_prefix = format!("{}/", label);
+    if is_rtf(data) {
+        documents.push(SourceDocument::new(label, data.to_vec()));
+    } else if msg::is_msg(data) {
+        debug!("{} is an Outlook message", label);
+        msg::extract_documents(data, &nested_prefix, documents)?;
+    } else if tnef::is_tnef(data) {
+        debug!("{} is a TNEF stream", label);
+        tnef::extract_documents(data, &nested_prefix, documents)?;
+    } else {
+        return Ok(false);
+    }
+    Ok(true)
+}
+
+/// Read the document and attachments of an `.rtfd` bundle directory
+pub fn extract_bundle(dir: &Path) -> Result<Vec<SourceDocument>> {
+    let bundle = rtfd::read_bundle_dir(dir)?;
+    Ok(vec![SourceDocument::from_bundle(bundle)])
+}
+
+/// Find the RTF documents in the input, which is either RTF itself or a
+/// container holding RTF documents
+pub fn extract_documents(data: Vec<u8>) -> Result<Vec<SourceDocument>> {
+    let mut documents = Vec::new();
+    let container = if msg::is_msg(&data) {
+        debug!("Input is an Outlook message.");
+        msg::extract_documents(&data, "", &mut documents)?;
+        "Outlook message"
+    } else if tnef::is_tnef(&data) {
+        debug!("Input is a TNEF (winmail.dat) stream.");
+        tnef::extract_documents(&data, "", &mut documents)?;
+        "TNEF stream"
+    } else if !is_rtf(&data) && mime::is_mime(&data) {
+        debug!("Input is a MIME message.");
+        mime::extract_documents(&data, "", &mut documents)?;
+        "MIME message"
+    } else if rtfd::is_flat_rtfd(&data) {
+        debug!("Input is a flattened RTFD bundle.");
+        let bundle = rtfd::parse_flat_rtfd(&data)?;
+        return Ok(vec![SourceDocument::from_bundle(bundle)]);
+    } else {
+        return Ok(vec![SourceDocument::new("document", data)]);
+    };
+    if documents.is_empty() {
+        bail!("{} has no RTF body or RTF attachments", container);
+    }
+    Ok(documents)
+}
@@ -0,0 +1,358 @@
+use std::io::Write;
+
+use anyhow::{Context, Result};
+use log::{debug, warn};
+use rtf_grimoire::tokenizer::Token;
+
+use crate::rtf_control;
+
+/// What to do with a document that encapsulates an original in another format
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EncapsulationMode {
+    /// Write the original content exactly as it was before encapsulation
+    #[default]
+    Original,
+    /// Write the original content converted to plain text
+    Text,
+    /// Ignore the encapsulation, and convert the RTF rendering like any other document
+    Rtf,
+}
+
+impl std::str::FromStr for EncapsulationMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "original" => Ok(EncapsulationMode::Original),
+            "text" => Ok(EncapsulationMode::Text),
+            "rtf" => Ok(EncapsulationMode::Rtf),
+            _ => Err(anyhow::anyhow!("Unrecognized encapsulation mode '{}'", s)),
+        }
+    }
+}
+
+/// The format of the content encapsulated in an RTF document (MS-OXRTFEX)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EncapsulatedFormat {
+    /// `\fromhtml1`: HTML, kept in `\htmltag` destinations and the text between them
+    Html,
+    /// `\fromtext`: plain text, kept in the document text outside `\htmlrtf` sections
+    Text,
+}
+
+/// The original content recovered from an encapsulating RTF document
+pub struct Encapsulated {
+    pub format: EncapsulatedFormat,
+    pub text: String,
+    /// The document's ANSI code page, which the original was written in
+    pub encoding: &'static encoding_rs::Encoding,
+}
+
+impl Encapsulated {
+    /// The original content as written out: HTML in its own encoding, using
+    /// character references for any characters the code page can't represent,
+    /// and plain text as UTF-8 like any other extracted text
+    pub fn original_bytes(&self) -> Vec<u8> {
+        match self.format {
+            EncapsulatedFormat::Html => self.encoding.encode(&self.text).0.into_owned(),
+            EncapsulatedFormat::Text => self.text.clone().into_bytes(),
+        }
+    }
+}
+
+/// Look for the control word marking an encapsulating document, which must
+/// appear in the document header before any text or nested group
+pub fn detect(tokens: &[Token]) -> Option<EncapsulatedFormat> {
+    let mut depth = 0;
+    for token in tokens {
+        match token {
+            Token::StartGroup if depth == 0 => depth += 1,
+            Token::StartGroup | Token::Text(_) => break,
+            Token::ControlWord { name, arg } if name == "fromhtml" && *arg != Some(0) => {
+                debug!("Found \\fromhtml, document encapsulates HTML");
+                return Some(EncapsulatedFormat::Html);
+            }
+            Token::ControlWord { name, .. } if name == "fromtext" => {
+                debug!("Found \\fromtext, document encapsulates plain text");
+                return Some(EncapsulatedFormat::Text);
+            }
+            _ => (),
+        }
+    }
+    None
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Destination {
+    Body,
+    HtmlTag,
+    Ignored,
+}
+
+#[derive(Clone)]
+struct GroupState {
+    destination: Destination,
+    /// Within an `\htmlrtf` section, which only exists for the RTF rendering
+    suppressed: bool,
+    /// Fallback characters written after each `\u` (`\uc`)
+    unicode_fallback: usize,
+}
+
+/// Recovers the original content of an encapsulating document, following the
+/// de-encapsulation rules of MS-OXRTFEX
+struct Decapsulator {
+    format: EncapsulatedFormat,
+    groups: Vec<GroupState>,
+    encoding: &'static encoding_rs::Encoding,
+    /// The encoding given on the command line, which wins over `\ansicpg`
+    forced_encoding: Option<&'static encoding_rs::Encoding>,
+    text: String,
+    /// Bytes from text and `\'xx` escapes, decoded together so that double
+    /// byte characters split across escapes come out whole
+    pending: Vec<u8>,
+    unicode_skip: usize,
+    high_surrogate: Option<u16>,
+    next_is_destination: bool,
+}
+
+impl Decapsulator {
+    fn new(
+        format: EncapsulatedFormat,
+        forced_encoding: Option<&'static encoding_rs::Encoding>,
+    ) -> Self {
+        Self {
+            format,
+            groups: vec![GroupState {
+                destination: Destination::Body,
+                suppressed: false,
+                unicode_fallback: 1,
+            }],
+            encoding: forced_encoding.unwrap_or(encoding_rs::WINDOWS_1252),
+            forced_encoding,
+            text: String::new(),
+            pending: Vec::new(),
+            unicode_skip: 0,
+            high_surrogate: None,
+            next_is_destination: false,
+        }
+    }
+
+    fn group(&mut self) -> &mut GroupState {
+        // The outermost state is never popped, see end_group
+        self.groups.last_mut().unwrap()
+    }
+
+    /// Whether content at this point is part of the original
+    fn is_original(&self) -> bool {
+        match self.groups.last() {
+            Some(group) => match group.destination {
+                Destination::HtmlTag => true,
+                Destination::Body => !group.suppressed,
+                Destination::Ignored => false,
+            },
+            None => false,
+        }
+    }
+
+    fn flush(&mut self) {
+        if !self.pending.is_empty() {
+            let (text, _, had_errors) = self.encoding.decode(&self.pending);
+            if had_errors {
+                warn!("Encapsulated text isn't valid in {}", self.encoding.name());
+            }
+            self.text.push_str(&text);
+            self.pending.clear();
+        }
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        let skipped = self.unicode_skip.min(bytes.len());
+        self.unicode_skip -= skipped;
+        if self.is_original() {
+            self.pending.extend(&bytes[skipped..]);
+        }
+    }
+
+    fn write_str(&mut self, text: &str) {
+        if self.is_original() {
+            self.flush();
+            self.text.push_str(text);
+        }
+    }
+
+    fn start_group(&mut self) {
+        let group = self.group().clone();
+        self.groups.push(group);
+        self.unicode_skip = 0;
+    }
+
+    fn end_group(&mut self) {
+        if self.groups.len() > 1 {
+            self.groups.pop();
+        } else {
+            warn!("Document format error: End group count exceeds number start groups");
+        }
+        self.unicode_skip = 0;
+    }
+
+    fn control_word(&mut self, name: &str, arg: Option<i32>) {
+        let is_destination = std::mem::take(&mut self.next_is_destination);
+        match name {
+            // The document body is a destination of its own
+            "rtf" => (),
+            // Only HTML is kept in tags, anything else uses them for RTF-only content
+            "htmltag" if self.format == EncapsulatedFormat::Html => {
+                self.group().destination = Destination::HtmlTag
+            }
+            "htmlrtf" => self.group().suppressed = arg != Some(0),
+            "ansicpg" if self.forced_encoding.is_none() => {
+                self.flush();
+                self.encoding = arg
+                    .and_then(|cp| codepage::to_encoding(cp as u16))
+                    .unwrap_or(encoding_rs::WINDOWS_1252);
+            }
+            "uc" => self.group().unicode_fallback = arg.unwrap_or(1).max(0) as usize,
+            "u" => {
+                let unit = arg.unwrap_or(0) as u16;
+                let units = match (self.high_surrogate.take(), unit) {
+                    (_, 0xD800..=0xDBFF) => {
+                        self.high_surrogate = Some(unit);
+                        vec![]
+                    }
+                    (Some(high), _) => vec![high, unit],
+                    (None, _) => vec![unit],
+                };
+                for c in char::decode_utf16(units).filter_map(Result::ok) {
+                    self.write_str(c.encode_utf8(&mut [0; 4]));
+                }
+                self.unicode_skip = self.group().unicode_fallback;
+            }
+            "'" => {
+                let byte = arg.unwrap_or(0) as u8;
+                self.write_bytes(&[byte]);
+            }
+            "par" | "line" => self.write_str("\r\n"),
+            "tab" => self.write_str("\t"),
+            "lquote" => self.write_str("\u{2018}"),
+            "rquote" => self.write_str("\u{2019}"),
+            "ldblquote" => self.write_str("\u{201C}"),
+            "rdblquote" => self.write_str("\u{201D}"),
+            "bullet" => self.write_str("\u{2022}"),
+            "endash" => self.write_str("\u{2013}"),
+            "emdash" => self.write_str("\u{2014}"),
+            "enspace" => self.write_str("\u{2002}"),
+            "emspace" => self.write_str("\u{2003}"),
+            _ if is_destination || rtf_control::DESTINATIONS.contains_key(name) => {
+                self.group().destination = Destination::Ignored
+            }
+            _ => (),
+        }
+    }
+
+    fn control_symbol(&mut self, symbol: char) {
+        match symbol {
+            '*' => self.next_is_destination = true,
+            '\\' | '{' | '}' => self.write_bytes(&[symbol as u8]),
+            '~' => self.write_str("\u{00A0}"),
+            '_' => self.write_str("\u{2011}"),
+            '\n' | '\r' => self.write_str("\r\n"),
+            _ => (),
+        }
+    }
+
+    fn finish(mut self) -> Encapsulated {
+        self.flush();
+        Encapsulated {
+            format: self.format,
+            text: self.text,
+            encoding: self.encoding,
+        }
+    }
+}
+
+/// Recover the original content of a document that encapsulates `format`
+pub fn de_encapsulate(
+    tokens: &[Token],
+    format: EncapsulatedFormat,
+    forced_encoding: Option<&'static encoding_rs::Encoding>,
+) -> Encapsulated {
+    let mut decapsulator = Decapsulator::new(format, forced_encoding);
+    for token in tokens {
+        match token {
+            Token::StartGroup => decapsulator.start_group(),
+            Token::EndGroup => decapsulator.end_group(),
+            Token::ControlWord { name, arg } => decapsulator.control_word(name, *arg),
+            Token::ControlSymbol(symbol) => decapsulator.control_symbol(*symbol),
+            Token::Text(bytes) => decapsulator.write_bytes(bytes),
+            Token::ControlBin(_) | Token::Newline => (),
+        }
+    }
+    decapsulator.finish()
+}
+
+/// Write the recovered content, either as it was originally or as plain text
+pub fn write_original<W: Write>(
+    original: &Encapsulated,
+    mode: EncapsulationMode,
+    mut writer: W,
+) -> Result<()> {
+    match (mode, original.format) {
+        (EncapsulationMode::Text, EncapsulatedFormat::Html) => {
+            debug!("Converting encapsulated HTML to text...");
+            let text = html2text::config::plain()
+                .allow_width_overflow()
+                .string_from_read(original.text.as_bytes(), usize::MAX / 2)
+                .context("Error converting encapsulated HTML to text")?;
+            writer
+                .write_all(text.as_bytes())
+                .context("Error writing to output file")
+        }
+        _ => {
+            debug!("Writing encapsulated {:?} content...", original.format);
+            writer
+                .write_all(&original.original_bytes())
+                .context("Error writing to output file")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decapsulate(
+        rtf: &[u8],
+        forced_encoding: Option<&'static encoding_rs::Encoding>,
+    ) -> Encapsulated {
+        let tokens = crate::rtftotext::tokenize(rtf).unwrap();
+        let format = detect(&tokens).unwrap();
+        de_encapsulate(&tokens, format, forced_encoding)
+    }
+
+    #[test]
+    fn recovers_encapsulated_html() {
+        let original = decapsulate(
+            br"{\rtf1\ansi\ansicpg1251\fromhtml1 {\*\htmltag1 <p>}\'cf{\htmlrtf \par\htmlrtf0}{\*\htmltag2 </p>}}",
+            None,
+        );
+        assert_eq!(original.format, EncapsulatedFormat::Html);
+        assert_eq!(original.text, "<p>\u{41f}</p>");
+        assert_eq!(original.original_bytes(), b"<p>\xcf</p>");
+    }
+
+    #[test]
+    fn uses_the_forced_encoding() {
+        let rtf = br"{\rtf1\ansi\ansicpg1251\fromtext \'cf\par}";
+        assert_eq!(decapsulate(rtf, None).text, "\u{41f}\r\n");
+        let forced = decapsulate(rtf, Some(encoding_rs::WINDOWS_1253));
+        assert_eq!(forced.text, "\u{39f}\r\n");
+        assert_eq!(forced.encoding, encoding_rs::WINDOWS_1253);
+    }
+
+    #[test]
+    fn ignores_documents_without_an_encapsulation_marker() {
+        let tokens =
+            crate::rtftotext::tokenize(&br"{\rtf1\ansi{\fonttbl}\fromtext Text}"[..]).unwrap();
+        assert_eq!(detect(&tokens), None);
+    }
+}
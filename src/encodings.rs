@@ -0,0 +1,443 @@
+use std::borrow::Cow;
+use std::io::{self, Write};
+
+use anyhow::{bail, Result};
+use encoding_rs::{EncoderResult, Encoding};
+use log::{debug, trace};
+
+use crate::transliterate;
+
+/// Decode the bytes of a text token or `\'xx` escape in `encoding`, or as
+/// UTF-8 when they look like raw UTF-8 from a writer that doesn't escape
+/// non-ASCII text
+///
+/// Bytes are taken as UTF-8 when they hold multi-byte sequences and are valid
+/// UTF-8 throughout, and either `assume_utf8` is set or the document declares
+/// a single-byte codepage, where that's all but impossible in genuine text:
+/// `é` in cp1252 would need to be written as `Ã©`. Escaped bytes come one at
+/// a time, so they never look like UTF-8 and keep the declared codepage.
+pub fn decode_text<'a>(
+    bytes: &'a [u8],
+    encoding: Option<&'static Encoding>,
+    assume_utf8: bool,
+) -> Option<Cow<'a, str>> {
+    let detect = assume_utf8 || encoding.is_some_and(Encoding::is_single_byte);
+    if detect && !bytes.is_ascii() {
+        if let Ok(text) = std::str::from_utf8(bytes) {
+            trace!("Decoding raw UTF-8 text {:?}", text);
+            return Some(Cow::Borrowed(text));
+        }
+    }
+    encoding.map(|encoding| encoding.decode(bytes).0)
+}
+
+/// A step of the fallback chain for documents that don't declare a codepage,
+/// or declare one that's `0` or unknown
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fallback {
+    /// The usual codepage of the document's default language (`\deflang`,
+    /// or else `\adeflang`), if it has one other than Western European
+    Language,
+    Encoding(&'static Encoding),
+}
+
+impl std::str::FromStr for Fallback {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "deflang" => Ok(Fallback::Language),
+            _ => parse_encoding(s).map(Fallback::Encoding),
+        }
+    }
+}
+
+/// Look up an encoding by Windows codepage number, such as `1251`, or by
+/// label, such as `koi8-r`
+pub fn parse_encoding(name: &str) -> Result<&'static Encoding> {
+    let encoding = match name.parse::<u16>() {
+        Ok(cp) => codepage::to_encoding(cp),
+        Err(_) => Encoding::for_label(name.trim().as_bytes()),
+    };
+    encoding.ok_or_else(|| anyhow::anyhow!("Unrecognized codepage or encoding '{}'", name))
+}
+
+/// Work through the fallback chain for the first encoding that applies to
+/// the document, given the default languages it has declared so far, in order
+/// of preference
+pub fn fallback_encoding(chain: &[Fallback], languages: &[i32]) -> Option<&'static Encoding> {
+    let encoding = chain.iter().find_map(|fallback| match fallback {
+        Fallback::Language => languages
+            .iter()
+            .copied()
+            .find_map(language_codepage)
+            .and_then(codepage::to_encoding),
+        Fallback::Encoding(encoding) => Some(*encoding),
+    });
+    debug!(
+        "Fallback encoding is {}",
+        encoding.map_or("unset", Encoding::name)
+    );
+    encoding
+}
+
+/// The ANSI codepage Windows uses for a language ID, for the languages that
+/// don't use Western European (1252)
+fn language_codepage(lcid: i32) -> Option<u16> {
+    let primary = lcid & 0x3ff;
+    let cp = match primary {
+        // Serbian and Bosnian in Cyrillic script, with Croatian and the Latin
+        // scripts sharing the primary language
+        0x1a if matches!(lcid, 0x0c1a | 0x1c1a | 0x201a | 0x281a | 0x301a) => 1251,
+        0x1a => 1250,
+        // Bulgarian, Russian, Ukrainian, Belarusian, Macedonian, Kazakh,
+        // Kyrgyz, Tatar and Mongolian
+        0x02 | 0x19 | 0x22 | 0x23 | 0x2f | 0x3f | 0x40 | 0x44 | 0x50 => 1251,
+        // Czech, Hungarian, Polish, Romanian, Slovak, Albanian and Slovenian
+        0x05 | 0x0e | 0x15 | 0x18 | 0x1b | 0x1c | 0x24 => 1250,
+        0x08 => 1253,
+        // Turkish and Azeri
+        0x1f | 0x2c => 1254,
+        0x0d => 1255,
+        // Arabic, Urdu and Farsi
+        0x01 | 0x20 | 0x29 => 1256,
+        // Estonian, Latvian and Lithuanian
+        0x25..=0x27 => 1257,
+        0x2a => 1258,
+        0x1e => 874,
+        0x11 => 932,
+        // Simplified Chinese for the PRC and Singapore, otherwise Traditional
+        0x04 if matches!(lcid, 0x0804 | 0x1004) => 936,
+        0x04 => 950,
+        0x12 => 949,
+        _ => return None,
+    };
+    Some(cp)
+}
+
+/// A character set to write the extracted text in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Charset {
+    /// US-ASCII, which `encoding_rs` treats as an alias of windows-1252
+    Ascii,
+    /// ISO 8859-1 proper, which `encoding_rs` also treats as windows-1252
+    Latin1,
+    Encoding(&'static Encoding),
+}
+
+impl std::str::FromStr for Charset {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "ascii" | "us-ascii" | "20127" => Ok(Charset::Ascii),
+            "latin1" | "latin-1" | "l1" | "iso-8859-1" | "iso8859-1" | "iso_8859-1" | "28591" => {
+                Ok(Charset::Latin1)
+            }
+            _ => {
+                let encoding = parse_encoding(s)?;
+                if encoding.output_encoding() != encoding {
+                    bail!("Can't write text in {}", encoding.name());
+                }
+                Ok(Charset::Encoding(encoding))
+            }
+        }
+    }
+}
+
+impl Charset {
+    fn name(&self) -> &'static str {
+        match self {
+            Charset::Ascii => "US-ASCII",
+            Charset::Latin1 => "ISO-8859-1",
+            Charset::Encoding(encoding) => encoding.name(),
+        }
+    }
+}
+
+/// What to write in place of characters the output encoding can't represent
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum UnmappableMode {
+    /// Stop with an error
+    #[default]
+    Error,
+    /// Write `?`
+    Replace,
+    /// Write an HTML numeric character reference, like `&#8212;`
+    Escape,
+    /// Write an ASCII stand-in where there is one, like `--` for an em dash,
+    /// or else `?`
+    Transliterate,
+}
+
+impl std::str::FromStr for UnmappableMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "error" => Ok(UnmappableMode::Error),
+            "replace" => Ok(UnmappableMode::Replace),
+            "escape" => Ok(UnmappableMode::Escape),
+            "transliterate" => Ok(UnmappableMode::Transliterate),
+            _ => Err(anyhow::anyhow!(
+                "Unrecognized unmappable character mode '{}'",
+                s
+            )),
+        }
+    }
+}
+
+/// The encoding the extracted text is written in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OutputEncoding {
+    pub charset: Charset,
+    pub unmappable: UnmappableMode,
+}
+
+impl Default for OutputEncoding {
+    fn default() -> Self {
+        OutputEncoding {
+            charset: Charset::Encoding(encoding_rs::UTF_8),
+            unmappable: UnmappableMode::default(),
+        }
+    }
+}
+
+impl OutputEncoding {
+    fn is_utf8(&self) -> bool {
+        self.charset == Charset::Encoding(encoding_rs::UTF_8)
+    }
+
+    pub fn encode(&self, text: &str) -> Result<Vec<u8>> {
+        let mut bytes = Vec::with_capacity(text.len());
+        self.encode_into(text, self.unmappable, &mut bytes)?;
+        Ok(bytes)
+    }
+
+    fn encode_into(&self, text: &str, mode: UnmappableMode, bytes: &mut Vec<u8>) -> Result<()> {
+        let limit = match self.charset {
+            Charset::Ascii => 0x7f,
+            Charset::Latin1 => 0xff,
+            Charset::Encoding(encoding) => return self.encode_with(encoding, text, mode, bytes),
+        };
+        for c in text.chars() {
+            if u32::from(c) <= limit {
+                bytes.push(u32::from(c) as u8);
+            } else {
+                self.encode_unmappable(c, mode, bytes)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn encode_with(
+        &self,
+        encoding: &'static Encoding,
+        text: &str,
+        mode: UnmappableMode,
+        bytes: &mut Vec<u8>,
+    ) -> Result<()> {
+        let mut encoder = encoding.new_encoder();
+        let mut rest = text;
+        loop {
+            if let Some(needed) =
+                encoder.max_buffer_length_from_utf8_without_replacement(rest.len())
+            {
+                bytes.reserve(needed);
+            }
+            let (result, read) =
+                encoder.encode_from_utf8_to_vec_without_replacement(rest, bytes, true);
+            rest = &rest[read..];
+            match result {
+                EncoderResult::InputEmpty => return Ok(()),
+                EncoderResult::OutputFull => (),
+                EncoderResult::Unmappable(c) => self.encode_unmappable(c, mode, bytes)?,
+            }
+        }
+    }
+
+    fn encode_unmappable(&self, c: char, mode: UnmappableMode, bytes: &mut Vec<u8>) -> Result<()> {
+        match mode {
+            UnmappableMode::Error => bail!(
+                "Character U+{:04X} ({}) can't be written in {}",
+                u32::from(c),
+                c,
+                self.charset.name()
+            ),
+            UnmappableMode::Replace => bytes.push(b'?'),
+            UnmappableMode::Escape => bytes.extend(format!("&#{};", u32::from(c)).as_bytes()),
+            UnmappableMode::Transliterate => match transliterate::transliterate(c) {
+                Some(text) => self.encode_into(text, UnmappableMode::Replace, bytes)?,
+                None => bytes.push(b'?'),
+            },
+        }
+        Ok(())
+    }
+}
+
+/// Writes UTF-8 text out in another encoding, holding back any character
+/// that's split across writes until the rest of it arrives
+pub struct EncodingWriter<W: Write> {
+    inner: W,
+    encoding: OutputEncoding,
+    pending: Vec<u8>,
+}
+
+impl<W: Write> EncodingWriter<W> {
+    pub fn new(inner: W, encoding: OutputEncoding) -> Self {
+        EncodingWriter {
+            inner,
+            encoding,
+            pending: Vec::new(),
+        }
+    }
+
+    /// The underlying writer, for content that's already in its own encoding
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+}
+
+impl<W: Write> Write for EncodingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.encoding.is_utf8() {
+            return self.inner.write(buf);
+        }
+        self.pending.extend_from_slice(buf);
+        let complete = match std::str::from_utf8(&self.pending) {
+            Ok(text) => text.len(),
+            Err(error) if error.error_len().is_none() => error.valid_up_to(),
+            Err(error) => return Err(io::Error::new(io::ErrorKind::InvalidData, error)),
+        };
+        let text = std::str::from_utf8(&self.pending[..complete]).unwrap_or_default();
+        let bytes = self
+            .encoding
+            .encode(text)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error.to_string()))?;
+        self.inner.write_all(&bytes)?;
+        self.pending.drain(..complete);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn output(charset: &str, unmappable: UnmappableMode) -> OutputEncoding {
+        OutputEncoding {
+            charset: charset.parse().unwrap(),
+            unmappable,
+        }
+    }
+
+    #[test]
+    fn decodes_raw_utf8_in_single_byte_documents() {
+        let text = "caf\u{e9}".as_bytes();
+        let windows_1252 = Some(encoding_rs::WINDOWS_1252);
+        assert_eq!(decode_text(text, windows_1252, false).unwrap(), "caf\u{e9}");
+        assert_eq!(
+            decode_text(b"caf\xe9", windows_1252, false).unwrap(),
+            "caf\u{e9}"
+        );
+        assert_eq!(
+            decode_text(text, Some(encoding_rs::SHIFT_JIS), false).unwrap(),
+            "caf\u{ff83}\u{ff69}"
+        );
+        assert!(decode_text(b"caf\xe9", None, false).is_none());
+    }
+
+    #[test]
+    fn works_through_the_fallback_chain() {
+        let chain: Vec<Fallback> = ["deflang", "1252"]
+            .iter()
+            .map(|step| step.parse().unwrap())
+            .collect();
+        assert_eq!(
+            fallback_encoding(&chain, &[0x0419]),
+            Some(encoding_rs::WINDOWS_1251)
+        );
+        // English has no codepage of its own, so the next language is tried
+        assert_eq!(
+            fallback_encoding(&chain, &[0x0409, 0x0401]),
+            Some(encoding_rs::WINDOWS_1256)
+        );
+        assert_eq!(
+            fallback_encoding(&chain, &[0x0409]),
+            Some(encoding_rs::WINDOWS_1252)
+        );
+        assert_eq!(fallback_encoding(&chain[..1], &[]), None);
+        assert_eq!(language_codepage(0x0c1a), Some(1251));
+        assert_eq!(language_codepage(0x041a), Some(1250));
+        assert_eq!(language_codepage(0x0804), Some(936));
+        assert_eq!(language_codepage(0x0404), Some(950));
+    }
+
+    #[test]
+    fn rejects_unknown_encodings() {
+        assert!("deflangs".parse::<Fallback>().is_err());
+        assert!("65001".parse::<Fallback>().is_ok());
+        assert!("no-such-encoding".parse::<Charset>().is_err());
+        // encoding_rs can decode UTF-16 but not encode it
+        assert!("utf-16le".parse::<Charset>().is_err());
+        assert!("sometimes".parse::<UnmappableMode>().is_err());
+    }
+
+    #[test]
+    fn writes_unmappable_characters_as_asked() {
+        let text = "na\u{ef}ve \u{2014} \u{263a}";
+        assert!(output("ascii", UnmappableMode::Error).encode(text).is_err());
+        assert_eq!(
+            output("ascii", UnmappableMode::Replace)
+                .encode(text)
+                .unwrap(),
+            b"na?ve ? ?"
+        );
+        assert_eq!(
+            output("ascii", UnmappableMode::Escape)
+                .encode(text)
+                .unwrap(),
+            b"na&#239;ve &#8212; &#9786;"
+        );
+        assert_eq!(
+            output("ascii", UnmappableMode::Transliterate)
+                .encode(text)
+                .unwrap(),
+            b"naive -- ?"
+        );
+        assert_eq!(
+            output("latin1", UnmappableMode::Replace)
+                .encode(text)
+                .unwrap(),
+            b"na\xefve ? ?"
+        );
+        assert_eq!(
+            output("1252", UnmappableMode::Replace)
+                .encode(text)
+                .unwrap(),
+            b"na\xefve \x97 ?"
+        );
+        assert_eq!(
+            output("koi8-r", UnmappableMode::Escape)
+                .encode("\u{416} \u{2014}")
+                .unwrap(),
+            b"\xf6 &#8212;"
+        );
+    }
+
+    #[test]
+    fn holds_back_characters_split_across_writes() {
+        let mut writer = EncodingWriter::new(Vec::new(), output("latin1", UnmappableMode::Error));
+        let text = "caf\u{e9}".as_bytes();
+        writer.write_all(&text[..4]).unwrap();
+        assert_eq!(writer.get_mut(), b"caf");
+        writer.write_all(&text[4..]).unwrap();
+        assert_eq!(writer.get_mut(), b"caf\xe9");
+        assert!(writer.write_all(b"\xff").is_err());
+        assert!(writer.write_all("\u{2014}".as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,286 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::io::Write;
+
+use anyhow::{Context, Result};
+use log::{debug, warn};
+
+/// `\ffres` value meaning the field has no result, so its default applies
+const NO_RESULT: i32 = 25;
+
+/// The kind of form field, from `\fftype`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FormFieldType {
+    #[default]
+    Text,
+    Checkbox,
+    Dropdown,
+}
+
+impl FormFieldType {
+    fn from_value(value: Option<i32>) -> Self {
+        match value {
+            Some(1) => FormFieldType::Checkbox,
+            Some(2) => FormFieldType::Dropdown,
+            _ => FormFieldType::Text,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            FormFieldType::Text => "text",
+            FormFieldType::Checkbox => "checkbox",
+            FormFieldType::Dropdown => "dropdown",
+        }
+    }
+}
+
+/// A legacy form field, from the `\formfield` in a FORMTEXT, FORMCHECKBOX or
+/// FORMDROPDOWN field's instructions
+#[derive(Clone, Debug, Default)]
+pub struct FormField {
+    pub field_type: FormFieldType,
+    pub name: Option<String>,
+    /// The default text of a text field (`\ffdeftext`)
+    pub default_text: Option<String>,
+    /// The entries of a dropdown (`\ffl`)
+    pub options: Vec<String>,
+    pub help_text: Option<String>,
+    pub status_text: Option<String>,
+    /// The checked state or selected entry (`\ffres`)
+    pub result: Option<i32>,
+    /// The default checked state or selected entry (`\ffdefres`)
+    pub default_result: Option<i32>,
+    /// The text of the field result, which holds a text field's value
+    pub result_text: String,
+}
+
+impl FormField {
+    /// Whether a checkbox is checked, falling back on its default state
+    pub fn checked(&self) -> bool {
+        self.effective_result() == Some(1)
+    }
+
+    /// The selected entry of a dropdown, falling back on its default selection
+    pub fn selected_option(&self) -> Option<&str> {
+        let index = self.effective_result().unwrap_or(0);
+        self.options
+            .get(usize::try_from(index).ok()?)
+            .map(String::as_str)
+    }
+
+    fn effective_result(&self) -> Option<i32> {
+        self.result
+            .filter(|result| *result != NO_RESULT)
+            .or(self.default_result)
+    }
+
+    /// The current value of the field, as written to the JSON dump
+    pub fn value(&self) -> serde_json::Value {
+        match self.field_type {
+            FormFieldType::Text => serde_json::json!(self.result_text.trim()),
+            FormFieldType::Checkbox => serde_json::json!(self.checked()),
+            FormFieldType::Dropdown => serde_json::json!(self.selected_option()),
+        }
+    }
+
+    /// The text standing in for the field in the document text, for fields
+    /// whose value isn't already written as the field result
+    fn inline_text(&self) -> Option<String> {
+        match self.field_type {
+            FormFieldType::Checkbox if self.checked() => Some("[X]".to_owned()),
+            FormFieldType::Checkbox => Some("[ ]".to_owned()),
+            FormFieldType::Dropdown if self.result_text.trim().is_empty() => {
+                self.selected_option().map(str::to_owned)
+            }
+            _ => None,
+        }
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "name": self.name,
+            "type": self.field_type.name(),
+            "value": self.value(),
+            "default": match self.field_type {
+                FormFieldType::Text => serde_json::json!(self.default_text),
+                FormFieldType::Checkbox => serde_json::json!(self.default_result == Some(1)),
+                FormFieldType::Dropdown => serde_json::json!(self
+                    .default_result
+                    .and_then(|index| self.options.get(usize::try_from(index).ok()?))),
+            },
+            "options": self.options,
+            "help_text": self.help_text,
+            "status_text": self.status_text,
+        })
+    }
+}
+
+/// A `\field` or `\fldrslt` group that is still open
+#[derive(Clone, Debug)]
+enum FieldPart {
+    Field {
+        depth: usize,
+        form_field: Option<FormField>,
+        /// The character a `SYMBOL` field inserts, until its result turns
+        /// out to show it already
+        symbol: Option<String>,
+    },
+    Result {
+        depth: usize,
+        destination: String,
+        start: usize,
+    },
+}
+
+/// Tracks the fields enclosing the current position, and gathers the form
+/// fields among them as they close, along with the characters of `SYMBOL`
+/// fields with no result
+#[derive(Clone, Default)]
+pub struct FormFieldCollector {
+    parts: Vec<FieldPart>,
+    pending: FormField,
+    /// Control words from the `\formfield` group, which Word writes in a
+    /// group of their own inside it
+    pending_properties: HashMap<String, Option<i32>>,
+    form_fields: Vec<FormField>,
+}
+
+impl FormFieldCollector {
+    pub fn handles_destination(name: &str) -> bool {
+        matches!(
+            name,
+            "formfield" | "ffname" | "ffdeftext" | "ffl" | "ffhelptext" | "ffstattext"
+        )
+    }
+
+    /// Note a `\field` opening in the group at `depth`
+    pub fn open_field(&mut self, depth: usize) {
+        self.parts.push(FieldPart::Field {
+            depth,
+            form_field: None,
+            symbol: None,
+        });
+    }
+
+    /// Note a `\fldrslt` opening in the group at `depth`, whose text starts at
+    /// `start` in `destination`
+    pub fn open_result(&mut self, depth: usize, destination: &str, start: usize) {
+        self.parts.push(FieldPart::Result {
+            depth,
+            destination: destination.to_owned(),
+            start,
+        });
+    }
+
+    /// If the innermost open field result belongs to a group deeper than
+    /// `depth`, close it, returning the destination and position it started at
+    pub fn close_result(&mut self, depth: usize) -> Option<(String, usize)> {
+        match self.parts.last() {
+            Some(FieldPart::Result { depth: open, .. }) if *open > depth => (),
+            _ => return None,
+        }
+        match self.parts.pop() {
+            Some(FieldPart::Result {
+                destination, start, ..
+            }) => Some((destination, start)),
+            _ => None,
+        }
+    }
+
+    /// Record the text of the field result that has just closed
+    pub fn set_result_text(&mut self, text: &str) {
+        if let Some(FieldPart::Field {
+            form_field, symbol, ..
+        }) = self.parts.last_mut()
+        {
+            if let Some(form_field) = form_field {
+                form_field.result_text.push_str(text);
+            }
+            if !text.is_empty() {
+                *symbol = None;
+            }
+        }
+    }
+
+    /// Note the character the innermost open field inserts, if it's a `SYMBOL` field
+    pub fn set_symbol(&mut self, text: String) {
+        if let Some(FieldPart::Field { symbol, .. }) = self.parts.last_mut() {
+            *symbol = Some(text);
+        }
+    }
+
+    /// If the innermost open field belongs to a group deeper than `depth`,
+    /// close it, returning the text to write in its place if it's a form
+    /// field, or a `SYMBOL` field with no result
+    pub fn close_field(&mut self, depth: usize) -> Option<String> {
+        match self.parts.last() {
+            Some(FieldPart::Field { depth: open, .. }) if *open > depth => (),
+            _ => return None,
+        }
+        match self.parts.pop() {
+            Some(FieldPart::Field {
+                form_field: Some(form_field),
+                ..
+            }) => {
+                debug!(
+                    "Completed {:?} form field {:?}",
+                    form_field.field_type, form_field.name
+                );
+                let text = form_field.inline_text();
+                self.form_fields.push(form_field);
+                text
+            }
+            Some(FieldPart::Field { symbol, .. }) => symbol,
+            _ => None,
+        }
+    }
+
+    /// Note the control words set in a group nested directly inside `\formfield`
+    pub fn add_properties(&mut self, properties: HashMap<String, Option<i32>>) {
+        self.pending_properties.extend(properties);
+    }
+
+    /// Handle the contents of a completed form field destination, with the
+    /// control words set in its group
+    pub fn fold(&mut self, name: &str, contents: &str, properties: &HashMap<String, Option<i32>>) {
+        let text = || Some(contents.trim().to_owned());
+        match name {
+            "ffname" => self.pending.name = text(),
+            "ffdeftext" => self.pending.default_text = text(),
+            "ffl" => self.pending.options.push(contents.trim().to_owned()),
+            "ffhelptext" => self.pending.help_text = text(),
+            "ffstattext" => self.pending.status_text = text(),
+            "formfield" => {
+                let mut form_field = std::mem::take(&mut self.pending);
+                let mut properties_set = std::mem::take(&mut self.pending_properties);
+                properties_set.extend(properties.clone());
+                let value = |name: &str| properties_set.get(name).copied().flatten();
+                form_field.field_type = FormFieldType::from_value(value("fftype"));
+                form_field.result = value("ffres");
+                form_field.default_result = value("ffdefres");
+                match self.parts.iter_mut().rev().find_map(|part| match part {
+                    FieldPart::Field { form_field, .. } => Some(form_field),
+                    FieldPart::Result { .. } => None,
+                }) {
+                    Some(slot) => *slot = Some(form_field),
+                    None => warn!("Form field data found outside of any field"),
+                }
+            }
+            _ => panic!(
+                "Programmer error: {} was routed to the form field collector without a handler",
+                name
+            ),
+        }
+    }
+
+    pub fn finish(self) -> Vec<FormField> {
+        self.form_fields
+    }
+}
+
+/// Write each form field's name, type and value as a JSON array
+pub fn write_json<W: Write>(form_fields: &[FormField], writer: W) -> Result<()> {
+    let list: Vec<serde_json::Value> = form_fields.iter().map(FormField::to_json).collect();
+    serde_json::to_writer_pretty(writer, &list).context("Error writing form field list")
+}
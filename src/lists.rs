@@ -0,0 +1,204 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+use log::{debug, warn};
+
+/// A level of a list from the `\listtable`
+#[derive(Clone, Debug, Default)]
+struct ListLevel {
+    /// Cocoa's template for the level's marker, such as `{decimal}.`
+    marker: Option<String>,
+    start_at: i32,
+}
+
+/// Gathers the list definitions from the `\listtable` and
+/// `\listoverridetable`, and numbers the list items that use them
+#[derive(Clone, Default)]
+pub struct ListCollector {
+    /// Levels of each list, by `\listid`
+    lists: HashMap<i32, Vec<ListLevel>>,
+    /// The `\listid` that each `\ls` list override refers to
+    overrides: HashMap<i32, i32>,
+    pending_levels: Vec<ListLevel>,
+    pending_marker: Option<String>,
+    /// The number of the last item at each level of each list override
+    counters: HashMap<i32, Vec<Option<i32>>>,
+}
+
+impl ListCollector {
+    pub fn handles_destination(name: &str) -> bool {
+        matches!(name, "levelmarker" | "listlevel" | "list" | "listoverride")
+    }
+
+    pub fn fold(&mut self, name: &str, contents: &str, properties: &HashMap<String, Option<i32>>) {
+        let value = |name: &str| properties.get(name).copied().flatten();
+        match name {
+            "levelmarker" => self.pending_marker = Some(contents.trim().to_owned()),
+            "listlevel" => self.pending_levels.push(ListLevel {
+                marker: self.pending_marker.take(),
+                start_at: value("levelstartat").unwrap_or(1),
+            }),
+            "list" => {
+                let levels = std::mem::take(&mut self.pending_levels);
+                match value("listid") {
+                    Some(id) => {
+                        debug!("List {} has {} levels", id, levels.len());
+                        self.lists.insert(id, levels);
+                    }
+                    None => warn!("Skipping list with no \\listid"),
+                }
+            }
+            "listoverride" => {
+                if let (Some(id), Some(ls)) = (value("listid"), value("ls")) {
+                    self.overrides.insert(ls, id);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    /// The marker of the next item at level `ilvl` of list override `ls`,
+    /// if the list level has a Cocoa `\levelmarker` to build it from
+    ///
+    /// Numbering starts over at the deeper levels after each item.
+    pub fn next_marker(&mut self, ls: i32, ilvl: i32) -> Option<String> {
+        let level_index = usize::try_from(ilvl).ok()?;
+        let list_id = self.overrides.get(&ls)?;
+        let level = self.lists.get(list_id)?.get(level_index)?;
+        let template = level.marker.as_ref()?;
+
+        let counters = self.counters.entry(ls).or_default();
+        counters.resize(level_index + 1, None);
+        let number =
+            counters[level_index].map_or(level.start_at, |number| number.saturating_add(1));
+        counters[level_index] = Some(number);
+        Some(expand_marker(template, number))
+    }
+}
+
+/// Fill in the `{format}` placeholders of a Cocoa list marker template
+fn expand_marker(template: &str, number: i32) -> String {
+    let mut marker = String::new();
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        let close = match rest[open..].find('}') {
+            Some(close) => open + close,
+            None => break,
+        };
+        marker.push_str(&rest[..open]);
+        marker.push_str(&format_number(&rest[open + 1..close], number));
+        rest = &rest[close + 1..];
+    }
+    marker.push_str(rest);
+    marker
+}
+
+/// Format a list item number in one of the marker formats of `NSTextList`
+fn format_number(format: &str, number: i32) -> String {
+    match format {
+        "disc" => "\u{2022}".to_owned(),
+        "circle" => "\u{25E6}".to_owned(),
+        "square" => "\u{25AA}".to_owned(),
+        "diamond" => "\u{25C6}".to_owned(),
+        "hyphen" => "\u{2043}".to_owned(),
+        "check" => "\u{2713}".to_owned(),
+        "box" => "\u{2610}".to_owned(),
+        "decimal" => number.to_string(),
+        "decimal-leading-zero" => format!("{:02}", number),
+        "octal" => format!("{:o}", number),
+        "lower-hexadecimal" => format!("{:x}", number),
+        "upper-hexadecimal" => format!("{:X}", number),
+        "lower-alpha" | "lower-latin" => alphabetic(number),
+        "upper-alpha" | "upper-latin" => alphabetic(number).to_uppercase(),
+        "lower-roman" => roman(number),
+        "upper-roman" => roman(number).to_uppercase(),
+        _ => {
+            warn!("Unsupported list marker format {{{}}}", format);
+            number.to_string()
+        }
+    }
+}
+
+/// a, b, ... z, aa, ab, ...
+fn alphabetic(number: i32) -> String {
+    let mut number = number.max(1) as u32;
+    let mut letters = Vec::new();
+    while number > 0 {
+        number -= 1;
+        letters.push(char::from(b'a' + (number % 26) as u8));
+        number /= 26;
+    }
+    letters.iter().rev().collect()
+}
+
+fn roman(number: i32) -> String {
+    const NUMERALS: &[(i32, &str)] = &[
+        (1000, "m"),
+        (900, "cm"),
+        (500, "d"),
+        (400, "cd"),
+        (100, "c"),
+        (90, "xc"),
+        (50, "l"),
+        (40, "xl"),
+        (10, "x"),
+        (9, "ix"),
+        (5, "v"),
+        (4, "iv"),
+        (1, "i"),
+    ];
+    let mut number = number;
+    if number <= 0 {
+        return number.to_string();
+    }
+    let mut numeral = String::new();
+    for (value, digits) in NUMERALS {
+        while number >= *value {
+            numeral.push_str(digits);
+            number -= value;
+        }
+    }
+    numeral
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn collector(start_at: i32) -> ListCollector {
+        let mut lists = ListCollector::default();
+        let properties = |values: &[(&str, i32)]| {
+            values
+                .iter()
+                .map(|(name, value)| (name.to_string(), Some(*value)))
+                .collect::<HashMap<_, _>>()
+        };
+        lists.fold("levelmarker", "{decimal}.", &HashMap::new());
+        lists.fold("listlevel", "", &properties(&[("levelstartat", start_at)]));
+        lists.fold("list", "", &properties(&[("listid", 7)]));
+        lists.fold("listoverride", "", &properties(&[("listid", 7), ("ls", 1)]));
+        lists
+    }
+
+    #[test]
+    fn numbers_list_items() {
+        let mut lists = collector(1);
+        assert_eq!(lists.next_marker(1, 0).as_deref(), Some("1."));
+        assert_eq!(lists.next_marker(1, 0).as_deref(), Some("2."));
+        assert_eq!(lists.next_marker(2, 0), None);
+    }
+
+    #[test]
+    fn stops_counting_at_the_largest_number() {
+        let mut lists = collector(i32::MAX);
+        assert_eq!(lists.next_marker(1, 0).as_deref(), Some("2147483647."));
+        assert_eq!(lists.next_marker(1, 0).as_deref(), Some("2147483647."));
+    }
+
+    #[test]
+    fn expands_marker_formats() {
+        assert_eq!(expand_marker("{lower-roman})", 14), "xiv)");
+        assert_eq!(expand_marker("({upper-alpha}", 28), "(AB");
+        assert_eq!(expand_marker("{disc}", 3), "\u{2022}");
+    }
+}
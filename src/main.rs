@@ -1,18 +1,43 @@
+use std::io::{Read, Write};
 use std::{fs, io, path};
 
 use anyhow::{Context, Result};
 use flexi_logger::{detailed_format, Logger};
 use log::debug;
 
+mod annotations;
+mod bytes;
+mod colors;
+mod compressed;
+mod containers;
+mod encapsulation;
+mod encodings;
+mod forms;
+mod lists;
+mod markdown;
+mod math;
+mod mime;
+mod msg;
+mod objects;
+mod pictures;
+mod revisions;
 mod rtf_control;
+mod rtfd;
 mod rtftotext;
+mod sections;
+mod shapes;
+mod stylesheet;
+mod symbols;
+mod tnef;
+mod transliterate;
 
 fn main() -> Result<()> {
     let app = clap::command!("")
         .setting(clap::AppSettings::ColorAuto)
         .setting(clap::AppSettings::ColoredHelp)
+        .after_help("When the input holds several documents, such as a message with RTF attachments, the JSON files and the extracted pictures, objects and attachments describe only the first one (the message body).")
         .arg(clap::Arg::with_name("input-file")
-            .help("Filename of Rich Text File to convert to text, or leave unset to read from stdin")
+            .help("Filename of Rich Text File, Outlook message (.msg), TNEF stream (winmail.dat) or MIME message (.eml), or RTFD bundle to convert to text, or leave unset to read from stdin")
             .short('i')
             .long("input-file")
             .takes_value(true)
@@ -23,6 +48,130 @@ fn main() -> Result<()> {
             .long("output-file")
             .takes_value(true)
             .value_name("OUTPUT-FILE"))
+        .arg(clap::Arg::with_name("format")
+            .help("Format of the extracted text")
+            .short('f')
+            .long("format")
+            .takes_value(true)
+            .possible_values(["text", "markdown"])
+            .default_value("text")
+            .value_name("FORMAT"))
+        .arg(clap::Arg::with_name("encapsulated")
+            .help("How to handle documents that encapsulate HTML or plain text (\\fromhtml, \\fromtext): write the original, convert it to text, or convert the RTF rendering")
+            .long("encapsulated")
+            .takes_value(true)
+            .possible_values(["original", "text", "rtf"])
+            .default_value("original")
+            .value_name("MODE"))
+        .arg(clap::Arg::with_name("paragraphs-json")
+            .help("Filename to write each paragraph's style name and heading level to, as a JSON list")
+            .long("paragraphs-json")
+            .takes_value(true)
+            .value_name("JSON-FILE"))
+        .arg(clap::Arg::with_name("sections-json")
+            .help("Filename to write each section's page style, headers and footers to, as a JSON list")
+            .long("sections-json")
+            .takes_value(true)
+            .value_name("JSON-FILE"))
+        .arg(clap::Arg::with_name("extract-images")
+            .help("Directory to write the document's pictures to, as numbered image files")
+            .long("extract-images")
+            .takes_value(true)
+            .value_name("DIR"))
+        .arg(clap::Arg::with_name("image-placeholders")
+            .help("Write a placeholder with the filename, size and description wherever a picture appears")
+            .long("image-placeholders"))
+        .arg(clap::Arg::with_name("picture-alternative")
+            .help("Which copy of each picture to use, when the document has both a shape (\\shppict) and a legacy (\\nonshppict) version")
+            .long("picture-alternative")
+            .takes_value(true)
+            .possible_values(["shppict", "nonshppict"])
+            .default_value("shppict")
+            .value_name("ALTERNATIVE"))
+        .arg(clap::Arg::with_name("extract-objects")
+            .help("Directory to write embedded OLE objects to, using the original filename for packaged files")
+            .long("extract-objects")
+            .takes_value(true)
+            .value_name("DIR"))
+        .arg(clap::Arg::with_name("objects-json")
+            .help("Filename to write the class and name of each embedded OLE object to, as a JSON list")
+            .long("objects-json")
+            .takes_value(true)
+            .value_name("JSON-FILE"))
+        .arg(clap::Arg::with_name("extract-attachments")
+            .help("Directory to copy the attachment files of an RTFD bundle to")
+            .long("extract-attachments")
+            .takes_value(true)
+            .value_name("DIR"))
+        .arg(clap::Arg::with_name("attachments-json")
+            .help("Filename to write the filename and size of each attachment (\\NeXTGraphic) to, as a JSON list")
+            .long("attachments-json")
+            .takes_value(true)
+            .value_name("JSON-FILE"))
+        .arg(clap::Arg::with_name("include-shape-text")
+            .help("Include the text of text boxes, callouts and WordArt where each shape is anchored")
+            .long("include-shape-text"))
+        .arg(clap::Arg::with_name("form-fields-json")
+            .help("Filename to write the name, type and value of each form field to, as a JSON list")
+            .long("form-fields-json")
+            .takes_value(true)
+            .value_name("JSON-FILE"))
+        .arg(clap::Arg::with_name("annotations")
+            .help("How review comments are written to the extracted text")
+            .long("annotations")
+            .takes_value(true)
+            .possible_values(["omit", "inline"])
+            .default_value("omit")
+            .value_name("MODE"))
+        .arg(clap::Arg::with_name("annotations-json")
+            .help("Filename to write review comments to, as a JSON list")
+            .long("annotations-json")
+            .takes_value(true)
+            .value_name("JSON-FILE"))
+        .arg(clap::Arg::with_name("revisions")
+            .help("How tracked changes are written to the extracted text")
+            .long("revisions")
+            .takes_value(true)
+            .possible_values(["accept", "reject", "show", "diff"])
+            .default_value("accept")
+            .value_name("MODE"))
+        .arg(clap::Arg::with_name("include-hidden")
+            .help("Include hidden text in the extracted text, rather than suppressing it")
+            .long("include-hidden"))
+        .arg(clap::Arg::with_name("assume-utf8")
+            .help("Decode text that's valid UTF-8 as UTF-8, whatever codepage the document declares (by default, only when it declares a single-byte codepage)")
+            .long("assume-utf8"))
+        .arg(clap::Arg::with_name("codepage")
+            .help("Windows codepage to decode the document's text in, whatever codepage it declares")
+            .long("codepage")
+            .takes_value(true)
+            .conflicts_with("encoding")
+            .value_name("N"))
+        .arg(clap::Arg::with_name("encoding")
+            .help("Encoding to decode the document's text in, such as koi8-r, whatever codepage it declares")
+            .long("encoding")
+            .takes_value(true)
+            .value_name("NAME"))
+        .arg(clap::Arg::with_name("fallback-encodings")
+            .help("Comma-separated codepages or encodings to try, in order, when the document doesn't declare a known codepage, where 'deflang' picks one from the document's default language")
+            .long("fallback-encodings")
+            .takes_value(true)
+            .use_value_delimiter(true)
+            .default_value("deflang,1252")
+            .value_name("LIST"))
+        .arg(clap::Arg::with_name("output-encoding")
+            .help("Encoding to write the extracted text in, such as windows-1252, latin1 or ascii (JSON files are always UTF-8, and encapsulated HTML written as the original keeps the charset it declares)")
+            .long("output-encoding")
+            .takes_value(true)
+            .default_value("utf-8")
+            .value_name("NAME"))
+        .arg(clap::Arg::with_name("unmappable")
+            .help("What to do with characters the output encoding can't represent: stop with an error, replace them with '?', write them as numeric escapes (&#8212;), or transliterate them to ASCII where possible")
+            .long("unmappable")
+            .takes_value(true)
+            .possible_values(["error", "replace", "escape", "transliterate"])
+            .default_value("error")
+            .value_name("MODE"))
         .arg(clap::Arg::with_name("debug")
             .short('g')
             .long("debug")
@@ -57,12 +206,71 @@ fn main() -> Result<()> {
 
     debug!("{} version {}", clap::crate_name!(), clap::crate_version!());
 
+    let format = matches.value_of("format").unwrap_or("text");
+    let (placeholder_style, math_style) = match format {
+        "markdown" => (pictures::PlaceholderStyle::Markdown, math::MathStyle::Latex),
+        _ => (pictures::PlaceholderStyle::Text, math::MathStyle::Linear),
+    };
+    let options = rtftotext::ConvertOptions {
+        annotations: matches.value_of_t("annotations")?,
+        revisions: matches.value_of_t("revisions")?,
+        include_hidden: matches.is_present("include-hidden"),
+        image_placeholders: matches
+            .is_present("image-placeholders")
+            .then_some(placeholder_style),
+        picture_alternative: matches.value_of_t("picture-alternative")?,
+        include_shape_text: matches.is_present("include-shape-text"),
+        math: math_style,
+        assume_utf8: matches.is_present("assume-utf8"),
+        forced_encoding: match (matches.value_of("codepage"), matches.value_of("encoding")) {
+            (Some(name), _) | (None, Some(name)) => Some(encodings::parse_encoding(name)?),
+            (None, None) => None,
+        },
+        fallback_encodings: matches.values_of_t("fallback-encodings")?,
+    };
+
+    let output_encoding = encodings::OutputEncoding {
+        charset: matches.value_of_t("output-encoding")?,
+        unmappable: matches.value_of_t("unmappable")?,
+    };
+
+    let side_outputs = SideOutputs {
+        annotations_json: matches.value_of("annotations-json"),
+        paragraphs_json: matches.value_of("paragraphs-json"),
+        sections_json: matches.value_of("sections-json"),
+        image_dir: matches.value_of("extract-images"),
+        object_dir: matches.value_of("extract-objects"),
+        objects_json: matches.value_of("objects-json"),
+        attachment_dir: matches.value_of("extract-attachments"),
+        attachments_json: matches.value_of("attachments-json"),
+        form_fields_json: matches.value_of("form-fields-json"),
+    };
+
     convert(
         matches.value_of("input-file"),
         matches.value_of("output-file"),
+        format,
+        matches.value_of_t("encapsulated")?,
+        output_encoding,
+        &side_outputs,
+        &options,
     )
 }
 
+/// Files written alongside the extracted text
+#[derive(Default)]
+struct SideOutputs<'a> {
+    annotations_json: Option<&'a str>,
+    paragraphs_json: Option<&'a str>,
+    sections_json: Option<&'a str>,
+    image_dir: Option<&'a str>,
+    object_dir: Option<&'a str>,
+    objects_json: Option<&'a str>,
+    attachment_dir: Option<&'a str>,
+    attachments_json: Option<&'a str>,
+    form_fields_json: Option<&'a str>,
+}
+
 fn make_input_reader(infile: Option<&str>) -> Result<io::BufReader<Box<dyn io::Read>>> {
     let inpath = infile.map(path::PathBuf::from);
     let reader = io::BufReader::new(match inpath {
@@ -89,19 +297,189 @@ fn make_output_writer(outfile: Option<&str>) -> Result<io::BufWriter<Box<dyn io:
     Ok(writer)
 }
 
-fn convert(infile: Option<&str>, outfile: Option<&str>) -> Result<()> {
-    let reader = make_input_reader(infile)?;
-    let writer = make_output_writer(outfile)?;
+fn convert(
+    infile: Option<&str>,
+    outfile: Option<&str>,
+    format: &str,
+    encapsulation: encapsulation::EncapsulationMode,
+    output_encoding: encodings::OutputEncoding,
+    side_outputs: &SideOutputs,
+    options: &rtftotext::ConvertOptions,
+) -> Result<()> {
+    let mut writer = encodings::EncodingWriter::new(make_output_writer(outfile)?, output_encoding);
     if let Some(inpath) = infile {
-        debug!("Parsing {} as rtf.", inpath);
+        debug!("Reading {}.", inpath);
     } else {
-        debug!("Parsing <stdin> as rtf.");
+        debug!("Reading <stdin>.");
     }
     if let Some(outpath) = outfile {
         debug!("Writing parsed text to {}.", outpath);
     } else {
         debug!("Writing parsed text to <stdout>.");
     }
-    let tokens = rtftotext::tokenize(reader)?;
-    rtftotext::write_plaintext(&tokens, writer)
+    let documents = match infile.map(path::Path::new) {
+        Some(inpath) if inpath.is_dir() => {
+            debug!("Reading {} as an RTFD bundle.", inpath.display());
+            containers::extract_bundle(inpath)?
+        }
+        _ => {
+            let mut data = Vec::new();
+            make_input_reader(infile)?
+                .read_to_end(&mut data)
+                .context("Error reading from input file")?;
+            containers::extract_documents(data)?
+        }
+    };
+    let labelled = documents.len() > 1;
+    for (index, document) in documents.iter().enumerate() {
+        debug!("Parsing {} as rtf.", document.label);
+        if labelled {
+            if index > 0 {
+                writeln!(writer).context("Error writing to output file")?;
+            }
+            writeln!(writer, "--- {} ---", document.label)
+                .context("Error writing to output file")?;
+        }
+        // Side outputs describe the main document, which comes first
+        let no_side_outputs = SideOutputs::default();
+        let document_side_outputs = if index == 0 {
+            side_outputs
+        } else {
+            &no_side_outputs
+        };
+        convert_document(
+            document,
+            &mut writer,
+            format,
+            encapsulation,
+            document_side_outputs,
+            options,
+        )?;
+    }
+    Ok(())
+}
+
+fn convert_document<W: io::Write>(
+    source: &containers::SourceDocument,
+    writer: &mut encodings::EncodingWriter<W>,
+    format: &str,
+    encapsulation: encapsulation::EncapsulationMode,
+    side_outputs: &SideOutputs,
+    options: &rtftotext::ConvertOptions,
+) -> Result<()> {
+    let tokens = rtftotext::tokenize(source.data.as_slice())?;
+    let document = rtftotext::parse_document(&tokens, options);
+    if let Some(json_path) = side_outputs.annotations_json {
+        debug!("Writing annotations to {}.", json_path);
+        let json_writer = make_output_writer(Some(json_path))?;
+        annotations::write_json(&document.annotations, json_writer)?;
+    }
+    if let Some(json_path) = side_outputs.paragraphs_json {
+        debug!("Writing paragraph styles to {}.", json_path);
+        let json_writer = make_output_writer(Some(json_path))?;
+        rtftotext::write_paragraphs_json(&document, json_writer)?;
+    }
+    if let Some(json_path) = side_outputs.sections_json {
+        debug!("Writing sections to {}.", json_path);
+        let json_writer = make_output_writer(Some(json_path))?;
+        sections::write_json(&document.sections, &document.page_styles, json_writer)?;
+    }
+    if let Some(image_dir) = side_outputs.image_dir {
+        debug!("Writing pictures to {}.", image_dir);
+        pictures::write_images(&document.pictures, image_dir)?;
+    }
+    if let Some(object_dir) = side_outputs.object_dir {
+        debug!("Writing embedded objects to {}.", object_dir);
+        objects::write_objects(&document.objects, object_dir)?;
+    }
+    if let Some(json_path) = side_outputs.objects_json {
+        debug!("Writing embedded object list to {}.", json_path);
+        let json_writer = make_output_writer(Some(json_path))?;
+        objects::write_json(&document.objects, json_writer)?;
+    }
+    if let Some(attachment_dir) = side_outputs.attachment_dir {
+        debug!("Copying attachments to {}.", attachment_dir);
+        rtfd::write_attachments(&document.attachments, &source.files, attachment_dir)?;
+    }
+    if let Some(json_path) = side_outputs.attachments_json {
+        debug!("Writing attachment list to {}.", json_path);
+        let json_writer = make_output_writer(Some(json_path))?;
+        rtfd::write_json(&document.attachments, &source.files, json_writer)?;
+    }
+    if let Some(json_path) = side_outputs.form_fields_json {
+        debug!("Writing form fields to {}.", json_path);
+        let json_writer = make_output_writer(Some(json_path))?;
+        forms::write_json(&document.form_fields, json_writer)?;
+    }
+    if encapsulation != encapsulation::EncapsulationMode::Rtf {
+        if let Some(encapsulated_format) = encapsulation::detect(&tokens) {
+            debug!("Writing encapsulated {:?} content.", encapsulated_format);
+            let original = encapsulation::de_encapsulate(
+                &tokens,
+                encapsulated_format,
+                options.forced_encoding,
+            );
+            // Original HTML is written in the charset it declares, and any
+            // other content in the output encoding
+            return match (encapsulation, original.format) {
+                (
+                    encapsulation::EncapsulationMode::Original,
+                    encapsulation::EncapsulatedFormat::Html,
+                ) => encapsulation::write_original(&original, encapsulation, writer.get_mut()),
+                _ => encapsulation::write_original(&original, encapsulation, writer),
+            };
+        }
+    }
+    match format {
+        "markdown" => markdown::write_markdown(&document, writer),
+        _ => rtftotext::write_plaintext(&document, writer),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn convert_with(
+        rtf: &[u8],
+        charset: &str,
+        unmappable: encodings::UnmappableMode,
+    ) -> Result<Vec<u8>> {
+        let source = containers::SourceDocument::new("test", rtf.to_vec());
+        let output_encoding = encodings::OutputEncoding {
+            charset: charset.parse()?,
+            unmappable,
+        };
+        let mut writer = encodings::EncodingWriter::new(Vec::new(), output_encoding);
+        convert_document(
+            &source,
+            &mut writer,
+            "text",
+            encapsulation::EncapsulationMode::Original,
+            &SideOutputs::default(),
+            &rtftotext::ConvertOptions::default(),
+        )?;
+        Ok(std::mem::take(writer.get_mut()))
+    }
+
+    #[test]
+    fn writes_text_in_the_output_encoding() {
+        use encodings::UnmappableMode::*;
+        let rtf = b"{\\rtf1\\ansi caf\\'e9 \\emdash  \\u26085?\\par}";
+        assert_eq!(
+            convert_with(rtf, "latin1", Replace).unwrap(),
+            b"caf\xe9 ? ?\n"
+        );
+        assert_eq!(
+            convert_with(rtf, "ascii", Transliterate).unwrap(),
+            b"cafe -- ?\n"
+        );
+        assert!(convert_with(rtf, "ascii", Error).is_err());
+        // Decapsulated plain text goes through the output encoding too
+        let text = b"{\\rtf1\\ansi\\fromtext caf\\'e9\\par}";
+        assert_eq!(convert_with(text, "latin1", Error).unwrap(), b"caf\xe9\r\n");
+        // while original HTML keeps the charset of the document
+        let html = b"{\\rtf1\\ansi\\ansicpg1251\\fromhtml1 {\\*\\htmltag <p>}\\'cf}";
+        assert_eq!(convert_with(html, "utf-8", Error).unwrap(), b"<p>\xcf");
+    }
 }
@@ -0,0 +1,203 @@
+use std::io::Write;
+
+use anyhow::{Context, Result};
+use log::debug;
+
+use crate::colors::Color;
+use crate::rtftotext::{CharacterFormat, Document, TextRun};
+
+/// Write the document text as markdown, marking up paragraphs that have a
+/// heading level as ATX headings, bold, italic and struck-through runs with
+/// emphasis, and colored runs with HTML spans
+pub fn write_markdown<W: Write>(document: &Document, mut writer: W) -> Result<()> {
+    debug!("Writing rtf1 content as markdown...");
+    let emphasis_runs = emphasis_runs(&document.runs);
+    let mut runs = emphasis_runs.iter().peekable();
+    for paragraph in &document.paragraphs {
+        let text = document.paragraph_text(paragraph);
+        let heading = match document.heading_level(paragraph) {
+            Some(level) if !text.trim().is_empty() => Some(level),
+            _ => None,
+        };
+        if let Some(level) = heading {
+            write!(writer, "{} ", "#".repeat(level as usize + 1))
+                .context("Error writing to output file")?;
+        }
+
+        let mut pos = paragraph.start;
+        if heading.is_some() {
+            pos += text.len() - text.trim_start().len();
+        }
+        while let Some(run) = runs.peek() {
+            if run.start >= paragraph.end {
+                break;
+            }
+            let start = run.start.max(pos);
+            let end = run.end.min(paragraph.end);
+            if start < end {
+                writer
+                    .write_all(&document.text.as_bytes()[pos..start])
+                    .context("Error writing to output file")?;
+                // Headings are already emphasized, so don't pile more onto them
+                let format = match heading {
+                    Some(_) => CharacterFormat::default(),
+                    None => run.format,
+                };
+                let color = document.run_color(&format);
+                write_run(&mut writer, &document.text[start..end], format, color)?;
+                pos = end;
+            }
+            if run.end > paragraph.end {
+                break;
+            }
+            runs.next();
+        }
+        writer
+            .write_all(&document.text.as_bytes()[pos..paragraph.end])
+            .context("Error writing to output file")?;
+    }
+    Ok(())
+}
+
+/// The document's runs with fonts and sizes, which markdown can't show,
+/// dropped and the runs that then have the same formatting merged
+fn emphasis_runs(runs: &[TextRun]) -> Vec<TextRun> {
+    let mut merged: Vec<TextRun> = Vec::new();
+    for run in runs {
+        let format = CharacterFormat {
+            font: None,
+            size: None,
+            ..run.format
+        };
+        match merged.last_mut() {
+            Some(last) if last.end == run.start && last.format == format => last.end = run.end,
+            _ => merged.push(TextRun { format, ..*run }),
+        }
+    }
+    merged
+}
+
+fn write_run<W: Write>(
+    writer: &mut W,
+    text: &str,
+    format: CharacterFormat,
+    color: Option<Color>,
+) -> Result<()> {
+    let mut marker = String::new();
+    if format.strike {
+        marker.push_str("~~");
+    }
+    if format.bold {
+        marker.push_str("**");
+    }
+    if format.italic {
+        marker.push('*');
+    }
+    let mut closing: String = marker.chars().rev().collect();
+    // Black is what text is shown in anyway, and Cocoa sets it explicitly everywhere
+    if let Some(color) = color.filter(|color| !color.is_black()) {
+        marker.insert_str(0, &format!("<span style=\"color: {}\">", color.hex()));
+        closing.push_str("</span>");
+    }
+    let core = text.trim();
+    if marker.is_empty() || core.is_empty() {
+        return writer
+            .write_all(escape(text).as_bytes())
+            .context("Error writing to output file");
+    }
+
+    // Markdown emphasis can't start or end with whitespace, so keep it outside the markers
+    let leading = &text[..text.len() - text.trim_start().len()];
+    let trailing = &text[text.trim_end().len()..];
+    write!(
+        writer,
+        "{}{}{}{}{}",
+        leading,
+        marker,
+        escape(core),
+        closing,
+        trailing
+    )
+    .context("Error writing to output file")
+}
+
+/// Escape the characters of document text that markdown would otherwise
+/// read as markup
+///
+/// Only text from the document's runs is escaped; what the converter writes
+/// between them, like picture placeholders and `$...$` equations, is markup.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if "\\`*_[]#~$<".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::MathStyle;
+    use crate::pictures::PlaceholderStyle;
+    use crate::rtftotext::{parse_document, tokenize, ConvertOptions};
+
+    fn markdown(rtf: &str) -> String {
+        let options = ConvertOptions {
+            image_placeholders: Some(PlaceholderStyle::Markdown),
+            math: MathStyle::Latex,
+            ..ConvertOptions::default()
+        };
+        let tokens = tokenize(rtf.as_bytes()).unwrap();
+        let document = parse_document(&tokens, &options);
+        let mut output = Vec::new();
+        write_markdown(&document, &mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn marks_up_headings_and_emphasis() {
+        let output = markdown(concat!(
+            r"{\rtf1\ansi{\colortbl;\red0\green0\blue0;\red255\green0\blue0;}",
+            r"{\stylesheet{Normal;}{\s1\sbasedon0\b heading 1;}{\s2\sbasedon0 heading 2;}}",
+            r"\pard\s1 Title\par\pard\s2 Part {\i one}\par",
+            r"\pard Some {\b bold }and {\i\strike gone} {\cf2 red}{\cf1  black}\par}"
+        ));
+        assert_eq!(
+            output,
+            concat!(
+                "# Title\n",
+                "## Part one\n",
+                "Some **bold** and ~~*gone*~~ <span style=\"color: #ff0000\">red</span> black\n"
+            )
+        );
+    }
+
+    #[test]
+    fn ignores_font_changes_within_emphasis() {
+        let output = markdown(r"{\rtf1\ansi {\b one {\f1 two} {\fs30 three}}\par}");
+        assert_eq!(output, "**one two three**\n");
+    }
+
+    #[test]
+    fn escapes_markdown_in_document_text() {
+        let output = markdown(
+            r"{\rtf1\ansi # 2*3 is snake_case [link] `code` \\ ~ $5 <b>{\b  *bold* }\par}",
+        );
+        assert_eq!(
+            output,
+            "\\# 2\\*3 is snake\\_case \\[link\\] \\`code\\` \\\\ \\~ \\$5 \\<b> **\\*bold\\*** \n"
+        );
+    }
+
+    #[test]
+    fn keeps_equations_and_pictures_as_markup() {
+        let output = markdown(concat!(
+            r"{\rtf1\ansi a_b {\*\moMath{\msSup{\me{\mr x}}{\msup{\mr 2}}}} ",
+            r"{\*\shppict{\pict\pngblip 89504e47}}\par}"
+        ));
+        assert_eq!(output, "a\\_b ${x}^{2}$ ![](image001.png)\n");
+    }
+}
@@ -0,0 +1,867 @@
+use log::{debug, warn};
+
+/// The Office Math (`\m*`) destinations that make up an equation, as opposed
+/// to the mail merge destinations that share the prefix
+const MATH_DESTINATIONS: &[&str] = &[
+    "macc",
+    "maccPr",
+    "maln",
+    "malnScr",
+    "margPr",
+    "mbar",
+    "mbarPr",
+    "mbaseJc",
+    "mbegChr",
+    "mborderBox",
+    "mborderBoxPr",
+    "mbox",
+    "mboxPr",
+    "mchr",
+    "mcount",
+    "mctrlPr",
+    "md",
+    "mdeg",
+    "mdegHide",
+    "mden",
+    "mdiff",
+    "mdPr",
+    "me",
+    "mendChr",
+    "meqArr",
+    "meqArrPr",
+    "mf",
+    "mfName",
+    "mfPr",
+    "mfunc",
+    "mfuncPr",
+    "mgroupChr",
+    "mgroupChrPr",
+    "mgrow",
+    "mhideBot",
+    "mhideLeft",
+    "mhideRight",
+    "mhideTop",
+    "mlim",
+    "mlimLoc",
+    "mlimloc",
+    "mlimLow",
+    "mlimlow",
+    "mlimLowPr",
+    "mlimlowPr",
+    "mlimUpp",
+    "mlimupp",
+    "mlimUppPr",
+    "mlimuppPr",
+    "mm",
+    "mmath",
+    "mmathPr",
+    "mmaxdist",
+    "mmc",
+    "mmcJc",
+    "mmcPr",
+    "mmcs",
+    "mmPr",
+    "mmr",
+    "mnary",
+    "mnaryPr",
+    "mnoBreak",
+    "mnum",
+    "mobjDist",
+    "moMath",
+    "moMathPara",
+    "moMathParaPr",
+    "mopEmu",
+    "mphant",
+    "mphantPr",
+    "mplcHide",
+    "mpos",
+    "mr",
+    "mrad",
+    "mradPr",
+    "mrPr",
+    "msepChr",
+    "mshow",
+    "mshp",
+    "msPre",
+    "msPrePr",
+    "msSub",
+    "msSubPr",
+    "msSubSup",
+    "msSubSupPr",
+    "msSup",
+    "msSupPr",
+    "mstrikeBLTR",
+    "mstrikeH",
+    "mstrikeTLBR",
+    "mstrikeV",
+    "msub",
+    "msubHide",
+    "msup",
+    "msupHide",
+    "mtransp",
+    "mtype",
+    "mvertJc",
+    "mzeroAsc",
+    "mzeroDesc",
+    "mzeroWid",
+];
+
+/// How equations are written to the document text
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MathStyle {
+    /// Unicode linear format (UnicodeMath), such as `x^2+√(y)`
+    #[default]
+    Linear,
+    /// LaTeX between `$` delimiters, such as `$x^{2}+\sqrt{y}$`
+    Latex,
+}
+
+#[derive(Clone, Debug)]
+enum MathItem {
+    Text(String),
+    Node(MathNode),
+}
+
+/// A math destination, with the text and nested destinations it contains in
+/// document order
+#[derive(Clone, Debug)]
+pub struct MathNode {
+    name: String,
+    /// Where this destination's text starts in its destination buffer
+    start: usize,
+    items: Vec<MathItem>,
+}
+
+impl MathNode {
+    fn nodes(&self) -> impl Iterator<Item = &MathNode> {
+        self.items.iter().filter_map(|item| match item {
+            MathItem::Node(node) => Some(node),
+            MathItem::Text(_) => None,
+        })
+    }
+
+    fn child(&self, name: &str) -> Option<&MathNode> {
+        self.nodes().find(|node| node.name == name)
+    }
+
+    /// All the text in this destination and those nested in it
+    fn text(&self) -> String {
+        self.items
+            .iter()
+            .map(|item| match item {
+                MathItem::Text(text) => text.clone(),
+                MathItem::Node(node) => node.text(),
+            })
+            .collect()
+    }
+
+    /// The value of a property from this object's `...Pr` destination
+    fn property(&self, name: &str) -> Option<String> {
+        let properties = self.nodes().find(|node| node.name.ends_with("Pr"))?;
+        properties.child(name).map(MathNode::text)
+    }
+
+    /// Whether an on/off property (such as `\mdegHide`) is switched on
+    fn flag(&self, name: &str) -> bool {
+        match self.property(name) {
+            Some(value) => !matches!(value.trim(), "0" | "off" | "false"),
+            None => false,
+        }
+    }
+}
+
+/// Builds the tree of each equation as its math destinations open and close
+#[derive(Clone, Default)]
+pub struct MathCollector {
+    stack: Vec<MathNode>,
+}
+
+impl MathCollector {
+    pub fn handles_destination(name: &str) -> bool {
+        MATH_DESTINATIONS.contains(&name)
+    }
+
+    pub fn open(&mut self, name: &str, start: usize) {
+        self.stack.push(MathNode {
+            name: name.to_owned(),
+            start,
+            items: Vec::new(),
+        });
+    }
+
+    /// The name and starting point of the innermost open math destination,
+    /// whose text should be collected before anything is nested inside it
+    pub fn current(&self) -> Option<(&str, usize)> {
+        self.stack
+            .last()
+            .map(|node| (node.name.as_str(), node.start))
+    }
+
+    pub fn push_text(&mut self, text: String) {
+        if let Some(node) = self.stack.last_mut() {
+            if !text.is_empty() {
+                node.items.push(MathItem::Text(text));
+            }
+        }
+    }
+
+    /// Close the innermost math destination, returning it if it's the
+    /// outermost one, and so holds a complete equation
+    pub fn close(&mut self, name: &str) -> Option<MathNode> {
+        let node = self.stack.pop()?;
+        if node.name != name {
+            warn!(
+                "Closing math destination {} while {} is open",
+                name, node.name
+            );
+        }
+        match self.stack.last_mut() {
+            Some(parent) => {
+                parent.items.push(MathItem::Node(node));
+                None
+            }
+            None => {
+                debug!("Completed equation in {}", node.name);
+                Some(node)
+            }
+        }
+    }
+}
+
+/// Render a complete equation in the given style, with delimiters for LaTeX
+pub fn render(node: &MathNode, style: MathStyle) -> String {
+    let body = Renderer { style }.node(node);
+    if body.trim().is_empty() {
+        return String::new();
+    }
+    let display = node.name == "moMathPara" || node.child("moMathPara").is_some();
+    match (style, display) {
+        (MathStyle::Linear, _) => body,
+        (MathStyle::Latex, true) => format!("$${}$$", body),
+        (MathStyle::Latex, false) => format!("${}$", body),
+    }
+}
+
+struct Renderer {
+    style: MathStyle,
+}
+
+impl Renderer {
+    fn latex(&self) -> bool {
+        self.style == MathStyle::Latex
+    }
+
+    /// Render the contents of a node in order, skipping property destinations
+    fn sequence(&self, node: &MathNode) -> String {
+        node.items
+            .iter()
+            .map(|item| match item {
+                MathItem::Text(text) => self.text(text),
+                MathItem::Node(child) => self.node(child),
+            })
+            .collect()
+    }
+
+    /// Render the named argument of an object, such as the `\me` base
+    fn arg(&self, node: &MathNode, name: &str) -> String {
+        node.child(name)
+            .map(|child| self.sequence(child))
+            .unwrap_or_default()
+    }
+
+    /// An argument as an operand: in braces for LaTeX, or in parentheses for
+    /// the linear format if it's more than a single letter or number
+    fn operand(&self, text: &str) -> String {
+        if self.latex() {
+            format!("{{{}}}", text)
+        } else if text.chars().all(char::is_alphanumeric) && !text.is_empty() {
+            text.to_owned()
+        } else {
+            format!("({})", text)
+        }
+    }
+
+    fn text(&self, text: &str) -> String {
+        if !self.latex() {
+            return text.to_owned();
+        }
+        let mut latex = String::new();
+        for c in text.chars() {
+            match latex_symbol(c) {
+                Some("") => (),
+                Some(command) => {
+                    latex.push_str(command);
+                    latex.push(' ');
+                }
+                None if "#$%&_{}".contains(c) => {
+                    latex.push('\\');
+                    latex.push(c);
+                }
+                None => latex.push(c),
+            }
+        }
+        latex
+    }
+
+    fn node(&self, node: &MathNode) -> String {
+        let name = node.name.as_str();
+        if name.ends_with("Pr") {
+            return String::new();
+        }
+        match name {
+            "moMathPara" => {
+                let separator = if self.latex() { " \\\\ " } else { "\n" };
+                node.nodes()
+                    .filter(|child| child.name == "moMath")
+                    .map(|child| self.sequence(child))
+                    .collect::<Vec<_>>()
+                    .join(separator)
+            }
+            "mf" => self.fraction(node),
+            "msSup" | "msSub" | "msSubSup" | "msPre" => self.script(node),
+            "mrad" => self.radical(node),
+            "mnary" => self.nary(node),
+            "md" => self.delimiter(node),
+            "mfunc" => self.function(node),
+            "mlimLow" | "mlimlow" | "mlimUpp" | "mlimupp" => self.limit(node),
+            "macc" => self.accent(node),
+            "mbar" => self.bar(node),
+            "mgroupChr" => self.group_character(node),
+            "mborderBox" => match self.style {
+                MathStyle::Latex => format!("\\boxed{{{}}}", self.arg(node, "me")),
+                MathStyle::Linear => format!("▭({})", self.arg(node, "me")),
+            },
+            "mphant" => match self.style {
+                MathStyle::Latex => format!("\\phantom{{{}}}", self.arg(node, "me")),
+                MathStyle::Linear => format!("⟡({})", self.arg(node, "me")),
+            },
+            "meqArr" => {
+                let rows: Vec<String> = node
+                    .nodes()
+                    .filter(|child| child.name == "me")
+                    .map(|child| self.sequence(child))
+                    .collect();
+                match self.style {
+                    MathStyle::Latex => {
+                        format!("\\begin{{aligned}}{}\\end{{aligned}}", rows.join(" \\\\ "))
+                    }
+                    MathStyle::Linear => format!("█({})", rows.join("@")),
+                }
+            }
+            "mm" => self.matrix(node),
+            // Character-valued properties are read by their objects, not rendered
+            "mchr" | "mbegChr" | "mendChr" | "msepChr" | "mtype" | "mpos" => String::new(),
+            _ => self.sequence(node),
+        }
+    }
+
+    fn fraction(&self, node: &MathNode) -> String {
+        let numerator = self.arg(node, "mnum");
+        let denominator = self.arg(node, "mden");
+        let no_bar = node.property("mtype").as_deref().map(str::trim) == Some("noBar");
+        match (self.style, no_bar) {
+            (MathStyle::Latex, false) => format!("\\frac{{{}}}{{{}}}", numerator, denominator),
+            (MathStyle::Latex, true) => format!("{{{} \\atop {}}}", numerator, denominator),
+            (MathStyle::Linear, false) => format!(
+                "{}/{}",
+                self.operand(&numerator),
+                self.operand(&denominator)
+            ),
+            (MathStyle::Linear, true) => format!("({}¦{})", numerator, denominator),
+        }
+    }
+
+    fn scripts(&self, node: &MathNode) -> String {
+        let mut scripts = String::new();
+        if node.child("msub").is_some() {
+            scripts.push('_');
+            scripts.push_str(&self.operand(&self.arg(node, "msub")));
+        }
+        if node.child("msup").is_some() {
+            scripts.push('^');
+            scripts.push_str(&self.operand(&self.arg(node, "msup")));
+        }
+        scripts
+    }
+
+    fn script(&self, node: &MathNode) -> String {
+        let base = self.arg(node, "me");
+        let base = if self.latex() {
+            format!("{{{}}}", base)
+        } else {
+            base
+        };
+        if node.name == "msPre" {
+            let prefix = if self.latex() { "{}" } else { "" };
+            format!("{}{}{}", prefix, self.scripts(node), base)
+        } else {
+            format!("{}{}", base, self.scripts(node))
+        }
+    }
+
+    fn radical(&self, node: &MathNode) -> String {
+        let base = self.arg(node, "me");
+        let degree = if node.flag("mdegHide") {
+            String::new()
+        } else {
+            self.arg(node, "mdeg")
+        };
+        match self.style {
+            MathStyle::Latex if degree.is_empty() => format!("\\sqrt{{{}}}", base),
+            MathStyle::Latex => format!("\\sqrt[{}]{{{}}}", degree, base),
+            MathStyle::Linear => match degree.trim() {
+                "" => format!("√{}", self.operand(&base)),
+                "3" => format!("∛{}", self.operand(&base)),
+                "4" => format!("∜{}", self.operand(&base)),
+                degree => format!("√({}&{})", degree, base),
+            },
+        }
+    }
+
+    fn nary(&self, node: &MathNode) -> String {
+        let operator = node
+            .property("mchr")
+            .filter(|chr| !chr.is_empty())
+            .unwrap_or_else(|| "∫".to_owned());
+        let mut limits = String::new();
+        if !node.flag("msubHide") && node.child("msub").is_some() {
+            limits.push('_');
+            limits.push_str(&self.operand(&self.arg(node, "msub")));
+        }
+        if !node.flag("msupHide") && node.child("msup").is_some() {
+            limits.push('^');
+            limits.push_str(&self.operand(&self.arg(node, "msup")));
+        }
+        let base = self.arg(node, "me");
+        match self.style {
+            MathStyle::Latex => {
+                let operator = match operator.as_str() {
+                    "∑" => "\\sum".to_owned(),
+                    "∏" => "\\prod".to_owned(),
+                    "∐" => "\\coprod".to_owned(),
+                    "∫" => "\\int".to_owned(),
+                    "∬" => "\\iint".to_owned(),
+                    "∭" => "\\iiint".to_owned(),
+                    "∮" => "\\oint".to_owned(),
+                    "⋃" => "\\bigcup".to_owned(),
+                    "⋂" => "\\bigcap".to_owned(),
+                    "⋁" => "\\bigvee".to_owned(),
+                    "⋀" => "\\bigwedge".to_owned(),
+                    other => self.text(other),
+                };
+                format!("{}{}{{{}}}", operator, limits, base)
+            }
+            MathStyle::Linear => format!("{}{}▒{}", operator, limits, self.operand(&base)),
+        }
+    }
+
+    fn delimiter(&self, node: &MathNode) -> String {
+        let begin = node.property("mbegChr").unwrap_or_else(|| "(".to_owned());
+        let end = node.property("mendChr").unwrap_or_else(|| ")".to_owned());
+        let separator = node.property("msepChr").unwrap_or_else(|| "|".to_owned());
+        let elements: Vec<String> = node
+            .nodes()
+            .filter(|child| child.name == "me")
+            .map(|child| self.sequence(child))
+            .collect();
+        match self.style {
+            MathStyle::Latex => format!(
+                "\\left{}{}\\right{}",
+                latex_delimiter(&begin),
+                elements.join(&latex_delimiter(&separator)),
+                latex_delimiter(&end)
+            ),
+            MathStyle::Linear => format!("{}{}{}", begin, elements.join(&separator), end),
+        }
+    }
+
+    fn function(&self, node: &MathNode) -> String {
+        let name = self.arg(node, "mfName");
+        let base = self.arg(node, "me");
+        match self.style {
+            MathStyle::Latex => {
+                let name = match name.trim() {
+                    known if LATEX_FUNCTIONS.contains(&known) => format!("\\{}", known),
+                    word if !word.is_empty() && word.chars().all(|c| c.is_ascii_alphabetic()) => {
+                        format!("\\operatorname{{{}}}", word)
+                    }
+                    _ => name,
+                };
+                format!("{}{{{}}}", name, base)
+            }
+            // U+2061 is the invisible function application operator
+            MathStyle::Linear => format!("{}\u{2061}{}", name, base),
+        }
+    }
+
+    fn limit(&self, node: &MathNode) -> String {
+        let base = self.arg(node, "me");
+        let limit = self.arg(node, "mlim");
+        let lower = node.name.eq_ignore_ascii_case("mlimlow");
+        match self.style {
+            MathStyle::Latex if LATEX_FUNCTIONS.contains(&base.trim()) => {
+                let operator = format!("\\{}", base.trim());
+                if lower {
+                    format!("{}_{{{}}}", operator, limit)
+                } else {
+                    format!("{}^{{{}}}", operator, limit)
+                }
+            }
+            MathStyle::Latex if lower => format!("\\underset{{{}}}{{{}}}", limit, base),
+            MathStyle::Latex => format!("\\overset{{{}}}{{{}}}", limit, base),
+            MathStyle::Linear => {
+                let operator = if lower { '┬' } else { '┴' };
+                format!("{}{}{}", base, operator, self.operand(&limit))
+            }
+        }
+    }
+
+    fn accent(&self, node: &MathNode) -> String {
+        let accent = node
+            .property("mchr")
+            .filter(|chr| !chr.is_empty())
+            .unwrap_or_else(|| "\u{0302}".to_owned());
+        let base = self.arg(node, "me");
+        match self.style {
+            MathStyle::Latex => {
+                let command = match accent.as_str() {
+                    "\u{0300}" => "grave",
+                    "\u{0301}" => "acute",
+                    "\u{0303}" => "tilde",
+                    "\u{0304}" | "\u{0305}" => "bar",
+                    "\u{0306}" => "breve",
+                    "\u{0307}" => "dot",
+                    "\u{0308}" => "ddot",
+                    "\u{030C}" => "check",
+                    "\u{20D7}" | "\u{20D1}" => "vec",
+                    _ => "hat",
+                };
+                format!("\\{}{{{}}}", command, base)
+            }
+            MathStyle::Linear => format!("{}{}", self.operand(&base), accent),
+        }
+    }
+
+    fn bar(&self, node: &MathNode) -> String {
+        let top = node.property("mpos").as_deref().map(str::trim) == Some("top");
+        let base = self.arg(node, "me");
+        match (self.style, top) {
+            (MathStyle::Latex, true) => format!("\\overline{{{}}}", base),
+            (MathStyle::Latex, false) => format!("\\underline{{{}}}", base),
+            (MathStyle::Linear, true) => format!("{}\u{0305}", self.operand(&base)),
+            (MathStyle::Linear, false) => format!("{}\u{0332}", self.operand(&base)),
+        }
+    }
+
+    fn group_character(&self, node: &MathNode) -> String {
+        let character = node
+            .property("mchr")
+            .filter(|chr| !chr.is_empty())
+            .unwrap_or_else(|| "⏟".to_owned());
+        let top = node.property("mpos").as_deref().map(str::trim) == Some("top");
+        let base = self.arg(node, "me");
+        match self.style {
+            MathStyle::Latex => match character.as_str() {
+                "⏟" => format!("\\underbrace{{{}}}", base),
+                "⏞" => format!("\\overbrace{{{}}}", base),
+                other if top => format!("\\overset{{{}}}{{{}}}", self.text(other), base),
+                other => format!("\\underset{{{}}}{{{}}}", self.text(other), base),
+            },
+            MathStyle::Linear => format!("{}({})", character, base),
+        }
+    }
+
+    fn matrix(&self, node: &MathNode) -> String {
+        let rows: Vec<Vec<String>> = node
+            .nodes()
+            .filter(|row| row.name == "mmr")
+            .map(|row| {
+                row.nodes()
+                    .filter(|cell| cell.name == "me")
+                    .map(|cell| self.sequence(cell))
+                    .collect()
+            })
+            .collect();
+        match self.style {
+            MathStyle::Latex => {
+                let rows: Vec<String> = rows.iter().map(|row| row.join(" & ")).collect();
+                format!("\\begin{{matrix}}{}\\end{{matrix}}", rows.join(" \\\\ "))
+            }
+            MathStyle::Linear => {
+                let rows: Vec<String> = rows.iter().map(|row| row.join("&")).collect();
+                format!("■({})", rows.join("@"))
+            }
+        }
+    }
+}
+
+/// Function names with their own LaTeX command
+const LATEX_FUNCTIONS: &[&str] = &[
+    "arccos", "arcsin", "arctan", "cos", "cosh", "cot", "coth", "csc", "det", "exp", "gcd", "inf",
+    "lim", "liminf", "limsup", "ln", "log", "max", "min", "sec", "sin", "sinh", "sup", "tan",
+    "tanh",
+];
+
+fn latex_delimiter(delimiter: &str) -> String {
+    match delimiter {
+        "" => ".".to_owned(),
+        "{" => "\\{".to_owned(),
+        "}" => "\\}".to_owned(),
+        "‖" => "\\|".to_owned(),
+        "⟨" | "〈" => "\\langle".to_owned(),
+        "⟩" | "〉" => "\\rangle".to_owned(),
+        "⌈" => "\\lceil".to_owned(),
+        "⌉" => "\\rceil".to_owned(),
+        "⌊" => "\\lfloor".to_owned(),
+        "⌋" => "\\rfloor".to_owned(),
+        other => other.to_owned(),
+    }
+}
+
+/// The LaTeX command for a symbol that has no ASCII equivalent
+fn latex_symbol(c: char) -> Option<&'static str> {
+    Some(match c {
+        'α' => "\\alpha",
+        'β' => "\\beta",
+        'γ' => "\\gamma",
+        'δ' => "\\delta",
+        'ε' => "\\varepsilon",
+        'ϵ' => "\\epsilon",
+        'ζ' => "\\zeta",
+        'η' => "\\eta",
+        'θ' => "\\theta",
+        'ι' => "\\iota",
+        'κ' => "\\kappa",
+        'λ' => "\\lambda",
+        'μ' => "\\mu",
+        'ν' => "\\nu",
+        'ξ' => "\\xi",
+        'π' => "\\pi",
+        'ρ' => "\\rho",
+        'σ' => "\\sigma",
+        'τ' => "\\tau",
+        'υ' => "\\upsilon",
+        'φ' => "\\varphi",
+        'ϕ' => "\\phi",
+        'χ' => "\\chi",
+        'ψ' => "\\psi",
+        'ω' => "\\omega",
+        'Γ' => "\\Gamma",
+        'Δ' => "\\Delta",
+        'Θ' => "\\Theta",
+        'Λ' => "\\Lambda",
+        'Ξ' => "\\Xi",
+        'Π' => "\\Pi",
+        'Σ' => "\\Sigma",
+        'Υ' => "\\Upsilon",
+        'Φ' => "\\Phi",
+        'Ψ' => "\\Psi",
+        'Ω' => "\\Omega",
+        '±' => "\\pm",
+        '∓' => "\\mp",
+        '×' => "\\times",
+        '÷' => "\\div",
+        '·' | '⋅' => "\\cdot",
+        '≤' => "\\leq",
+        '≥' => "\\geq",
+        '≠' => "\\neq",
+        '≈' => "\\approx",
+        '≡' => "\\equiv",
+        '∝' => "\\propto",
+        '∞' => "\\infty",
+        '→' => "\\rightarrow",
+        '←' => "\\leftarrow",
+        '↔' => "\\leftrightarrow",
+        '⇒' => "\\Rightarrow",
+        '⇔' => "\\Leftrightarrow",
+        '∈' => "\\in",
+        '∉' => "\\notin",
+        '∂' => "\\partial",
+        '∇' => "\\nabla",
+        '∀' => "\\forall",
+        '∃' => "\\exists",
+        '¬' => "\\neg",
+        '∧' => "\\wedge",
+        '∨' => "\\vee",
+        '⊂' => "\\subset",
+        '⊆' => "\\subseteq",
+        '⊃' => "\\supset",
+        '⊇' => "\\supseteq",
+        '∪' => "\\cup",
+        '∩' => "\\cap",
+        '∅' => "\\emptyset",
+        '′' => "'",
+        '…' => "\\ldots",
+        '⋯' => "\\cdots",
+        '∑' => "\\sum",
+        '∏' => "\\prod",
+        '∫' => "\\int",
+        '√' => "\\surd",
+        'ℏ' => "\\hbar",
+        'ℓ' => "\\ell",
+        // ASCII characters that mean something else to LaTeX
+        '\\' => "\\backslash",
+        '^' => "\\hat{}",
+        '~' => "\\sim",
+        // Invisible operators have no LaTeX equivalent
+        '\u{2061}' | '\u{2062}' | '\u{2063}' => "",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(name: &str, items: Vec<MathItem>) -> MathItem {
+        MathItem::Node(MathNode {
+            name: name.to_owned(),
+            start: 0,
+            items,
+        })
+    }
+
+    fn text(text: &str) -> MathItem {
+        MathItem::Text(text.to_owned())
+    }
+
+    /// A property destination holding a single value, like `{\mchr ∑}`
+    fn property(object: &str, name: &str, value: &str) -> MathItem {
+        node(
+            &format!("{}Pr", object),
+            vec![node(name, vec![text(value)])],
+        )
+    }
+
+    /// Render an inline equation in both styles
+    fn render_both(items: Vec<MathItem>) -> (String, String) {
+        let equation = match node("moMath", items) {
+            MathItem::Node(equation) => equation,
+            MathItem::Text(_) => unreachable!(),
+        };
+        (
+            render(&equation, MathStyle::Linear),
+            render(&equation, MathStyle::Latex),
+        )
+    }
+
+    #[test]
+    fn renders_fractions() {
+        let fraction = |properties: Vec<MathItem>| {
+            let mut items = properties;
+            items.push(node("mnum", vec![text("a")]));
+            items.push(node("mden", vec![text("b+1")]));
+            render_both(vec![node("mf", items)])
+        };
+        assert_eq!(
+            fraction(vec![]),
+            ("a/(b+1)".to_owned(), "$\\frac{a}{b+1}$".to_owned())
+        );
+        assert_eq!(
+            fraction(vec![property("mf", "mtype", "noBar")]),
+            ("(a¦b+1)".to_owned(), "${a \\atop b+1}$".to_owned())
+        );
+    }
+
+    #[test]
+    fn renders_scripts() {
+        let scripts = render_both(vec![node(
+            "msSubSup",
+            vec![
+                node("me", vec![text("x")]),
+                node("msub", vec![text("i")]),
+                node("msup", vec![text("2")]),
+            ],
+        )]);
+        assert_eq!(scripts, ("x_i^2".to_owned(), "${x}_{i}^{2}$".to_owned()));
+        let superscript = render_both(vec![node(
+            "msSup",
+            vec![node("me", vec![text("e")]), node("msup", vec![text("-x")])],
+        )]);
+        assert_eq!(superscript, ("e^(-x)".to_owned(), "${e}^{-x}$".to_owned()));
+    }
+
+    #[test]
+    fn renders_radicals() {
+        let square_root = render_both(vec![node(
+            "mrad",
+            vec![
+                property("mrad", "mdegHide", "1"),
+                node("mdeg", vec![]),
+                node("me", vec![text("x+1")]),
+            ],
+        )]);
+        assert_eq!(
+            square_root,
+            ("√(x+1)".to_owned(), "$\\sqrt{x+1}$".to_owned())
+        );
+        let cube_root = render_both(vec![node(
+            "mrad",
+            vec![node("mdeg", vec![text("3")]), node("me", vec![text("y")])],
+        )]);
+        assert_eq!(cube_root, ("∛y".to_owned(), "$\\sqrt[3]{y}$".to_owned()));
+    }
+
+    #[test]
+    fn renders_nary_operators() {
+        let sum = render_both(vec![node(
+            "mnary",
+            vec![
+                property("mnary", "mchr", "∑"),
+                node("msub", vec![text("i=1")]),
+                node("msup", vec![text("n")]),
+                node("me", vec![text("i")]),
+            ],
+        )]);
+        assert_eq!(
+            sum,
+            ("∑_(i=1)^n▒i".to_owned(), "$\\sum_{i=1}^{n}{i}$".to_owned())
+        );
+        // Integrals are the default, and limits can be hidden
+        let integral = render_both(vec![node(
+            "mnary",
+            vec![
+                property("mnary", "msubHide", "on"),
+                node("msub", vec![]),
+                node("me", vec![text("f")]),
+            ],
+        )]);
+        assert_eq!(integral, ("∫▒f".to_owned(), "$\\int{f}$".to_owned()));
+    }
+
+    #[test]
+    fn renders_delimiters() {
+        let parentheses = render_both(vec![node(
+            "md",
+            vec![node("me", vec![text("a")]), node("me", vec![text("b")])],
+        )]);
+        assert_eq!(
+            parentheses,
+            ("(a|b)".to_owned(), "$\\left(a|b\\right)$".to_owned())
+        );
+        let set = render_both(vec![node(
+            "md",
+            vec![
+                node(
+                    "mdPr",
+                    vec![
+                        node("mbegChr", vec![text("{")]),
+                        node("mendChr", vec![text("}")]),
+                    ],
+                ),
+                node("me", vec![text("x")]),
+            ],
+        )]);
+        assert_eq!(set, ("{x}".to_owned(), "$\\left\\{x\\right\\}$".to_owned()));
+    }
+
+    #[test]
+    fn escapes_latex_special_characters() {
+        let (linear, latex) = render_both(vec![text("a&b_1 50% #2 {c} $ x^y ~ \\ α")]);
+        assert_eq!(linear, "a&b_1 50% #2 {c} $ x^y ~ \\ α");
+        assert_eq!(
+            latex,
+            "$a\\&b\\_1 50\\% \\#2 \\{c\\} \\$ x\\hat{} y \\sim  \\backslash  \\alpha $"
+        );
+    }
+}
@@ -0,0 +1,612 @@
+use anyhow::Result;
+use base64::engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig};
+use base64::Engine;
+use log::{debug, warn};
+
+use crate::containers::{self, SourceDocument};
+
+/// Headers that mark the start of the input as a message, rather than some
+/// other text that happens to have a colon on its first line
+const MESSAGE_HEADERS: &[&str] = &[
+    "mime-version",
+    "content-type",
+    "from",
+    "to",
+    "subject",
+    "date",
+    "received",
+    "return-path",
+    "message-id",
+];
+
+/// How far into the input to look for the end of the message headers
+const MAX_HEADER_SIZE: usize = 64 * 1024;
+
+/// How deeply multipart bodies and attached messages may nest, well past
+/// anything a mailer writes but short of exhausting the stack
+const MAX_NESTING: usize = 16;
+
+/// Base64 decoding that tolerates missing padding and stray bits, as
+/// produced by some mailers
+const BASE64: GeneralPurpose = GeneralPurpose::new(
+    &base64::alphabet::STANDARD,
+    GeneralPurposeConfig::new()
+        .with_decode_padding_mode(DecodePaddingMode::Indifferent)
+        .with_decode_allow_trailing_bits(true),
+);
+
+/// Whether `data` starts with an RFC 5322 header block, as `.eml` files and
+/// raw emails do
+pub fn is_mime(data: &[u8]) -> bool {
+    let head = &data[..data.len().min(MAX_HEADER_SIZE)];
+    let (header_block, _) = match split_headers(head) {
+        Some(split) => split,
+        None => return false,
+    };
+    let mut lines = header_block.split(|b| *b == b'\n').peekable();
+    // An mbox separator line may precede the headers
+    if lines.peek().is_some_and(|line| line.starts_with(b"From ")) {
+        lines.next();
+    }
+    let mut known_header = false;
+    for (index, line) in lines.enumerate() {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        if line.starts_with(b" ") || line.starts_with(b"\t") {
+            if index == 0 {
+                return false;
+            }
+            continue;
+        }
+        let name = match line.iter().position(|b| *b == b':') {
+            Some(colon) if colon > 0 => &line[..colon],
+            _ => return false,
+        };
+        if !name.iter().all(|b| (33..=126).contains(b)) {
+            return false;
+        }
+        known_header |= MESSAGE_HEADERS
+            .iter()
+            .any(|known| known.as_bytes().eq_ignore_ascii_case(name));
+    }
+    known_header
+}
+
+/// Split an entity at the blank line ending its headers, returning `None`
+/// if there isn't one
+fn split_headers(data: &[u8]) -> Option<(&[u8], &[u8])> {
+    if let Some(body) = data
+        .strip_prefix(b"\r\n")
+        .or_else(|| data.strip_prefix(b"\n"))
+    {
+        return Some((&[], body));
+    }
+    let mut pos = 0;
+    while let Some(offset) = data[pos..].iter().position(|b| *b == b'\n') {
+        let line_end = pos + offset;
+        let next = &data[line_end + 1..];
+        if let Some(body) = next
+            .strip_prefix(b"\r\n")
+            .or_else(|| next.strip_prefix(b"\n"))
+        {
+            return Some((&data[..line_end], body));
+        }
+        pos = line_end + 1;
+    }
+    None
+}
+
+/// A MIME entity: a message or one of the parts of a multipart body
+struct Entity<'a> {
+    /// Header names in lower case, with their unfolded values
+    headers: Vec<(String, String)>,
+    body: &'a [u8],
+}
+
+impl<'a> Entity<'a> {
+    fn parse(data: &'a [u8]) -> Self {
+        let (header_block, body) = split_headers(data).unwrap_or((data, &[]));
+        let mut headers: Vec<(String, String)> = Vec::new();
+        for line in String::from_utf8_lossy(header_block).lines() {
+            if line.starts_with([' ', '\t']) {
+                if let Some((_, value)) = headers.last_mut() {
+                    value.push(' ');
+                    value.push_str(line.trim());
+                }
+            } else if let Some((name, value)) = line.split_once(':') {
+                headers.push((name.trim().to_lowercase(), value.trim().to_owned()));
+            }
+        }
+        Entity { headers, body }
+    }
+
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The media type in lower case, and its parameters
+    fn content_type(&self) -> (String, Vec<(String, String)>) {
+        match self.header("content-type") {
+            Some(value) => {
+                let (mime_type, params) = parse_header_value(value);
+                (mime_type.to_lowercase(), params)
+            }
+            None => ("text/plain".to_owned(), Vec::new()),
+        }
+    }
+
+    fn filename(&self) -> Option<String> {
+        let disposition = self
+            .header("content-disposition")
+            .map(|value| parse_header_value(value).1)
+            .unwrap_or_default();
+        find_param(&disposition, "filename")
+            .or_else(|| find_param(&self.content_type().1, "name"))
+            .map(|name| decode_encoded_words(&name))
+            .filter(|name| !name.is_empty())
+    }
+
+    /// The body with its content transfer encoding undone
+    fn decoded_body(&self) -> Vec<u8> {
+        let encoding = self
+            .header("content-transfer-encoding")
+            .unwrap_or("7bit")
+            .to_lowercase();
+        match encoding.as_str() {
+            "base64" => {
+                let mut encoded: Vec<u8> = self
+                    .body
+                    .iter()
+                    .copied()
+                    .filter(|b| b.is_ascii_alphanumeric() || *b == b'+' || *b == b'/')
+                    .collect();
+                // A lone trailing character can't encode anything
+                if encoded.len() % 4 == 1 {
+                    encoded.pop();
+                }
+                BASE64.decode(&encoded).unwrap_or_else(|e| {
+                    warn!("Error decoding base64 MIME part: {}", e);
+                    Vec::new()
+                })
+            }
+            "quoted-printable" => {
+                quoted_printable::decode(self.body, quoted_printable::ParseMode::Robust)
+                    .unwrap_or_else(|e| {
+                        warn!("Error decoding quoted-printable MIME part: {}", e);
+                        self.body.to_vec()
+                    })
+            }
+            _ => self.body.to_vec(),
+        }
+    }
+}
+
+/// Parse a structured header value such as `text/rtf; name="a.rtf"` into
+/// its leading value and its parameters, joining RFC 2231 continuations
+/// and decoding RFC 2231 extended values
+fn parse_header_value(value: &str) -> (String, Vec<(String, String)>) {
+    let mut chars = value.chars().peekable();
+    let mut leading = String::new();
+    while let Some(c) = chars.next_if(|c| *c != ';') {
+        leading.push(c);
+    }
+
+    // (name, section, extended, value)
+    let mut sections: Vec<(String, usize, bool, String)> = Vec::new();
+    while chars.next() == Some(';') {
+        let mut name = String::new();
+        while let Some(c) = chars.next_if(|c| *c != '=' && *c != ';') {
+            name.push(c);
+        }
+        let mut param_value = String::new();
+        if chars.next_if_eq(&'=').is_some() {
+            while chars.next_if(|c| c.is_whitespace()).is_some() {}
+            if chars.next_if_eq(&'"').is_some() {
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => break,
+                        '\\' => param_value.extend(chars.next()),
+                        _ => param_value.push(c),
+                    }
+                }
+                while chars.next_if(|c| *c != ';').is_some() {}
+            } else {
+                while let Some(c) = chars.next_if(|c| *c != ';') {
+                    param_value.push(c);
+                }
+            }
+        }
+        let name = name.trim().to_lowercase();
+        if name.is_empty() {
+            continue;
+        }
+        let (name, extended) = match name.strip_suffix('*') {
+            Some(name) => (name.to_owned(), true),
+            None => (name, false),
+        };
+        let (name, section) = match name.split_once('*') {
+            Some((base, index)) => (base.to_owned(), index.parse().unwrap_or(0)),
+            None => (name, 0),
+        };
+        sections.push((name, section, extended, param_value.trim().to_owned()));
+    }
+
+    let mut params: Vec<(String, String)> = Vec::new();
+    sections.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.cmp(&b.1)));
+    let mut charset = None;
+    let mut pending: Vec<u8> = Vec::new();
+    for (index, (name, section, extended, value)) in sections.iter().enumerate() {
+        if *section == 0 {
+            charset = None;
+        }
+        if *extended {
+            let mut value = value.as_str();
+            if *section == 0 {
+                // charset'language'value
+                let mut fields = value.splitn(3, '\'');
+                if let (Some(set), Some(_), Some(rest)) =
+                    (fields.next(), fields.next(), fields.next())
+                {
+                    charset = encoding_rs::Encoding::for_label(set.as_bytes());
+                    value = rest;
+                }
+            }
+            pending.extend(percent_decode(value));
+        } else {
+            pending.extend(value.as_bytes());
+        }
+        let last_section = sections.get(index + 1).is_none_or(|next| next.0 != *name);
+        if last_section {
+            let encoding = charset.unwrap_or(encoding_rs::UTF_8);
+            params.push((name.clone(), encoding.decode(&pending).0.into_owned()));
+            pending.clear();
+        }
+    }
+    (leading.trim().to_owned(), params)
+}
+
+fn find_param(params: &[(String, String)], name: &str) -> Option<String> {
+    params
+        .iter()
+        .find(|(param, _)| param == name)
+        .map(|(_, value)| value.clone())
+}
+
+fn hex_value(digit: u8) -> Option<u8> {
+    (digit as char).to_digit(16).map(|value| value as u8)
+}
+
+fn percent_decode(value: &str) -> Vec<u8> {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = match (bytes[i], bytes.get(i + 1), bytes.get(i + 2)) {
+            (b'%', Some(high), Some(low)) => hex_value(*high).zip(hex_value(*low)),
+            _ => None,
+        };
+        match escaped {
+            Some((high, low)) => {
+                decoded.push(high << 4 | low);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    decoded
+}
+
+/// Decode one RFC 2047 encoded word, `=?charset?encoding?text?=`
+fn decode_encoded_word(word: &str) -> Option<String> {
+    let inner = word.strip_prefix("=?")?.strip_suffix("?=")?;
+    let mut fields = inner.splitn(3, '?');
+    let (charset, encoding, text) = (fields.next()?, fields.next()?, fields.next()?);
+    // Drop any RFC 2231 language suffix
+    let charset = charset.split('*').next()?;
+    let bytes = match encoding {
+        "B" | "b" => BASE64.decode(text.trim_end_matches('=')).ok()?,
+        "Q" | "q" => percent_decode(&text.replace('_', " ").replace('=', "%")),
+        _ => return None,
+    };
+    let encoding = encoding_rs::Encoding::for_label(charset.as_bytes())?;
+    Some(encoding.decode(&bytes).0.into_owned())
+}
+
+/// Decode the RFC 2047 encoded words in a header value, dropping the
+/// whitespace between adjacent encoded words
+fn decode_encoded_words(value: &str) -> String {
+    let mut decoded = String::new();
+    let mut pending_space = String::new();
+    let mut last_was_encoded = false;
+    for token in value.split_inclusive(char::is_whitespace) {
+        let word = token.trim_end();
+        let space = &token[word.len()..];
+        match decode_encoded_word(word) {
+            Some(text) => {
+                if !last_was_encoded {
+                    decoded.push_str(&pending_space);
+                }
+                decoded.push_str(&text);
+                last_was_encoded = true;
+            }
+            None => {
+                decoded.push_str(&pending_space);
+                decoded.push_str(word);
+                last_was_encoded = false;
+            }
+        }
+        pending_space = space.to_owned();
+    }
+    decoded
+}
+
+/// Split a multipart body at its boundary delimiter lines, dropping the
+/// preamble and epilogue
+fn split_multipart<'a>(body: &'a [u8], boundary: &str) -> Vec<&'a [u8]> {
+    let delimiter = format!("--{}", boundary);
+    let mut parts = Vec::new();
+    let mut part_start = None;
+    let mut pos = 0;
+    while pos < body.len() {
+        let line_end = body[pos..]
+            .iter()
+            .position(|b| *b == b'\n')
+            .map_or(body.len(), |offset| pos + offset);
+        let line = &body[pos..line_end];
+        if let Some(rest) = line.strip_prefix(delimiter.as_bytes()) {
+            let is_close = rest.starts_with(b"--");
+            if is_close || rest.iter().all(u8::is_ascii_whitespace) {
+                if let Some(start) = part_start {
+                    // The line break before a delimiter belongs to the delimiter
+                    let mut end = pos;
+                    if end > start && body[end - 1] == b'\n' {
+                        end -= 1;
+                        if end > start && body[end - 1] == b'\r' {
+                            end -= 1;
+                        }
+                    }
+                    parts.push(&body[start..end]);
+                }
+                if is_close {
+                    return parts;
+                }
+                part_start = Some((line_end + 1).min(body.len()));
+            }
+        }
+        pos = line_end + 1;
+    }
+    if let Some(start) = part_start {
+        warn!("Multipart body has no closing boundary");
+        parts.push(&body[start..]);
+    }
+    parts
+}
+
+fn join_part_path(path: &str, index: usize) -> String {
+    if path.is_empty() {
+        index.to_string()
+    } else {
+        format!("{}.{}", path, index)
+    }
+}
+
+/// Collect the RTF parts of a MIME message, and the RTF documents within
+/// any attached Outlook messages or TNEF streams, labelling each after
+/// `label_prefix` by its IMAP style part path
+pub fn extract_documents(
+    data: &[u8],
+    label_prefix: &str,
+    documents: &mut Vec<SourceDocument>,
+) -> Result<()> {
+    extract_entity(data, "", true, 0, label_prefix, documents)
+}
+
+fn extract_entity(
+    data: &[u8],
+    path: &str,
+    is_message: bool,
+    depth: usize,
+    label_prefix: &str,
+    documents: &mut Vec<SourceDocument>,
+) -> Result<()> {
+    if depth > MAX_NESTING {
+        warn!(
+            "Skipping part {:?}, which is nested more than {} levels deep",
+            path, MAX_NESTING
+        );
+        return Ok(());
+    }
+    let entity = Entity::parse(data);
+    let (mime_type, params) = entity.content_type();
+
+    if mime_type.starts_with("multipart/") {
+        match find_param(&params, "boundary") {
+            Some(boundary) => {
+                let parts = split_multipart(entity.body, &boundary);
+                debug!("{} at part {:?} has {} parts", mime_type, path, parts.len());
+                for (index, part) in parts.iter().enumerate() {
+                    let part_path = join_part_path(path, index + 1);
+                    extract_entity(part, &part_path, false, depth + 1, label_prefix, documents)?;
+                }
+                return Ok(());
+            }
+            None => warn!("{} part {:?} has no boundary", mime_type, path),
+        }
+    }
+
+    // The body of a single part message is its part 1
+    let path = if is_message {
+        join_part_path(path, 1)
+    } else {
+        path.to_owned()
+    };
+    if mime_type == "message/rfc822" {
+        debug!("Part {} is an attached message", path);
+        return extract_entity(
+            &entity.decoded_body(),
+            &path,
+            true,
+            depth + 1,
+            label_prefix,
+            documents,
+        );
+    }
+
+    let filename = entity.filename();
+    let label = match &filename {
+        Some(name) => format!("{}part {} ({})", label_prefix, path, name),
+        None => format!("{}part {}", label_prefix, path),
+    };
+    let data = entity.decoded_body();
+    // A broken attachment shouldn't cost the rest of the message
+    match containers::extract_attachment(&label, &data, documents) {
+        Ok(true) => debug!("Found {} in part {}", mime_type, path),
+        Ok(false)
+            if containers::is_named_rtf(filename.as_deref(), Some(&mime_type))
+                || mime_type == "application/x-rtf" =>
+        {
+            warn!("Skipping {}, which is labelled as RTF but isn't", label)
+        }
+        Ok(false) => (),
+        Err(e) => warn!("Skipping {}: {:#}", label, e),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn extract(message: &str) -> Vec<SourceDocument> {
+        let mut documents = Vec::new();
+        extract_documents(message.as_bytes(), "", &mut documents).unwrap();
+        documents
+    }
+
+    #[test]
+    fn finds_rtf_parts_of_multipart_messages() {
+        let message = concat!(
+            "From: a@example.com\r\n",
+            "MIME-Version: 1.0\r\n",
+            "Content-Type: multipart/mixed; boundary=\"outer\"\r\n",
+            "\r\n",
+            "--outer\r\n",
+            "Content-Type: text/plain\r\n",
+            "\r\n",
+            "Hello\r\n",
+            "--outer\r\n",
+            "Content-Type: application/rtf; name=\"=?utf-8?Q?r=C3=A9sum=C3=A9.rtf?=\"\r\n",
+            "Content-Transfer-Encoding: base64\r\n",
+            "\r\n",
+            "e1xydGYxIEhpfQ==\r\n",
+            "--outer\r\n",
+            "Content-Type: message/rfc822\r\n",
+            "\r\n",
+            "Subject: Inner\r\n",
+            "Content-Type: text/rtf\r\n",
+            "Content-Disposition: attachment;\r\n",
+            " filename*0*=iso-8859-1''caf%E9;\r\n",
+            " filename*1=\".rtf\"\r\n",
+            "\r\n",
+            "{\\rtf1 Inner}\r\n",
+            "--outer--\r\n",
+        );
+        assert!(is_mime(message.as_bytes()));
+        let documents = extract(message);
+        let labels: Vec<&str> = documents.iter().map(|doc| doc.label.as_str()).collect();
+        assert_eq!(
+            labels,
+            ["part 2 (r\u{e9}sum\u{e9}.rtf)", "part 3.1 (caf\u{e9}.rtf)"]
+        );
+        assert_eq!(documents[0].data, b"{\\rtf1 Hi}");
+        assert_eq!(documents[1].data, b"{\\rtf1 Inner}");
+    }
+
+    #[test]
+    fn decodes_encoded_words() {
+        assert_eq!(
+            decode_encoded_words("=?ISO-8859-1?B?Y2Fm6Q==?= =?utf-8?q?_au_lait?="),
+            "caf\u{e9} au lait"
+        );
+        // Words that don't decode are left as they are
+        assert_eq!(
+            decode_encoded_words("=?x-unknown?Q?a?="),
+            "=?x-unknown?Q?a?="
+        );
+        assert_eq!(
+            decode_encoded_words("=?utf-8?B?not base64?="),
+            "=?utf-8?B?not base64?="
+        );
+    }
+
+    #[test]
+    fn tolerates_malformed_messages() {
+        assert!(!is_mime(b"{\\rtf1 Not a message}"));
+        assert!(!is_mime(b"Subject: no blank line"));
+        // A multipart body without a boundary is taken as a single part
+        let message = concat!(
+            "Content-Type: multipart/mixed\r\n",
+            "\r\n",
+            "{\\rtf1 Body}\r\n",
+        );
+        let documents = extract(message);
+        assert_eq!(documents.len(), 1);
+        assert_eq!(documents[0].label, "part 1");
+        // Broken base64, and no closing boundary
+        let message = concat!(
+            "Content-Type: multipart/mixed; boundary=b\r\n",
+            "\r\n",
+            "--b\r\n",
+            "Content-Type: text/rtf\r\n",
+            "Content-Transfer-Encoding: base64\r\n",
+            "\r\n",
+            "!!!!\r\n",
+            "--b\r\n",
+            "Content-Type: text/rtf\r\n",
+            "\r\n",
+            "{\\rtf1 Cut off",
+        );
+        let documents = extract(message);
+        assert_eq!(documents.len(), 1);
+        assert_eq!(documents[0].label, "part 2");
+    }
+
+    #[test]
+    fn skips_broken_attachments() {
+        let message = concat!(
+            "Content-Type: multipart/mixed; boundary=b\r\n",
+            "\r\n",
+            "--b\r\n",
+            "Content-Type: text/rtf\r\n",
+            "\r\n",
+            "{\\rtf1 Body}\r\n",
+            "--b\r\n",
+            "Content-Type: application/vnd.ms-outlook; name=\"broken.msg\"\r\n",
+            "Content-Transfer-Encoding: base64\r\n",
+            "\r\n",
+            // Just the compound file signature, and nothing after it
+            "0M8R4KGxGuE=\r\n",
+            "--b--\r\n",
+        );
+        let documents = extract(message);
+        assert_eq!(documents.len(), 1);
+        assert_eq!(documents[0].label, "part 1");
+        assert_eq!(documents[0].data, b"{\\rtf1 Body}");
+    }
+
+    #[test]
+    fn stops_at_deeply_nested_messages() {
+        let mut message = "Content-Type: text/rtf\r\n\r\n{\\rtf1 Deep}".to_owned();
+        for _ in 0..MAX_NESTING {
+            message = format!("Content-Type: message/rfc822\r\n\r\n{}", message);
+        }
+        assert_eq!(extract(&message).len(), 1);
+        message = format!("Content-Type: message/rfc822\r\n\r\n{}", message);
+        assert!(extract(&message).is_empty());
+    }
+}
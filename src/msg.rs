@@ -0,0 +1,205 @@
+use std::io::{Cursor, Read};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use log::{debug, warn};
+
+use crate::bytes::utf16_string;
+use crate::containers::{self, SourceDocument};
+
+/// The signature at the start of every OLE Compound File
+const CFB_SIGNATURE: [u8; 8] = [0xD0, 0xCF, 0x11, 0xE0, 0xA1, 0xB1, 0x1A, 0xE1];
+
+/// PR_RTF_COMPRESSED, the message body as compressed RTF
+const RTF_COMPRESSED_STREAM: &str = "__substg1.0_10090102";
+/// PR_ATTACH_DATA_BIN, the contents of a file attachment
+const ATTACH_DATA_STREAM: &str = "__substg1.0_37010102";
+/// PR_ATTACH_DATA_OBJ, the storage of an attached message
+const ATTACH_MESSAGE_STORAGE: &str = "__substg1.0_3701000D";
+/// PR_ATTACH_LONG_FILENAME and PR_ATTACH_FILENAME
+const ATTACH_FILENAME_PROPERTIES: &[&str] = &["3707", "3704"];
+/// PR_ATTACH_MIME_TAG
+const ATTACH_MIME_TAG_PROPERTY: &str = "370E";
+const ATTACHMENT_PREFIX: &str = "__attach_version1.0_#";
+
+/// Whether `data` is an OLE Compound File, as Outlook `.msg` files are
+pub fn is_msg(data: &[u8]) -> bool {
+    data.starts_with(&CFB_SIGNATURE)
+}
+
+type MessageFile<'a> = cfb::CompoundFile<Cursor<&'a [u8]>>;
+
+fn read_stream(file: &mut MessageFile, path: &Path) -> Option<Vec<u8>> {
+    if !file.is_stream(path) {
+        return None;
+    }
+    let mut data = Vec::new();
+    match file
+        .open_stream(path)
+        .and_then(|mut stream| stream.read_to_end(&mut data))
+    {
+        Ok(_) => Some(data),
+        Err(e) => {
+            warn!("Error reading {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+/// Read a string property, stored as UTF-16 (type 001F) or in the ANSI code page (001E)
+fn read_string_property(file: &mut MessageFile, storage: &Path, tag: &str) -> Option<String> {
+    let unicode = storage.join(format!("__substg1.0_{}001F", tag));
+    if let Some(data) = read_stream(file, &unicode) {
+        return Some(utf16_string(&data));
+    }
+    let ansi = storage.join(format!("__substg1.0_{}001E", tag));
+    read_stream(file, &ansi).map(|data| {
+        let text = encoding_rs::WINDOWS_1252.decode(&data).0;
+        text.trim_end_matches('\0').to_owned()
+    })
+}
+
+/// Collect the RTF body and RTF attachments of an Outlook `.msg` file,
+/// including those of any messages attached to it, labelling each after
+/// `label_prefix`
+pub fn extract_documents(
+    data: &[u8],
+    label_prefix: &str,
+    documents: &mut Vec<SourceDocument>,
+) -> Result<()> {
+    let mut file = cfb::CompoundFile::open(Cursor::new(data))
+        .context("Error opening Outlook message as a compound file")?;
+    extract_message(&mut file, Path::new("/"), label_prefix, documents);
+    Ok(())
+}
+
+fn extract_message(
+    file: &mut MessageFile,
+    storage: &Path,
+    label_prefix: &str,
+    documents: &mut Vec<SourceDocument>,
+) {
+    match read_stream(file, &storage.join(RTF_COMPRESSED_STREAM)) {
+        Some(data) => {
+            debug!("Found compressed RTF body in {}", storage.display());
+            documents.push(SourceDocument::new(&format!("{}body", label_prefix), data));
+        }
+        None => debug!("No RTF body in {}", storage.display()),
+    }
+
+    let mut attachments: Vec<PathBuf> = match file.read_storage(storage) {
+        Ok(entries) => entries
+            .filter(|entry| entry.is_storage() && entry.name().starts_with(ATTACHMENT_PREFIX))
+            .map(|entry| entry.path().to_path_buf())
+            .collect(),
+        Err(e) => {
+            warn!("Error listing {}: {}", storage.display(), e);
+            return;
+        }
+    };
+    attachments.sort();
+    for (index, attachment) in attachments.iter().enumerate() {
+        let filename = ATTACH_FILENAME_PROPERTIES
+            .iter()
+            .find_map(|tag| read_string_property(file, attachment, tag))
+            .filter(|name| !name.is_empty());
+        let label = containers::attachment_label(label_prefix, index, filename.as_deref());
+
+        let message = attachment.join(ATTACH_MESSAGE_STORAGE);
+        if file.is_storage(&message) {
+            debug!("Attachment {} is an attached message", attachment.display());
+            extract_message(file, &message, &format!("{}/", label), documents);
+            continue;
+        }
+
+        let data = match read_stream(file, &attachment.join(ATTACH_DATA_STREAM)) {
+            Some(data) => data,
+            None => continue,
+        };
+        let mime_type = read_string_property(file, attachment, ATTACH_MIME_TAG_PROPERTY);
+        if containers::is_rtf(&data) {
+            debug!("Attachment {} is RTF", attachment.display());
+            documents.push(SourceDocument::new(&label, data));
+        } else if containers::is_named_rtf(filename.as_deref(), mime_type.as_deref()) {
+            warn!("Skipping {}, which is named as RTF but isn't", label);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    fn message(streams: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut file = cfb::CompoundFile::create(Cursor::new(Vec::new())).unwrap();
+        for (path, data) in streams {
+            let path = Path::new(path);
+            for storage in path.ancestors().skip(1) {
+                if !file.exists(storage) {
+                    file.create_storage(storage).unwrap();
+                }
+            }
+            file.create_stream(path).unwrap().write_all(data).unwrap();
+        }
+        file.into_inner().into_inner()
+    }
+
+    fn utf16(text: &str) -> Vec<u8> {
+        text.encode_utf16().flat_map(u16::to_le_bytes).collect()
+    }
+
+    #[test]
+    fn finds_the_body_and_rtf_attachments() {
+        let first = format!("/{}00000000", ATTACHMENT_PREFIX);
+        let second = format!("/{}00000001", ATTACHMENT_PREFIX);
+        let inner = format!("{}/{}", second, ATTACH_MESSAGE_STORAGE);
+        let data = message(&[
+            (&format!("/{}", RTF_COMPRESSED_STREAM), b"{\\rtf1 Body}"),
+            (
+                &format!("{}/{}", first, ATTACH_DATA_STREAM),
+                b"{\\rtf1 Notes}",
+            ),
+            (
+                &format!("{}/__substg1.0_3707001F", first),
+                &utf16("n\u{f6}tes.rtf\0"),
+            ),
+            (&format!("{}/__substg1.0_3704001E", second), b"INNER.MSG"),
+            (
+                &format!("{}/{}", inner, RTF_COMPRESSED_STREAM),
+                b"{\\rtf1 Inner}",
+            ),
+        ]);
+        assert!(is_msg(&data));
+        let mut documents = Vec::new();
+        extract_documents(&data, "", &mut documents).unwrap();
+        let labels: Vec<&str> = documents.iter().map(|doc| doc.label.as_str()).collect();
+        assert_eq!(
+            labels,
+            [
+                "body",
+                "attachment 1 (n\u{f6}tes.rtf)",
+                "attachment 2 (INNER.MSG)/body"
+            ]
+        );
+        assert_eq!(documents[2].data, b"{\\rtf1 Inner}");
+    }
+
+    #[test]
+    fn rejects_malformed_compound_files() {
+        let data = message(&[(&format!("/{}", RTF_COMPRESSED_STREAM), b"{\\rtf1 Body}")]);
+        let mut documents = Vec::new();
+        assert!(extract_documents(&data[..600], "", &mut documents).is_err());
+        assert!(extract_documents(&CFB_SIGNATURE, "", &mut documents).is_err());
+        assert!(documents.is_empty());
+        // A message without an RTF body has nothing to convert
+        extract_documents(
+            &message(&[("/__substg1.0_1000001F", b"")]),
+            "",
+            &mut documents,
+        )
+        .unwrap();
+        assert!(documents.is_empty());
+    }
+}
@@ -0,0 +1,364 @@
+use std::collections::HashSet;
+use std::io::Write;
+use std::{fs, path};
+
+use anyhow::{Context, Result};
+use log::{debug, warn};
+
+use crate::bytes::ByteReader;
+use crate::pictures::decode_hex;
+
+/// A file embedded in an OLE `Package` object
+#[derive(Clone, Debug)]
+pub struct PackageFile {
+    pub label: String,
+    pub source_path: String,
+    pub data: Vec<u8>,
+}
+
+impl PackageFile {
+    /// The original filename, without any directory components
+    pub fn filename(&self) -> String {
+        let path = if self.source_path.is_empty() {
+            &self.label
+        } else {
+            &self.source_path
+        };
+        path.rsplit(['\\', '/'])
+            .next()
+            .filter(|name| !name.is_empty() && *name != "." && *name != "..")
+            .unwrap_or("package.bin")
+            .to_owned()
+    }
+}
+
+/// An OLE object from an `\object` destination
+#[derive(Clone, Debug, Default)]
+pub struct EmbeddedObject {
+    /// The class name from `\objclass`, or from the OLE1 header if that's missing
+    pub class_name: Option<String>,
+    pub name: Option<String>,
+    /// The native data of an embedded object, from its `\objdata` OLE1 stream
+    pub native_data: Option<Vec<u8>>,
+    pub package: Option<PackageFile>,
+}
+
+impl EmbeddedObject {
+    /// The filename used when extracting the object: the original filename
+    /// for packaged files, or numbered from 1 for anything else
+    fn filename(&self, index: usize) -> Option<String> {
+        match (&self.package, &self.native_data) {
+            (Some(package), _) => Some(package.filename()),
+            (None, Some(_)) => Some(format!("object{:03}.bin", index + 1)),
+            (None, None) => None,
+        }
+    }
+
+    fn to_json(&self, filename: Option<&String>) -> serde_json::Value {
+        serde_json::json!({
+            "class": self.class_name,
+            "name": self.name,
+            "filename": filename,
+            "original_filename": self.package.as_ref().map(PackageFile::filename),
+            "size": self.package.as_ref().map(|package| package.data.len())
+                .or_else(|| self.native_data.as_ref().map(Vec::len)),
+        })
+    }
+}
+
+/// Gathers the pieces of each `\object` as their destinations are closed
+#[derive(Clone, Default)]
+pub struct ObjectCollector {
+    objects: Vec<EmbeddedObject>,
+    pending: EmbeddedObject,
+}
+
+impl ObjectCollector {
+    pub fn handles_destination(name: &str) -> bool {
+        matches!(name, "object" | "objclass" | "objname" | "objdata")
+    }
+
+    pub fn fold(&mut self, name: &str, contents: &[u8]) {
+        match name {
+            "objclass" => {
+                self.pending.class_name = Some(String::from_utf8_lossy(contents).trim().to_owned())
+            }
+            "objname" => {
+                self.pending.name = Some(String::from_utf8_lossy(contents).trim().to_owned())
+            }
+            "objdata" => {
+                let data = decode_hex(contents);
+                match parse_ole1_object(&data) {
+                    Some((class_name, native_data)) => {
+                        if self.pending.class_name.is_none() {
+                            self.pending.class_name = Some(class_name);
+                        }
+                        self.pending.native_data = native_data;
+                    }
+                    None => warn!("Unable to parse OLE1 object data"),
+                }
+            }
+            "object" => {
+                let mut object = std::mem::take(&mut self.pending);
+                if object.class_name.as_deref() == Some("Package") {
+                    object.package = object.native_data.as_deref().and_then(parse_package);
+                }
+                debug!(
+                    "Completed {:?} object, with {} bytes of native data",
+                    object.class_name,
+                    object.native_data.as_ref().map(Vec::len).unwrap_or(0)
+                );
+                self.objects.push(object);
+            }
+            _ => panic!(
+                "Programmer error: {} was routed to the object collector without a handler",
+                name
+            ),
+        }
+    }
+
+    pub fn finish(self) -> Vec<EmbeddedObject> {
+        self.objects
+    }
+}
+
+/// Parse an OLE1 ObjectHeader (MS-OLEDS 2.2.4), returning the class name and,
+/// for embedded objects, the native data
+fn parse_ole1_object(data: &[u8]) -> Option<(String, Option<Vec<u8>>)> {
+    let mut reader = ByteReader::new(data);
+    let _ole_version = reader.u32()?;
+    let format_id = reader.u32()?;
+    let class_name = reader.length_prefixed_string()?;
+    let _topic_name = reader.length_prefixed_string()?;
+    let _item_name = reader.length_prefixed_string()?;
+    // Format 2 is an embedded object, 1 is a link with no native data
+    let native_data = if format_id == 2 {
+        let size = reader.u32()? as usize;
+        Some(reader.bytes(size)?.to_vec())
+    } else {
+        None
+    };
+    Some((class_name, native_data))
+}
+
+/// Parse the native data of a Packager `Package` object, which wraps a file
+fn parse_package(native: &[u8]) -> Option<PackageFile> {
+    let mut reader = ByteReader::new(native);
+    let _signature = reader.u16()?;
+    let label = reader.null_terminated_string()?;
+    let source_path = reader.null_terminated_string()?;
+    let _flags = reader.u32()?;
+    let _temp_path_len = reader.u32()?;
+    let _temp_path = reader.null_terminated_string()?;
+    let size = reader.u32()? as usize;
+    let data = reader.bytes(size)?.to_vec();
+    debug!(
+        "Package object contains '{}' ({} bytes)",
+        source_path,
+        data.len()
+    );
+    Some(PackageFile {
+        label,
+        source_path,
+        data,
+    })
+}
+
+/// The extracted filename of each object, numbering any that would collide
+fn extracted_filenames(objects: &[EmbeddedObject]) -> Vec<Option<String>> {
+    let mut used = HashSet::new();
+    objects
+        .iter()
+        .enumerate()
+        .map(|(index, object)| {
+            let filename = object.filename(index)?;
+            let (stem, extension) = match filename.rsplit_once('.') {
+                Some((stem, extension)) => (stem.to_owned(), format!(".{}", extension)),
+                None => (filename.clone(), String::new()),
+            };
+            let mut candidate = filename;
+            let mut copy = 1;
+            while !used.insert(candidate.clone()) {
+                copy += 1;
+                candidate = format!("{}-{}{}", stem, copy, extension);
+            }
+            Some(candidate)
+        })
+        .collect()
+}
+
+/// Write each embedded object into `dir`: the wrapped file for packages, or
+/// the raw native data for other classes
+pub fn write_objects(objects: &[EmbeddedObject], dir: &str) -> Result<()> {
+    let dir = path::Path::new(dir);
+    fs::create_dir_all(dir).with_context(|| "Error creating object directory")?;
+    for (object, filename) in objects.iter().zip(extracted_filenames(objects)) {
+        let (data, filename) = match (&object.package, &object.native_data, filename) {
+            (Some(package), _, Some(filename)) => (&package.data, filename),
+            (None, Some(native_data), Some(filename)) => (native_data, filename),
+            _ => continue,
+        };
+        let path = dir.join(filename);
+        debug!("Writing object to {}...", path.display());
+        fs::write(&path, data)
+            .with_context(|| format!("Error writing object {}", path.display()))?;
+    }
+    Ok(())
+}
+
+/// Write the list of embedded objects as a JSON array
+pub fn write_json<W: Write>(objects: &[EmbeddedObject], writer: W) -> Result<()> {
+    let list: Vec<serde_json::Value> = objects
+        .iter()
+        .zip(extracted_filenames(objects))
+        .map(|(object, filename)| object.to_json(filename.as_ref()))
+        .collect();
+    serde_json::to_writer_pretty(writer, &list).context("Error writing object list")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rtftotext::{parse_document, tokenize, ConvertOptions};
+
+    fn length_prefixed(text: &str) -> Vec<u8> {
+        let mut bytes = (text.len() as u32).to_le_bytes().to_vec();
+        bytes.extend(text.as_bytes());
+        bytes
+    }
+
+    /// An OLE1 ObjectHeader, followed by the native data for embedded objects
+    fn ole1_object(format_id: u32, class_name: &str, native: Option<&[u8]>) -> Vec<u8> {
+        let mut data = 0x0501u32.to_le_bytes().to_vec();
+        data.extend(format_id.to_le_bytes());
+        data.extend(length_prefixed(&format!("{}\0", class_name)));
+        data.extend(length_prefixed(""));
+        data.extend(length_prefixed(""));
+        if let Some(native) = native {
+            data.extend((native.len() as u32).to_le_bytes());
+            data.extend(native);
+        }
+        data
+    }
+
+    fn package(label: &str, source_path: &str, contents: &[u8]) -> Vec<u8> {
+        let mut native = 2u16.to_le_bytes().to_vec();
+        native.extend(format!("{}\0{}\0", label, source_path).as_bytes());
+        native.extend(0x0003_0000u32.to_le_bytes());
+        let temp_path = format!("C:\\Temp\\{}\0", label);
+        native.extend((temp_path.len() as u32).to_le_bytes());
+        native.extend(temp_path.as_bytes());
+        native.extend((contents.len() as u32).to_le_bytes());
+        native.extend(contents);
+        native
+    }
+
+    fn hex(data: &[u8]) -> String {
+        data.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    #[test]
+    fn parses_ole1_object_headers() {
+        let embedded = ole1_object(2, "Excel.Sheet.8", Some(b"native"));
+        assert_eq!(
+            parse_ole1_object(&embedded),
+            Some(("Excel.Sheet.8".to_owned(), Some(b"native".to_vec())))
+        );
+        let linked = ole1_object(1, "Word.Document.8", None);
+        assert_eq!(
+            parse_ole1_object(&linked),
+            Some(("Word.Document.8".to_owned(), None))
+        );
+        // Native data cut short, and a header missing its names
+        assert_eq!(parse_ole1_object(&embedded[..embedded.len() - 1]), None);
+        assert_eq!(parse_ole1_object(&embedded[..10]), None);
+        assert_eq!(parse_ole1_object(&[]), None);
+    }
+
+    #[test]
+    fn parses_packaged_files() {
+        let native = package("memo.pdf", "C:\\Users\\me\\memo.pdf", b"%PDF-1.4");
+        let file = parse_package(&native).unwrap();
+        assert_eq!(file.label, "memo.pdf");
+        assert_eq!(file.filename(), "memo.pdf");
+        assert_eq!(file.data, b"%PDF-1.4");
+        // The label stands in for a missing path, and unsafe names are replaced
+        let file = parse_package(&package("notes.txt", "", b"")).unwrap();
+        assert_eq!(file.filename(), "notes.txt");
+        let file = parse_package(&package("..", "..", b"")).unwrap();
+        assert_eq!(file.filename(), "package.bin");
+        // A label or path without its terminating null, or data cut short
+        assert!(parse_package(&native[..8]).is_none());
+        assert!(parse_package(&native[..native.len() - 1]).is_none());
+    }
+
+    #[test]
+    fn numbers_colliding_filenames() {
+        let packaged = |path: &str| EmbeddedObject {
+            package: Some(PackageFile {
+                label: String::new(),
+                source_path: path.to_owned(),
+                data: Vec::new(),
+            }),
+            ..EmbeddedObject::default()
+        };
+        let objects = [
+            packaged("a\\memo.pdf"),
+            packaged("b/memo.pdf"),
+            EmbeddedObject {
+                native_data: Some(Vec::new()),
+                ..EmbeddedObject::default()
+            },
+            EmbeddedObject::default(),
+        ];
+        assert_eq!(
+            extracted_filenames(&objects),
+            [
+                Some("memo.pdf".to_owned()),
+                Some("memo-2.pdf".to_owned()),
+                Some("object003.bin".to_owned()),
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn collects_objects_and_shows_their_result() {
+        let parse = |objdata: &[u8]| {
+            let rtf = format!(
+                concat!(
+                    r"{{\rtf1\ansi Before {{\object\objemb{{\*\objclass Package}}",
+                    r"{{\*\objdata {}}}{{\result Memo icon}}}} after\par}}"
+                ),
+                hex(objdata)
+            );
+            let tokens = tokenize(rtf.as_bytes()).unwrap();
+            parse_document(&tokens, &ConvertOptions::default())
+        };
+
+        let native = package("memo.pdf", "C:\\memo.pdf", b"%PDF");
+        let document = parse(&ole1_object(2, "Package", Some(&native)));
+        assert_eq!(document.text, "Before Memo icon after\n");
+        let object = &document.objects[0];
+        assert_eq!(object.class_name.as_deref(), Some("Package"));
+        assert_eq!(object.package.as_ref().unwrap().data, b"%PDF");
+
+        // Malformed headers leave just the class name, and the result is still shown
+        for objdata in [&b"\x01\x05\x00\x00"[..], &native[..native.len() - 1]] {
+            let document = parse(objdata);
+            assert_eq!(document.text, "Before Memo icon after\n");
+            let object = &document.objects[0];
+            assert_eq!(object.class_name.as_deref(), Some("Package"));
+            assert!(object.native_data.is_none() && object.package.is_none());
+        }
+
+        // A package whose native data is cut short is kept as plain native data
+        let truncated = ole1_object(2, "Package", Some(&native[..native.len() - 1]));
+        let document = parse(&truncated);
+        assert!(document.objects[0].package.is_none());
+        assert_eq!(
+            extracted_filenames(&document.objects),
+            [Some("object001.bin".to_owned())]
+        );
+    }
+}
@@ -0,0 +1,467 @@
+use std::collections::HashMap;
+use std::{fs, path};
+
+use anyhow::{Context, Result};
+use log::{debug, warn};
+
+use crate::shapes::ShapeProperties;
+
+/// How a picture is represented in the document text
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PlaceholderStyle {
+    /// `[image: image001.png 120x40mm "Company logo"]`
+    Text,
+    /// `![Company logo](image001.png)`
+    Markdown,
+}
+
+/// The format of a picture's data, as declared by the `\pict` destination
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PictureFormat {
+    Png,
+    Jpeg,
+    Emf,
+    Wmf,
+    Dib,
+    Ddb,
+    MacPict,
+    Os2Metafile,
+    Unknown,
+}
+
+impl PictureFormat {
+    fn from_properties(properties: &HashMap<String, Option<i32>>) -> Self {
+        let formats = [
+            ("pngblip", PictureFormat::Png),
+            ("jpegblip", PictureFormat::Jpeg),
+            ("emfblip", PictureFormat::Emf),
+            ("wmetafile", PictureFormat::Wmf),
+            ("dibitmap", PictureFormat::Dib),
+            ("wbitmap", PictureFormat::Ddb),
+            ("macpict", PictureFormat::MacPict),
+            ("pmmetafile", PictureFormat::Os2Metafile),
+        ];
+        formats
+            .iter()
+            .find(|(name, _)| properties.contains_key(*name))
+            .map(|(_, format)| *format)
+            .unwrap_or(PictureFormat::Unknown)
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            PictureFormat::Png => "png",
+            PictureFormat::Jpeg => "jpg",
+            PictureFormat::Emf => "emf",
+            PictureFormat::Wmf => "wmf",
+            PictureFormat::Dib => "bmp",
+            PictureFormat::MacPict => "pict",
+            PictureFormat::Os2Metafile => "met",
+            PictureFormat::Ddb | PictureFormat::Unknown => "bin",
+        }
+    }
+}
+
+/// Which copy of a picture to keep when the document carries more than one
+///
+/// Word writes each picture once for readers that understand shapes
+/// (`\shppict`, or the `pib` property inside `\shpinst`), and again for those
+/// that don't (`\nonshppict`, or the `\shprslt` of a shape).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PictureAlternative {
+    #[default]
+    ShpPict,
+    NonShpPict,
+}
+
+impl std::str::FromStr for PictureAlternative {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "shppict" => Ok(PictureAlternative::ShpPict),
+            "nonshppict" => Ok(PictureAlternative::NonShpPict),
+            _ => Err(anyhow::anyhow!("Unrecognized picture alternative '{}'", s)),
+        }
+    }
+}
+
+/// A picture from a `\pict` destination, with its data decoded
+#[derive(Clone, Debug)]
+pub struct Picture {
+    pub format: PictureFormat,
+    pub data: Vec<u8>,
+    /// Source picture dimensions (`\picw`/`\pich`), in pixels or hundredths of a millimeter
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    /// Desired display dimensions (`\picwgoal`/`\pichgoal`), in twips
+    pub width_goal: Option<i32>,
+    pub height_goal: Option<i32>,
+    /// Display scaling (`\picscalex`/`\picscaley`), in percent
+    pub scale_x: Option<i32>,
+    pub scale_y: Option<i32>,
+    /// Properties from the picture's `\picprop` or its enclosing shape
+    pub properties: ShapeProperties,
+}
+
+impl Picture {
+    /// Build a picture from the contents of a `\pict` destination and the
+    /// control words set in its group
+    ///
+    /// Picture data is hex-encoded text, unless it was written with `\bin`.
+    pub fn new(contents: &[u8], properties: &HashMap<String, Option<i32>>) -> Self {
+        let data = if properties.contains_key("bin") {
+            contents.to_vec()
+        } else {
+            decode_hex(contents)
+        };
+        let value = |name: &str| properties.get(name).copied().flatten();
+        let picture = Picture {
+            format: PictureFormat::from_properties(properties),
+            data,
+            width: value("picw"),
+            height: value("pich"),
+            width_goal: value("picwgoal"),
+            height_goal: value("pichgoal"),
+            scale_x: value("picscalex"),
+            scale_y: value("picscaley"),
+            properties: ShapeProperties::new(),
+        };
+        debug!(
+            "Decoded {:?} picture of {} bytes",
+            picture.format,
+            picture.data.len()
+        );
+        picture
+    }
+
+    /// The filename used when extracting the picture, numbered from 1
+    pub fn filename(&self, index: usize) -> String {
+        format!("image{:03}.{}", index + 1, self.format.extension())
+    }
+
+    /// The picture's alternative text, from its shape description or name
+    pub fn description(&self) -> Option<&str> {
+        ["wzDescription", "wzName"]
+            .iter()
+            .filter_map(|name| self.properties.get(*name))
+            .map(|value| value.trim())
+            .find(|value| !value.is_empty())
+    }
+
+    /// The displayed size of the picture in millimeters, if its goal size is known
+    pub fn display_size_mm(&self) -> Option<(f64, f64)> {
+        let to_mm = |twips: i32, scale: Option<i32>| {
+            f64::from(twips) * f64::from(scale.unwrap_or(100)) / 100.0 / 1440.0 * 25.4
+        };
+        Some((
+            to_mm(self.width_goal?, self.scale_x),
+            to_mm(self.height_goal?, self.scale_y),
+        ))
+    }
+
+    /// The text standing in for the picture in the document text
+    pub fn placeholder(&self, index: usize, style: PlaceholderStyle) -> String {
+        let filename = self.filename(index);
+        match style {
+            PlaceholderStyle::Text => {
+                let mut placeholder = format!("[image: {}", filename);
+                if let Some((width, height)) = self.display_size_mm() {
+                    placeholder.push_str(&format!(" {:.0}x{:.0}mm", width, height));
+                }
+                if let Some(description) = self.description() {
+                    placeholder.push_str(&format!(" \"{}\"", description));
+                }
+                placeholder.push(']');
+                placeholder
+            }
+            PlaceholderStyle::Markdown => {
+                format!(
+                    "![{}]({})",
+                    self.description().unwrap_or_default(),
+                    filename
+                )
+            }
+        }
+    }
+
+    /// The picture data as a standalone file, adding whatever file header the
+    /// format needs that RTF leaves out
+    pub fn file_contents(&self) -> Vec<u8> {
+        match self.format {
+            PictureFormat::Dib => bitmap_file_header(&self.data)
+                .into_iter()
+                .chain(self.data.iter().copied())
+                .collect(),
+            PictureFormat::Wmf => self
+                .placeable_metafile_header()
+                .into_iter()
+                .chain(self.data.iter().copied())
+                .collect(),
+            // PICT files start with a 512 byte header reserved for application use
+            PictureFormat::MacPict => [vec![0u8; 512], self.data.clone()].concat(),
+            _ => self.data.clone(),
+        }
+    }
+
+    /// An Aldus placeable metafile header, sized from the goal dimensions in twips
+    fn placeable_metafile_header(&self) -> Vec<u8> {
+        let width = self.width_goal.or(self.width).unwrap_or(0).clamp(0, 0x7FFF) as u16;
+        let height = self
+            .height_goal
+            .or(self.height)
+            .unwrap_or(0)
+            .clamp(0, 0x7FFF) as u16;
+        let mut header = Vec::with_capacity(22);
+        header.extend(0x9AC6CDD7u32.to_le_bytes());
+        header.extend(0u16.to_le_bytes()); // handle
+        header.extend(0i16.to_le_bytes()); // left
+        header.extend(0i16.to_le_bytes()); // top
+        header.extend(width.to_le_bytes()); // right
+        header.extend(height.to_le_bytes()); // bottom
+        header.extend(1440u16.to_le_bytes()); // units per inch (twips)
+        header.extend(0u32.to_le_bytes()); // reserved
+        let checksum = header
+            .chunks(2)
+            .map(|word| u16::from_le_bytes([word[0], word[1]]))
+            .fold(0u16, |sum, word| sum ^ word);
+        header.extend(checksum.to_le_bytes());
+        header
+    }
+}
+
+/// A BITMAPFILEHEADER for a device-independent bitmap
+fn bitmap_file_header(dib: &[u8]) -> Vec<u8> {
+    let read_u32 = |offset: usize| {
+        dib.get(offset..offset + 4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .unwrap_or(0)
+    };
+    let read_u16 = |offset: usize| {
+        dib.get(offset..offset + 2)
+            .map(|b| u16::from_le_bytes([b[0], b[1]]))
+            .unwrap_or(0)
+    };
+    let header_size = read_u32(0);
+    let (bit_count, compression, colors_used) = if header_size == 12 {
+        // BITMAPCOREHEADER, with 3-byte palette entries
+        (read_u16(10), 0, 0)
+    } else {
+        (read_u16(14), read_u32(16), read_u32(32))
+    };
+    let palette_entry_size = if header_size == 12 { 3 } else { 4 };
+    let palette_entries = if colors_used != 0 {
+        colors_used
+    } else if bit_count <= 8 {
+        1 << bit_count
+    } else {
+        0
+    };
+    // BI_BITFIELDS masks follow a BITMAPINFOHEADER
+    let masks = if compression == 3 && header_size == 40 {
+        12
+    } else {
+        0
+    };
+    let pixel_offset = 14 + header_size + palette_entries * palette_entry_size + masks;
+
+    let mut header = Vec::with_capacity(14);
+    header.extend(b"BM");
+    header.extend((14 + dib.len() as u32).to_le_bytes());
+    header.extend(0u32.to_le_bytes());
+    header.extend(pixel_offset.to_le_bytes());
+    header
+}
+
+/// Decode hex-encoded data, as used by `\pict` and `\objdata`, ignoring whitespace
+pub fn decode_hex(contents: &[u8]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(contents.len() / 2);
+    let mut high: Option<u8> = None;
+    for c in contents {
+        let nibble = match (*c as char).to_digit(16) {
+            Some(nibble) => nibble as u8,
+            None => {
+                if !c.is_ascii_whitespace() {
+                    warn!("Skipping non-hex character {:?} in hex data", *c as char);
+                }
+                continue;
+            }
+        };
+        match high.take() {
+            Some(high) => data.push(high << 4 | nibble),
+            None => high = Some(nibble),
+        }
+    }
+    if high.is_some() {
+        warn!("Hex data has an odd number of digits, dropping the last one");
+    }
+    data
+}
+
+/// Write each picture into `dir` as a numbered file
+pub fn write_images(pictures: &[Picture], dir: &str) -> Result<()> {
+    let dir = path::Path::new(dir);
+    fs::create_dir_all(dir).with_context(|| "Error creating image directory")?;
+    for (index, picture) in pictures.iter().enumerate() {
+        let path = dir.join(picture.filename(index));
+        debug!("Writing picture to {}...", path.display());
+        fs::write(&path, picture.file_contents())
+            .with_context(|| format!("Error writing image {}", path.display()))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rtftotext::{parse_document, tokenize, ConvertOptions, Document};
+
+    fn parse(rtf: &str, options: &ConvertOptions) -> Document {
+        let tokens = tokenize(rtf.as_bytes()).unwrap();
+        parse_document(&tokens, options)
+    }
+
+    fn picture(words: &[(&str, Option<i32>)], shape: &[(&str, &str)]) -> Picture {
+        let properties = words
+            .iter()
+            .map(|(name, value)| (name.to_string(), *value))
+            .collect();
+        let mut picture = Picture::new(b"89 50\n4e 47", &properties);
+        picture.properties = shape
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        picture
+    }
+
+    #[test]
+    fn decodes_picture_data() {
+        let png = picture(&[("pngblip", None)], &[]);
+        assert_eq!(png.format, PictureFormat::Png);
+        assert_eq!(png.data, b"\x89PNG");
+        assert_eq!(png.filename(0), "image001.png");
+        assert_eq!(decode_hex(b"0g1 2f3"), [0x01, 0x2f]);
+    }
+
+    #[test]
+    fn describes_pictures_in_placeholders() {
+        let sized = [
+            ("jpegblip", None),
+            ("picwgoal", Some(1440)),
+            ("pichgoal", Some(720)),
+            ("picscalex", Some(50)),
+        ];
+        let logo = picture(
+            &sized,
+            &[("wzName", "Picture 1"), ("wzDescription", "Company logo")],
+        );
+        assert_eq!(
+            logo.placeholder(1, PlaceholderStyle::Text),
+            "[image: image002.jpg 13x13mm \"Company logo\"]"
+        );
+        assert_eq!(
+            logo.placeholder(1, PlaceholderStyle::Markdown),
+            "![Company logo](image002.jpg)"
+        );
+
+        // The shape name stands in for a missing or blank description
+        let named = picture(&sized, &[("wzName", "Picture 1"), ("wzDescription", " ")]);
+        assert_eq!(named.description(), Some("Picture 1"));
+
+        let bare = picture(&[("emfblip", None)], &[]);
+        assert_eq!(
+            bare.placeholder(0, PlaceholderStyle::Text),
+            "[image: image001.emf]"
+        );
+        assert_eq!(
+            bare.placeholder(0, PlaceholderStyle::Markdown),
+            "![](image001.emf)"
+        );
+    }
+
+    #[test]
+    fn keeps_one_alternative_of_each_picture() {
+        let rtf = concat!(
+            r"{\rtf1\ansi A{\*\shppict{\pict\pngblip 89504e47}}",
+            r"{\nonshppict{\pict\wmetafile8 0100}}B\par}"
+        );
+        let pictures = |picture_alternative| {
+            let options = ConvertOptions {
+                picture_alternative,
+                image_placeholders: Some(PlaceholderStyle::Text),
+                ..ConvertOptions::default()
+            };
+            let document = parse(rtf, &options);
+            let formats: Vec<_> = document
+                .pictures
+                .iter()
+                .map(|picture| picture.format)
+                .collect();
+            (formats, document.text)
+        };
+        assert_eq!(
+            pictures(PictureAlternative::ShpPict),
+            (
+                vec![PictureFormat::Png],
+                "A[image: image001.png]B\n".to_owned()
+            )
+        );
+        assert_eq!(
+            pictures(PictureAlternative::NonShpPict),
+            (
+                vec![PictureFormat::Wmf],
+                "A[image: image001.wmf]B\n".to_owned()
+            )
+        );
+    }
+
+    #[test]
+    fn takes_alt_text_from_the_enclosing_shape() {
+        let document = parse(
+            concat!(
+                r"{\rtf1\ansi {\shp{\*\shpinst{\sp{\sn wzDescription}{\sv Company logo}}",
+                r"{\sp{\sn pib}{\sv {\pict\pngblip\picwgoal1440\pichgoal720 89504e47}}}}",
+                r"{\shprslt{\pict\wmetafile8 0100}}}\par}"
+            ),
+            &ConvertOptions {
+                image_placeholders: Some(PlaceholderStyle::Text),
+                ..ConvertOptions::default()
+            },
+        );
+        assert_eq!(document.pictures.len(), 1);
+        assert_eq!(
+            document.text,
+            "[image: image001.png 25x13mm \"Company logo\"]\n"
+        );
+    }
+
+    #[test]
+    fn keeps_repeated_pictures_once() {
+        let document = parse(
+            concat!(
+                r"{\rtf1\ansi {\pict\pngblip 89504e47}, {\pict\jpegblip 89504e47}, ",
+                r"{\shp{\*\shpinst{\sp{\sn wzDescription}{\sv Logo}}",
+                r"{\sp{\sn pib}{\sv {\pict\pngblip 8950 4e47}}}}}, ",
+                r"{\pict\pngblip 89504e47}\par}"
+            ),
+            &ConvertOptions {
+                image_placeholders: Some(PlaceholderStyle::Text),
+                ..ConvertOptions::default()
+            },
+        );
+        let formats: Vec<_> = document
+            .pictures
+            .iter()
+            .map(|picture| picture.format)
+            .collect();
+        assert_eq!(formats, [PictureFormat::Png, PictureFormat::Jpeg]);
+        // The first copy picks up the description of the shape repeating it
+        assert_eq!(
+            document.text,
+            concat!(
+                "[image: image001.png], [image: image002.jpg], ",
+                "[image: image001.png \"Logo\"], [image: image001.png \"Logo\"]\n"
+            )
+        );
+    }
+}
@@ -0,0 +1,212 @@
+use std::collections::HashMap;
+
+use crate::annotations::format_dttm;
+
+/// How tracked changes (revision marks) are represented in the document text
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RevisionMode {
+    /// Keep inserted text and drop deleted text, as if all changes were accepted
+    #[default]
+    Accept,
+    /// Drop inserted text and keep deleted text, as if all changes were rejected
+    Reject,
+    /// Keep both inserted and deleted text as if they were ordinary text
+    Show,
+    /// Keep both, wrapping insertions in `[+ +]` and deletions in `[- -]`
+    Diff,
+}
+
+impl std::str::FromStr for RevisionMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "accept" => Ok(RevisionMode::Accept),
+            "reject" => Ok(RevisionMode::Reject),
+            "show" => Ok(RevisionMode::Show),
+            "diff" => Ok(RevisionMode::Diff),
+            _ => Err(anyhow::anyhow!("Unrecognized revision mode '{}'", s)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RevisionKind {
+    Insertion,
+    Deletion,
+}
+
+/// The revision state of a run of text, taken from `\revised`/`\deleted` and
+/// their `\revauth`/`\revdttm` (or `\revauthdel`/`\revdttmdel`) values
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RevisionMark {
+    pub kind: RevisionKind,
+    pub author: Option<i32>,
+    pub date: Option<i32>,
+}
+
+/// Applies the revision mode to text as it's written, keeping track of which
+/// diff markers are still open in each destination
+#[derive(Clone, Default)]
+pub struct RevisionTracker {
+    mode: RevisionMode,
+    authors: Vec<String>,
+    open_marks: HashMap<String, RevisionMark>,
+}
+
+impl RevisionTracker {
+    pub fn new(mode: RevisionMode) -> Self {
+        Self {
+            mode,
+            ..Default::default()
+        }
+    }
+
+    /// Load the author names from the contents of a `\revtbl` destination
+    pub fn set_authors(&mut self, revtbl: &str) {
+        // \revauth indexes count empty entries too, so only drop what follows the last ';'
+        let mut authors: Vec<String> = revtbl
+            .split(';')
+            .map(|name| name.trim().to_owned())
+            .collect();
+        if authors.last().is_some_and(String::is_empty) {
+            authors.pop();
+        }
+        self.authors = authors;
+    }
+
+    /// Produce the text to actually write to `destination` for a run of
+    /// `text` with the given revision state, or `None` if the run is dropped
+    pub fn filter(
+        &mut self,
+        destination: &str,
+        mark: Option<RevisionMark>,
+        text: &str,
+    ) -> Option<String> {
+        match (self.mode, mark.map(|mark| mark.kind)) {
+            (RevisionMode::Accept, Some(RevisionKind::Deletion))
+            | (RevisionMode::Reject, Some(RevisionKind::Insertion)) => None,
+            (RevisionMode::Diff, _) => {
+                let mut output = String::with_capacity(text.len());
+                if self.open_marks.get(destination) != mark.as_ref() {
+                    if let Some(closer) = self.close(destination) {
+                        output.push_str(&closer);
+                    }
+                    if let Some(mark) = mark {
+                        output.push_str(&self.opener(&mark));
+                        self.open_marks.insert(destination.to_owned(), mark);
+                    }
+                }
+                output.push_str(text);
+                Some(output)
+            }
+            _ => Some(text.to_owned()),
+        }
+    }
+
+    /// Close any diff marker left open in `destination`, returning the text
+    /// that closes it
+    pub fn close(&mut self, destination: &str) -> Option<String> {
+        self.open_marks
+            .remove(destination)
+            .map(|mark| match mark.kind {
+                RevisionKind::Insertion => "+]".to_owned(),
+                RevisionKind::Deletion => "-]".to_owned(),
+            })
+    }
+
+    fn opener(&self, mark: &RevisionMark) -> String {
+        let sigil = match mark.kind {
+            RevisionKind::Insertion => '+',
+            RevisionKind::Deletion => '-',
+        };
+        let author = mark
+            .author
+            .and_then(|index| self.authors.get(index as usize))
+            .map(String::as_str)
+            .filter(|name| !name.is_empty())
+            .unwrap_or("Unknown");
+        match mark.date.and_then(|date| format_dttm(date as u32)) {
+            Some(date) => format!("[{}{} ({}): ", sigil, author, date),
+            None => format!("[{}{}: ", sigil, author),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mark(kind: RevisionKind, author: i32) -> Option<RevisionMark> {
+        Some(RevisionMark {
+            kind,
+            author: Some(author),
+            date: None,
+        })
+    }
+
+    /// Run `runs` through a tracker for `mode`, as text written to the body
+    fn apply(mode: RevisionMode, runs: &[(Option<RevisionMark>, &str)]) -> String {
+        let mut tracker = RevisionTracker::new(mode);
+        tracker.set_authors("Unknown;Alice;;Bob;");
+        let mut output: String = runs
+            .iter()
+            .filter_map(|(mark, text)| tracker.filter("rtf", *mark, text))
+            .collect();
+        output.extend(tracker.close("rtf"));
+        output
+    }
+
+    #[test]
+    fn applies_the_revision_mode() {
+        let runs = [
+            (None, "The "),
+            (mark(RevisionKind::Deletion, 1), "old"),
+            (mark(RevisionKind::Insertion, 3), "new"),
+            (None, " text"),
+        ];
+        assert_eq!(apply(RevisionMode::Accept, &runs), "The new text");
+        assert_eq!(apply(RevisionMode::Reject, &runs), "The old text");
+        assert_eq!(apply(RevisionMode::Show, &runs), "The oldnew text");
+        assert_eq!(
+            apply(RevisionMode::Diff, &runs),
+            "The [-Alice: old-][+Bob: new+] text"
+        );
+    }
+
+    #[test]
+    fn diff_markers_span_runs_with_the_same_mark() {
+        let insertion = mark(RevisionKind::Insertion, 1);
+        let runs = [(insertion, "one "), (insertion, "two")];
+        assert_eq!(apply(RevisionMode::Diff, &runs), "[+Alice: one two+]");
+        // A marker left open at the end is closed by the caller
+        let runs = [(insertion, "open")];
+        assert_eq!(apply(RevisionMode::Diff, &runs), "[+Alice: open+]");
+    }
+
+    #[test]
+    fn looks_up_authors_in_the_revision_table() {
+        let author = |index| {
+            apply(
+                RevisionMode::Diff,
+                &[(mark(RevisionKind::Insertion, index), "x")],
+            )
+        };
+        assert_eq!(author(0), "[+Unknown: x+]");
+        // Empty entries still take up an index
+        assert_eq!(author(3), "[+Bob: x+]");
+        assert_eq!(author(2), "[+Unknown: x+]");
+        assert_eq!(author(4), "[+Unknown: x+]");
+        assert_eq!(author(-1), "[+Unknown: x+]");
+
+        let dated = Some(RevisionMark {
+            kind: RevisionKind::Deletion,
+            author: Some(1),
+            date: Some(-2017099170),
+        });
+        assert_eq!(
+            apply(RevisionMode::Diff, &[(dated, "x")]),
+            "[-Alice (2024-05-16 09:30): x-]"
+        );
+    }
+}
@@ -6,6 +6,81 @@ use crate::rtftotext::GroupState;
 
 type StateHandler = dyn Fn(&mut GroupState, &str, Option<i32>) + 'static + Sync;
 
+// Destinations holding document tables rather than content, whose text isn't subject to
+// character formatting such as hidden text or revision marks
+pub const TABLE_DESTINATIONS: &[&str] = &[
+    "colortbl",
+    "expandedcolortbl",
+    "filetbl",
+    "fonttbl",
+    "listoverridetable",
+    "listtable",
+    "revtbl",
+    "rsidtbl",
+    "stylesheet",
+];
+
+// Paragraph alignment control words, only the last of which applies
+pub const PARAGRAPH_ALIGNMENTS: &[&str] = &["ql", "qc", "qr", "qj", "qd"];
+
+// Paragraph formatting control words that \pard returns to their defaults
+const PARAGRAPH_PROPERTIES: &[&str] = &[
+    "s",
+    "outlinelevel",
+    "ql",
+    "qc",
+    "qr",
+    "qj",
+    "qd",
+    "li",
+    "ri",
+    "fi",
+    "lin",
+    "rin",
+    "sb",
+    "sa",
+    "ilvl",
+    "ls",
+    "intbl",
+    "itap",
+    "keep",
+    "keepn",
+];
+
+// Character formatting control words that \plain returns to their defaults
+const CHARACTER_PROPERTIES: &[&str] = &[
+    "b",
+    "i",
+    "ul",
+    "ulnone",
+    "uld",
+    "uldb",
+    "ulw",
+    "strike",
+    "striked",
+    "caps",
+    "scaps",
+    "v",
+    "webhidden",
+    "spv",
+    "cs",
+    "f",
+    "fs",
+    "cf",
+    "cb",
+    "highlight",
+    "super",
+    "sub",
+    "nosupersub",
+    "up",
+    "dn",
+    "outl",
+    "shad",
+    "embo",
+    "impr",
+    "lang",
+];
+
 lazy_static::lazy_static! {
     // The values for these tables are draw from the Word 2007 RTF Spec (1.9.1)
     // Typically the easiest way to deal with these is to copy/paste the table
@@ -16,16 +91,16 @@ lazy_static::lazy_static! {
         m.insert("aftncn", Box::new(destination_control_set_state_default));
         m.insert("aftnsep", Box::new(destination_control_set_state_default));
         m.insert("aftnsepc", Box::new(destination_control_set_state_default));
-        m.insert("annotation", Box::new(destination_control_set_state_default));
-        m.insert("atnauthor", Box::new(destination_control_set_state_default));
-        m.insert("atndate", Box::new(destination_control_set_state_default));
+        m.insert("annotation", Box::new(destination_control_set_state_encoding));
+        m.insert("atnauthor", Box::new(destination_control_set_state_encoding));
+        m.insert("atndate", Box::new(destination_control_set_state_encoding));
         m.insert("atnicn", Box::new(destination_control_set_state_default));
-        m.insert("atnid", Box::new(destination_control_set_state_default));
+        m.insert("atnid", Box::new(destination_control_set_state_encoding));
         m.insert("atnparent", Box::new(destination_control_set_state_default));
-        m.insert("atnref", Box::new(destination_control_set_state_default));
-        m.insert("atntime", Box::new(destination_control_set_state_default));
-        m.insert("atrfend", Box::new(destination_control_set_state_default));
-        m.insert("atrfstart", Box::new(destination_control_set_state_default));
+        m.insert("atnref", Box::new(destination_control_set_state_encoding));
+        m.insert("atntime", Box::new(destination_control_set_state_encoding));
+        m.insert("atrfend", Box::new(destination_control_set_state_encoding));
+        m.insert("atrfstart", Box::new(destination_control_set_state_encoding));
         m.insert("author", Box::new(destination_control_set_state_default));
         m.insert("background", Box::new(destination_control_set_state_default));
         m.insert("bkmkend", Box::new(destination_control_set_state_default));
@@ -45,46 +120,44 @@ lazy_static::lazy_static! {
         m.insert("do", Box::new(destination_control_set_state_default));
         m.insert("doccomm", Box::new(destination_control_set_state_default));
         m.insert("docvar", Box::new(destination_control_set_state_default));
-        m.insert("dptxbxtext", Box::new(destination_control_set_state_default));
+        m.insert("dptxbxtext", Box::new(destination_control_set_state_encoding));
         m.insert("ebcend", Box::new(destination_control_set_state_default));
         m.insert("ebcstart", Box::new(destination_control_set_state_default));
         m.insert("factoidname", Box::new(destination_control_set_state_default));
         m.insert("falt", Box::new(destination_control_set_state_default));
         m.insert("fchars", Box::new(destination_control_set_state_default));
-        m.insert("ffdeftext", Box::new(destination_control_set_state_default));
+        m.insert("ffdeftext", Box::new(destination_control_set_state_encoding));
         m.insert("ffentrymcr", Box::new(destination_control_set_state_default));
         m.insert("ffexitmcr", Box::new(destination_control_set_state_default));
         m.insert("ffformat", Box::new(destination_control_set_state_default));
-        m.insert("ffhelptext", Box::new(destination_control_set_state_default));
-        m.insert("ffl", Box::new(destination_control_set_state_default));
-        m.insert("ffname", Box::new(destination_control_set_state_default));
-        m.insert("ffstattext", Box::new(destination_control_set_state_default));
-        m.insert("field", Box::new(destination_control_set_state_default));
+        m.insert("ffhelptext", Box::new(destination_control_set_state_encoding));
+        m.insert("ffl", Box::new(destination_control_set_state_encoding));
+        m.insert("ffname", Box::new(destination_control_set_state_encoding));
+        m.insert("ffstattext", Box::new(destination_control_set_state_encoding));
         m.insert("file", Box::new(destination_control_set_state_default));
         m.insert("filetbl", Box::new(destination_control_set_state_default));
         m.insert("fldinst", Box::new(destination_control_set_state_default));
-        m.insert("fldrslt", Box::new(destination_control_set_state_default));
         m.insert("fldtype", Box::new(destination_control_set_state_default));
         m.insert("fname", Box::new(destination_control_set_state_default));
         m.insert("fontemb", Box::new(destination_control_set_state_default));
         m.insert("fontfile", Box::new(destination_control_set_state_default));
         m.insert("fonttbl", Box::new(destination_control_set_state_default));
-        m.insert("footer", Box::new(destination_control_set_state_default));
-        m.insert("footerf", Box::new(destination_control_set_state_default));
-        m.insert("footerl", Box::new(destination_control_set_state_default));
-        m.insert("footerr", Box::new(destination_control_set_state_default));
+        m.insert("footer", Box::new(destination_control_set_state_encoding));
+        m.insert("footerf", Box::new(destination_control_set_state_encoding));
+        m.insert("footerl", Box::new(destination_control_set_state_encoding));
+        m.insert("footerr", Box::new(destination_control_set_state_encoding));
         m.insert("footnote", Box::new(destination_control_set_state_default));
-        m.insert("formfield", Box::new(destination_control_set_state_default));
+        m.insert("formfield", Box::new(destination_control_set_state_encoding));
         m.insert("ftncn", Box::new(destination_control_set_state_default));
         m.insert("ftnsep", Box::new(destination_control_set_state_default));
         m.insert("ftnsepc", Box::new(destination_control_set_state_default));
         m.insert("g", Box::new(destination_control_set_state_default));
         m.insert("generator", Box::new(destination_control_set_state_default));
         m.insert("gridtbl", Box::new(destination_control_set_state_default));
-        m.insert("header", Box::new(destination_control_set_state_default));
-        m.insert("headerf", Box::new(destination_control_set_state_default));
-        m.insert("headerl", Box::new(destination_control_set_state_default));
-        m.insert("headerr", Box::new(destination_control_set_state_default));
+        m.insert("header", Box::new(destination_control_set_state_encoding));
+        m.insert("headerf", Box::new(destination_control_set_state_encoding));
+        m.insert("headerl", Box::new(destination_control_set_state_encoding));
+        m.insert("headerr", Box::new(destination_control_set_state_encoding));
         m.insert("hl", Box::new(destination_control_set_state_default));
         m.insert("hlfr", Box::new(destination_control_set_state_default));
         m.insert("hlinkbase", Box::new(destination_control_set_state_default));
@@ -111,65 +184,70 @@ lazy_static::lazy_static! {
         m.insert("listtable", Box::new(destination_control_set_state_default));
         m.insert("listtext", Box::new(destination_control_set_state_default));
         m.insert("lsdlockedexcept", Box::new(destination_control_set_state_default));
-        m.insert("macc", Box::new(destination_control_set_state_default));
-        m.insert("maccPr", Box::new(destination_control_set_state_default));
+        m.insert("macc", Box::new(destination_control_set_state_encoding));
+        m.insert("maccPr", Box::new(destination_control_set_state_encoding));
         m.insert("mailmerge", Box::new(destination_control_set_state_default));
-        m.insert("maln", Box::new(destination_control_set_state_default));
-        m.insert("malnScr", Box::new(destination_control_set_state_default));
+        m.insert("maln", Box::new(destination_control_set_state_encoding));
+        m.insert("malnScr", Box::new(destination_control_set_state_encoding));
         m.insert("manager", Box::new(destination_control_set_state_default));
-        m.insert("margPr", Box::new(destination_control_set_state_default));
-        m.insert("mbar", Box::new(destination_control_set_state_default));
-        m.insert("mbarPr", Box::new(destination_control_set_state_default));
-        m.insert("mbaseJc", Box::new(destination_control_set_state_default));
-        m.insert("mbegChr", Box::new(destination_control_set_state_default));
-        m.insert("mborderBox", Box::new(destination_control_set_state_default));
-        m.insert("mborderBoxPr", Box::new(destination_control_set_state_default));
-        m.insert("mbox", Box::new(destination_control_set_state_default));
-        m.insert("mboxPr", Box::new(destination_control_set_state_default));
-        m.insert("mchr", Box::new(destination_control_set_state_default));
-        m.insert("mcount", Box::new(destination_control_set_state_default));
-        m.insert("mctrlPr", Box::new(destination_control_set_state_default));
-        m.insert("md", Box::new(destination_control_set_state_default));
-        m.insert("mdeg", Box::new(destination_control_set_state_default));
-        m.insert("mdegHide", Box::new(destination_control_set_state_default));
-        m.insert("mden", Box::new(destination_control_set_state_default));
-        m.insert("mdiff", Box::new(destination_control_set_state_default));
-        m.insert("mdPr", Box::new(destination_control_set_state_default));
-        m.insert("me", Box::new(destination_control_set_state_default));
-        m.insert("mendChr", Box::new(destination_control_set_state_default));
-        m.insert("meqArr", Box::new(destination_control_set_state_default));
-        m.insert("meqArrPr", Box::new(destination_control_set_state_default));
-        m.insert("mf", Box::new(destination_control_set_state_default));
-        m.insert("mfName", Box::new(destination_control_set_state_default));
-        m.insert("mfPr", Box::new(destination_control_set_state_default));
-        m.insert("mfunc", Box::new(destination_control_set_state_default));
-        m.insert("mfuncPr", Box::new(destination_control_set_state_default));
-        m.insert("mgroupChr", Box::new(destination_control_set_state_default));
-        m.insert("mgroupChrPr", Box::new(destination_control_set_state_default));
-        m.insert("mgrow", Box::new(destination_control_set_state_default));
-        m.insert("mhideBot", Box::new(destination_control_set_state_default));
-        m.insert("mhideLeft", Box::new(destination_control_set_state_default));
-        m.insert("mhideRight", Box::new(destination_control_set_state_default));
-        m.insert("mhideTop", Box::new(destination_control_set_state_default));
+        m.insert("margPr", Box::new(destination_control_set_state_encoding));
+        m.insert("mbar", Box::new(destination_control_set_state_encoding));
+        m.insert("mbarPr", Box::new(destination_control_set_state_encoding));
+        m.insert("mbaseJc", Box::new(destination_control_set_state_encoding));
+        m.insert("mbegChr", Box::new(destination_control_set_state_encoding));
+        m.insert("mborderBox", Box::new(destination_control_set_state_encoding));
+        m.insert("mborderBoxPr", Box::new(destination_control_set_state_encoding));
+        m.insert("mbox", Box::new(destination_control_set_state_encoding));
+        m.insert("mboxPr", Box::new(destination_control_set_state_encoding));
+        m.insert("mchr", Box::new(destination_control_set_state_encoding));
+        m.insert("mcount", Box::new(destination_control_set_state_encoding));
+        m.insert("mctrlPr", Box::new(destination_control_set_state_encoding));
+        m.insert("md", Box::new(destination_control_set_state_encoding));
+        m.insert("mdeg", Box::new(destination_control_set_state_encoding));
+        m.insert("mdegHide", Box::new(destination_control_set_state_encoding));
+        m.insert("mden", Box::new(destination_control_set_state_encoding));
+        m.insert("mdiff", Box::new(destination_control_set_state_encoding));
+        m.insert("mdPr", Box::new(destination_control_set_state_encoding));
+        m.insert("me", Box::new(destination_control_set_state_encoding));
+        m.insert("mendChr", Box::new(destination_control_set_state_encoding));
+        m.insert("meqArr", Box::new(destination_control_set_state_encoding));
+        m.insert("meqArrPr", Box::new(destination_control_set_state_encoding));
+        m.insert("mf", Box::new(destination_control_set_state_encoding));
+        m.insert("mfName", Box::new(destination_control_set_state_encoding));
+        m.insert("mfPr", Box::new(destination_control_set_state_encoding));
+        m.insert("mfunc", Box::new(destination_control_set_state_encoding));
+        m.insert("mfuncPr", Box::new(destination_control_set_state_encoding));
+        m.insert("mgroupChr", Box::new(destination_control_set_state_encoding));
+        m.insert("mgroupChrPr", Box::new(destination_control_set_state_encoding));
+        m.insert("mgrow", Box::new(destination_control_set_state_encoding));
+        m.insert("mhideBot", Box::new(destination_control_set_state_encoding));
+        m.insert("mhideLeft", Box::new(destination_control_set_state_encoding));
+        m.insert("mhideRight", Box::new(destination_control_set_state_encoding));
+        m.insert("mhideTop", Box::new(destination_control_set_state_encoding));
         m.insert("mhtmltag", Box::new(destination_control_set_state_default));
-        m.insert("mlim", Box::new(destination_control_set_state_default));
-        m.insert("mlimloc", Box::new(destination_control_set_state_default));
-        m.insert("mlimlow", Box::new(destination_control_set_state_default));
-        m.insert("mlimlowPr", Box::new(destination_control_set_state_default));
-        m.insert("mlimupp", Box::new(destination_control_set_state_default));
-        m.insert("mlimuppPr", Box::new(destination_control_set_state_default));
-        m.insert("mm", Box::new(destination_control_set_state_default));
+        m.insert("mlim", Box::new(destination_control_set_state_encoding));
+        m.insert("mlimloc", Box::new(destination_control_set_state_encoding));
+        m.insert("mlimLoc", Box::new(destination_control_set_state_encoding));
+        m.insert("mlimlow", Box::new(destination_control_set_state_encoding));
+        m.insert("mlimLow", Box::new(destination_control_set_state_encoding));
+        m.insert("mlimlowPr", Box::new(destination_control_set_state_encoding));
+        m.insert("mlimLowPr", Box::new(destination_control_set_state_encoding));
+        m.insert("mlimupp", Box::new(destination_control_set_state_encoding));
+        m.insert("mlimUpp", Box::new(destination_control_set_state_encoding));
+        m.insert("mlimuppPr", Box::new(destination_control_set_state_encoding));
+        m.insert("mlimUppPr", Box::new(destination_control_set_state_encoding));
+        m.insert("mm", Box::new(destination_control_set_state_encoding));
         m.insert("mmaddfieldname", Box::new(destination_control_set_state_default));
-        m.insert("mmath", Box::new(destination_control_set_state_default));
+        m.insert("mmath", Box::new(destination_control_set_state_encoding));
         m.insert("mmathPict", Box::new(destination_control_set_state_default));
-        m.insert("mmathPr", Box::new(destination_control_set_state_default));
-        m.insert("mmaxdist", Box::new(destination_control_set_state_default));
-        m.insert("mmc", Box::new(destination_control_set_state_default));
-        m.insert("mmcJc", Box::new(destination_control_set_state_default));
+        m.insert("mmathPr", Box::new(destination_control_set_state_encoding));
+        m.insert("mmaxdist", Box::new(destination_control_set_state_encoding));
+        m.insert("mmc", Box::new(destination_control_set_state_encoding));
+        m.insert("mmcJc", Box::new(destination_control_set_state_encoding));
         m.insert("mmconnectstr", Box::new(destination_control_set_state_default));
         m.insert("mmconnectstrdata", Box::new(destination_control_set_state_default));
-        m.insert("mmcPr", Box::new(destination_control_set_state_default));
-        m.insert("mmcs", Box::new(destination_control_set_state_default));
+        m.insert("mmcPr", Box::new(destination_control_set_state_encoding));
+        m.insert("mmcs", Box::new(destination_control_set_state_encoding));
         m.insert("mmdatasource", Box::new(destination_control_set_state_default));
         m.insert("mmheadersource", Box::new(destination_control_set_state_default));
         m.insert("mmmailsubject", Box::new(destination_control_set_state_default));
@@ -185,55 +263,55 @@ lazy_static::lazy_static! {
         m.insert("mmodsoudl", Box::new(destination_control_set_state_default));
         m.insert("mmodsoudldata", Box::new(destination_control_set_state_default));
         m.insert("mmodsouniquetag", Box::new(destination_control_set_state_default));
-        m.insert("mmPr", Box::new(destination_control_set_state_default));
+        m.insert("mmPr", Box::new(destination_control_set_state_encoding));
         m.insert("mmquery", Box::new(destination_control_set_state_default));
-        m.insert("mmr", Box::new(destination_control_set_state_default));
-        m.insert("mnary", Box::new(destination_control_set_state_default));
-        m.insert("mnaryPr", Box::new(destination_control_set_state_default));
-        m.insert("mnoBreak", Box::new(destination_control_set_state_default));
-        m.insert("mnum", Box::new(destination_control_set_state_default));
-        m.insert("mobjDist", Box::new(destination_control_set_state_default));
-        m.insert("moMath", Box::new(destination_control_set_state_default));
-        m.insert("moMathPara", Box::new(destination_control_set_state_default));
-        m.insert("moMathParaPr", Box::new(destination_control_set_state_default));
-        m.insert("mopEmu", Box::new(destination_control_set_state_default));
-        m.insert("mphant", Box::new(destination_control_set_state_default));
-        m.insert("mphantPr", Box::new(destination_control_set_state_default));
-        m.insert("mplcHide", Box::new(destination_control_set_state_default));
-        m.insert("mpos", Box::new(destination_control_set_state_default));
-        m.insert("mr", Box::new(destination_control_set_state_default));
-        m.insert("mrad", Box::new(destination_control_set_state_default));
-        m.insert("mradPr", Box::new(destination_control_set_state_default));
-        m.insert("mrPr", Box::new(destination_control_set_state_default));
-        m.insert("msepChr", Box::new(destination_control_set_state_default));
-        m.insert("mshow", Box::new(destination_control_set_state_default));
-        m.insert("mshp", Box::new(destination_control_set_state_default));
-        m.insert("msPre", Box::new(destination_control_set_state_default));
-        m.insert("msPrePr", Box::new(destination_control_set_state_default));
-        m.insert("msSub", Box::new(destination_control_set_state_default));
-        m.insert("msSubPr", Box::new(destination_control_set_state_default));
-        m.insert("msSubSup", Box::new(destination_control_set_state_default));
-        m.insert("msSubSupPr", Box::new(destination_control_set_state_default));
-        m.insert("msSup", Box::new(destination_control_set_state_default));
-        m.insert("msSupPr", Box::new(destination_control_set_state_default));
-        m.insert("mstrikeBLTR", Box::new(destination_control_set_state_default));
-        m.insert("mstrikeH", Box::new(destination_control_set_state_default));
-        m.insert("mstrikeTLBR", Box::new(destination_control_set_state_default));
-        m.insert("mstrikeV", Box::new(destination_control_set_state_default));
-        m.insert("msub", Box::new(destination_control_set_state_default));
-        m.insert("msubHide", Box::new(destination_control_set_state_default));
-        m.insert("msup", Box::new(destination_control_set_state_default));
-        m.insert("msupHide", Box::new(destination_control_set_state_default));
-        m.insert("mtransp", Box::new(destination_control_set_state_default));
-        m.insert("mtype", Box::new(destination_control_set_state_default));
-        m.insert("mvertJc", Box::new(destination_control_set_state_default));
+        m.insert("mmr", Box::new(destination_control_set_state_encoding));
+        m.insert("mnary", Box::new(destination_control_set_state_encoding));
+        m.insert("mnaryPr", Box::new(destination_control_set_state_encoding));
+        m.insert("mnoBreak", Box::new(destination_control_set_state_encoding));
+        m.insert("mnum", Box::new(destination_control_set_state_encoding));
+        m.insert("mobjDist", Box::new(destination_control_set_state_encoding));
+        m.insert("moMath", Box::new(destination_control_set_state_encoding));
+        m.insert("moMathPara", Box::new(destination_control_set_state_encoding));
+        m.insert("moMathParaPr", Box::new(destination_control_set_state_encoding));
+        m.insert("mopEmu", Box::new(destination_control_set_state_encoding));
+        m.insert("mphant", Box::new(destination_control_set_state_encoding));
+        m.insert("mphantPr", Box::new(destination_control_set_state_encoding));
+        m.insert("mplcHide", Box::new(destination_control_set_state_encoding));
+        m.insert("mpos", Box::new(destination_control_set_state_encoding));
+        m.insert("mr", Box::new(destination_control_set_state_encoding));
+        m.insert("mrad", Box::new(destination_control_set_state_encoding));
+        m.insert("mradPr", Box::new(destination_control_set_state_encoding));
+        m.insert("mrPr", Box::new(destination_control_set_state_encoding));
+        m.insert("msepChr", Box::new(destination_control_set_state_encoding));
+        m.insert("mshow", Box::new(destination_control_set_state_encoding));
+        m.insert("mshp", Box::new(destination_control_set_state_encoding));
+        m.insert("msPre", Box::new(destination_control_set_state_encoding));
+        m.insert("msPrePr", Box::new(destination_control_set_state_encoding));
+        m.insert("msSub", Box::new(destination_control_set_state_encoding));
+        m.insert("msSubPr", Box::new(destination_control_set_state_encoding));
+        m.insert("msSubSup", Box::new(destination_control_set_state_encoding));
+        m.insert("msSubSupPr", Box::new(destination_control_set_state_encoding));
+        m.insert("msSup", Box::new(destination_control_set_state_encoding));
+        m.insert("msSupPr", Box::new(destination_control_set_state_encoding));
+        m.insert("mstrikeBLTR", Box::new(destination_control_set_state_encoding));
+        m.insert("mstrikeH", Box::new(destination_control_set_state_encoding));
+        m.insert("mstrikeTLBR", Box::new(destination_control_set_state_encoding));
+        m.insert("mstrikeV", Box::new(destination_control_set_state_encoding));
+        m.insert("msub", Box::new(destination_control_set_state_encoding));
+        m.insert("msubHide", Box::new(destination_control_set_state_encoding));
+        m.insert("msup", Box::new(destination_control_set_state_encoding));
+        m.insert("msupHide", Box::new(destination_control_set_state_encoding));
+        m.insert("mtransp", Box::new(destination_control_set_state_encoding));
+        m.insert("mtype", Box::new(destination_control_set_state_encoding));
+        m.insert("mvertJc", Box::new(destination_control_set_state_encoding));
         m.insert("mvfmf", Box::new(destination_control_set_state_default));
         m.insert("mvfml", Box::new(destination_control_set_state_default));
         m.insert("mvtof", Box::new(destination_control_set_state_default));
         m.insert("mvtol", Box::new(destination_control_set_state_default));
-        m.insert("mzeroAsc", Box::new(destination_control_set_state_default));
-        m.insert("mzeroDesc", Box::new(destination_control_set_state_default));
-        m.insert("mzeroWid", Box::new(destination_control_set_state_default));
+        m.insert("mzeroAsc", Box::new(destination_control_set_state_encoding));
+        m.insert("mzeroDesc", Box::new(destination_control_set_state_encoding));
+        m.insert("mzeroWid", Box::new(destination_control_set_state_encoding));
         m.insert("nesttableprops", Box::new(destination_control_set_state_default));
         m.insert("nextfile", Box::new(destination_control_set_state_default));
         m.insert("nonesttables", Box::new(destination_control_set_state_default));
@@ -272,8 +350,8 @@ lazy_static::lazy_static! {
         m.insert("protstart", Box::new(destination_control_set_state_default));
         m.insert("protusertbl", Box::new(destination_control_set_state_default));
         m.insert("pxe", Box::new(destination_control_set_state_default));
-        m.insert("result", Box::new(destination_control_set_state_default));
-        m.insert("revtbl", Box::new(destination_control_set_state_default));
+        m.insert("result", Box::new(destination_control_set_state_encoding));
+        m.insert("revtbl", Box::new(destination_control_set_state_encoding));
         m.insert("revtim", Box::new(destination_control_set_state_default));
         m.insert("rsidtbl", Box::new(destination_control_set_state_default));
         // This is the basic document text destination
@@ -284,13 +362,13 @@ lazy_static::lazy_static! {
         m.insert("shpinst", Box::new(destination_control_set_state_default));
         m.insert("shppict", Box::new(destination_control_set_state_default));
         m.insert("shprslt", Box::new(destination_control_set_state_default));
-        m.insert("shptxt", Box::new(destination_control_set_state_default));
-        m.insert("sn", Box::new(destination_control_set_state_default));
-        m.insert("sp", Box::new(destination_control_set_state_default));
+        m.insert("shptxt", Box::new(destination_control_set_state_encoding));
+        m.insert("sn", Box::new(destination_control_set_state_encoding));
+        m.insert("sp", Box::new(destination_control_set_state_encoding));
         m.insert("staticval", Box::new(destination_control_set_state_default));
-        m.insert("stylesheet", Box::new(destination_control_set_state_default));
+        m.insert("stylesheet", Box::new(destination_control_set_state_encoding));
         m.insert("subject", Box::new(destination_control_set_state_default));
-        m.insert("sv", Box::new(destination_control_set_state_default));
+        m.insert("sv", Box::new(destination_control_set_state_encoding));
         m.insert("svb", Box::new(destination_control_set_state_default));
         m.insert("tc", Box::new(destination_control_set_state_default));
         m.insert("template", Box::new(destination_control_set_state_default));
@@ -314,13 +392,13 @@ lazy_static::lazy_static! {
         m.insert("xmlopen", Box::new(destination_control_set_state_default));
         // These are unofficial destinations used by the macOS CocoaRTF export filter
         // https://developer.apple.com/library/archive/documentation/Cocoa/Conceptual/AttributedStrings/Tasks/RTFAndAttrStrings.html
-        m.insert("NeXTGraphic", Box::new(destination_control_set_state_default));
+        m.insert("NeXTGraphic", Box::new(destination_control_set_state_encoding));
+        m.insert("expandedcolortbl", Box::new(destination_control_set_state_default));
         m.insert("glid", Box::new(destination_control_and_value_set_state_default));
         m.insert("levelmarker", Box::new(destination_control_set_state_default));
         // These are unofficial destinations used by OpenOffice RTF export filter
         m.insert("hyphen", Box::new(destination_control_and_value_set_state_default));
         m.insert("pgdsc", Box::new(destination_control_and_value_set_state_default));
-        m.insert("pgdscno", Box::new(destination_control_and_value_set_state_default));
         m.insert("pgdsctbl", Box::new(destination_control_set_state_default));
         m
     };
@@ -360,7 +438,7 @@ lazy_static::lazy_static! {
         m.insert("nestcell", Box::new(control_word_ignore));
         m.insert("nestrow", Box::new(control_word_ignore));
         m.insert("page", Box::new(control_symbol_write_ansi_char));
-        m.insert("par", Box::new(control_symbol_write_ansi_char));
+        m.insert("par", Box::new(control_symbol_end_paragraph));
         m.insert("qmspace", Box::new(control_word_ignore));
         m.insert("rdblquote", Box::new(control_symbol_write_ansi_char));
         m.insert("row", Box::new(control_value_set_state_and_write_ansi_char));
@@ -377,8 +455,8 @@ lazy_static::lazy_static! {
         // recognized in the tables of symbols
         m.insert("\"", Box::new(control_symbol_write_ansi_char));
         // Not official control symbols, but the spec says to make allowances for them
-        m.insert("\n", Box::new(control_symbol_write_ansi_char));
-        m.insert("\r", Box::new(control_symbol_write_ansi_char));
+        m.insert("\n", Box::new(control_symbol_end_paragraph));
+        m.insert("\r", Box::new(control_symbol_end_paragraph));
         m.insert("\t", Box::new(control_symbol_write_ansi_char));
         m.insert(" ", Box::new(control_symbol_write_ansi_char));
         // Not defined anywhere, but I've seen it used
@@ -649,6 +727,7 @@ lazy_static::lazy_static! {
         m.insert("fetch", Box::new(control_value_set_state_default));
         m.insert("fhimajor", Box::new(control_value_set_state_default));
         m.insert("fhiminor", Box::new(control_value_set_state_default));
+        m.insert("field", Box::new(control_value_set_state_default));
         m.insert("fjgothic", Box::new(control_value_set_state_default));
         m.insert("fjminchou", Box::new(control_value_set_state_default));
         m.insert("fldalt", Box::new(control_value_set_state_default));
@@ -656,6 +735,7 @@ lazy_static::lazy_static! {
         m.insert("fldedit", Box::new(control_value_set_state_default));
         m.insert("fldlock", Box::new(control_value_set_state_default));
         m.insert("fldpriv", Box::new(control_value_set_state_default));
+        m.insert("fldrslt", Box::new(control_value_set_state_default));
         m.insert("flomajor", Box::new(control_value_set_state_default));
         m.insert("flominor", Box::new(control_value_set_state_default));
         m.insert("fmodern", Box::new(control_value_set_state_default));
@@ -845,7 +925,7 @@ lazy_static::lazy_static! {
         m.insert("otblrul", Box::new(control_value_set_state_default));
         m.insert("overlay", Box::new(control_value_set_state_default));
         m.insert("pagebb", Box::new(control_value_set_state_default));
-        m.insert("pard", Box::new(control_value_set_state_default));
+        m.insert("pard", Box::new(control_flag_reset_paragraph_properties));
         m.insert("pc", Box::new(control_flag_set_state_encoding));
         m.insert("pca", Box::new(control_flag_set_state_encoding));
         m.insert("pgbrdrb", Box::new(control_value_set_state_default));
@@ -901,7 +981,7 @@ lazy_static::lazy_static! {
         m.insert("pindtabqc", Box::new(control_value_set_state_default));
         m.insert("pindtabql", Box::new(control_value_set_state_default));
         m.insert("pindtabqr", Box::new(control_value_set_state_default));
-        m.insert("plain", Box::new(control_value_set_state_default));
+        m.insert("plain", Box::new(control_flag_reset_character_properties));
         m.insert("pmartabqc", Box::new(control_value_set_state_default));
         m.insert("pmartabql", Box::new(control_value_set_state_default));
         m.insert("pmartabqr", Box::new(control_value_set_state_default));
@@ -983,11 +1063,11 @@ lazy_static::lazy_static! {
         m.insert("pvmrg", Box::new(control_value_set_state_default));
         m.insert("pvpara", Box::new(control_value_set_state_default));
         m.insert("pvpg", Box::new(control_value_set_state_default));
-        m.insert("qc", Box::new(control_value_set_state_default));
-        m.insert("qd", Box::new(control_value_set_state_default));
-        m.insert("qj", Box::new(control_value_set_state_default));
-        m.insert("ql", Box::new(control_value_set_state_default));
-        m.insert("qr", Box::new(control_value_set_state_default));
+        m.insert("qc", Box::new(control_value_set_alignment));
+        m.insert("qd", Box::new(control_value_set_alignment));
+        m.insert("qj", Box::new(control_value_set_alignment));
+        m.insert("ql", Box::new(control_value_set_alignment));
+        m.insert("qr", Box::new(control_value_set_alignment));
         m.insert("qt", Box::new(control_value_set_state_default));
         m.insert("rawclbgdkbdiag", Box::new(control_value_set_state_default));
         m.insert("rawclbgbdiag", Box::new(control_value_set_state_default));
@@ -1276,6 +1356,11 @@ lazy_static::lazy_static! {
         m.insert("outdisponlyhtml", Box::new(control_value_set_state_default));
         // These are unofficial flags used by the macOS CocoaRTF export filter
         // https://developer.apple.com/library/archive/documentation/Cocoa/Conceptual/AttributedStrings/Tasks/RTFAndAttrStrings.html
+        m.insert("cscmyk", Box::new(control_value_set_state_default));
+        m.insert("csgenericgray", Box::new(control_value_set_state_default));
+        m.insert("csgenericrgb", Box::new(control_value_set_state_default));
+        m.insert("csgray", Box::new(control_value_set_state_default));
+        m.insert("cssrgb", Box::new(control_value_set_state_default));
         m.insert("glnam", Box::new(control_value_set_state_default));
         m.insert("pardirnatural", Box::new(control_value_set_state_default));
         m.insert("qnatural", Box::new(control_value_set_state_default));
@@ -1864,7 +1949,7 @@ lazy_static::lazy_static! {
         m.insert("tscellwidthfts", Box::new(control_value_set_state_default));
         m.insert("twoinone", Box::new(control_value_set_state_default));
         m.insert("tx", Box::new(control_value_set_state_default));
-        m.insert("u", Box::new(control_value_set_state_default));
+        m.insert("u", Box::new(control_value_write_unicode_char));
         m.insert("uc", Box::new(control_value_set_state_default));
         m.insert("ulc", Box::new(control_value_set_state_default));
         m.insert("up", Box::new(control_value_set_state_default));
@@ -1890,13 +1975,17 @@ lazy_static::lazy_static! {
         // https://developer.apple.com/library/archive/documentation/Cocoa/Conceptual/AttributedStrings/Tasks/RTFAndAttrStrings.html
         m.insert("AppleTypeServicesU", Box::new(control_value_set_state_default));
         m.insert("CocoaLigature", Box::new(control_value_set_state_default));
+        m.insert("c", Box::new(control_value_set_state_default));
+        m.insert("cocoaplatform", Box::new(control_value_set_state_default));
         m.insert("cocoartf", Box::new(control_value_set_state_default));
         m.insert("cocoasubrtf", Box::new(control_value_set_state_default));
+        m.insert("cocoatextscaling", Box::new(control_value_set_state_default));
         m.insert("expansion", Box::new(control_value_set_state_default));
         m.insert("fsmilli", Box::new(control_value_set_state_default));
         m.insert("glcol", Box::new(control_value_set_state_default));
         m.insert("obliqueness", Box::new(control_value_set_state_default));
         m.insert("pardeftab", Box::new(control_value_set_state_default));
+        m.insert("partightenfactor", Box::new(control_value_set_state_default));
         m.insert("readonlydoc", Box::new(control_value_set_state_default));
         m.insert("shadr", Box::new(control_value_set_state_default));
         m.insert("shadx", Box::new(control_value_set_state_default));
@@ -1916,6 +2005,8 @@ lazy_static::lazy_static! {
         // These are unofficial values used by OpenOffice RTF export filter
         m.insert("hyphlead", Box::new(control_value_set_state_default));
         m.insert("hyphtrail", Box::new(control_value_set_state_default));
+        // Written both inline and in a group of its own, so it's a value rather than a destination
+        m.insert("pgdscno", Box::new(control_value_set_state_default));
         m.insert("pgdscuse", Box::new(control_value_set_state_default));
         m
     };
@@ -1926,9 +2017,10 @@ fn control_flag_set_state_encoding(state: &mut GroupState, name: &str, arg: Opti
         "ansi" => {
             // It's possible that this is supposed to be translated to the host's
             // preferred language codepage, but I think that's only on write, and
-            // is supposed to be followed up by a codepage.  I think in the absence
-            // of a specific codepage, it should default to 1252 (Western European)
-            state.set_codepage(1252u16)
+            // is supposed to be followed up by a codepage.  In the absence of a
+            // specific codepage, take the fallback encoding, which defaults to
+            // 1252 (Western European)
+            state.set_default_codepage(1252u16)
         }
         "pc" => {
             // IBM PC codepage 437
@@ -1949,10 +2041,25 @@ fn control_flag_set_state_encoding(state: &mut GroupState, name: &str, arg: Opti
     state.set_value(name, arg);
 }
 
+fn control_flag_reset_paragraph_properties(state: &mut GroupState, name: &str, arg: Option<i32>) {
+    state.clear_values(PARAGRAPH_PROPERTIES);
+    state.set_value(name, arg);
+}
+
+fn control_flag_reset_character_properties(state: &mut GroupState, name: &str, arg: Option<i32>) {
+    state.clear_values(CHARACTER_PROPERTIES);
+    state.set_value(name, arg);
+}
+
 fn control_value_set_state_default(state: &mut GroupState, name: &str, arg: Option<i32>) {
     state.set_value(name, arg);
 }
 
+fn control_value_set_alignment(state: &mut GroupState, name: &str, arg: Option<i32>) {
+    state.clear_values(PARAGRAPH_ALIGNMENTS);
+    state.set_value(name, arg);
+}
+
 fn control_value_set_state_encoding(state: &mut GroupState, name: &str, arg: Option<i32>) {
     match name {
         "ansicpg" => state.set_codepage(arg.unwrap_or(1252i32) as u16),
@@ -1964,6 +2071,13 @@ fn control_value_set_state_encoding(state: &mut GroupState, name: &str, arg: Opt
     state.set_value(name, arg);
 }
 
+fn control_value_write_unicode_char(state: &mut GroupState, name: &str, arg: Option<i32>) {
+    state.set_value(name, arg);
+    if let Some(value) = arg {
+        state.write_unicode(value);
+    }
+}
+
 fn control_word_ignore(_state: &mut GroupState, name: &str, _arg: Option<i32>) {
     trace!("Ignoring control word {}", name);
 }
@@ -2020,11 +2134,19 @@ fn control_symbol_write_ansi_char(state: &mut GroupState, name: &str, arg: Optio
         }
     };
 
-    if let Some(bytes) = opt_bytes {
-        state.write(bytes);
+    match (name, opt_bytes) {
+        // Only escaped bytes are in the current font's encoding
+        ("'", Some(bytes)) => state.write(bytes),
+        (_, Some(bytes)) => state.write_ansi(bytes),
+        (_, None) => (),
     }
 }
 
+fn control_symbol_end_paragraph(state: &mut GroupState, name: &str, arg: Option<i32>) {
+    control_symbol_write_ansi_char(state, name, arg);
+    state.end_paragraph();
+}
+
 fn control_symbol_next_control_is_optional(state: &mut GroupState, _name: &str, _arg: Option<i32>) {
     state.set_opt_ignore_next_control();
 }
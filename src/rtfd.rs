@@ -0,0 +1,274 @@
+use std::collections::HashMap;
+use std::io::Write;
+use std::{fs, path};
+
+use anyhow::{bail, Context, Result};
+use log::{debug, warn};
+
+use crate::bytes::ByteReader;
+use crate::pictures::PlaceholderStyle;
+
+/// The document inside an RTFD bundle, next to its attachment files
+pub const BUNDLE_DOCUMENT: &str = "TXT.rtf";
+
+/// The signature of a flattened RTFD bundle, as written by
+/// `NSFileWrapper`'s serialized representation and the `com.apple.flat-rtfd`
+/// pasteboard type
+const FLAT_RTFD_SIGNATURE: &[u8; 4] = b"rtfd";
+
+/// An RTFD bundle: the document and the files it refers to
+pub struct Bundle {
+    pub document: Vec<u8>,
+    /// Attachment files by name
+    pub files: HashMap<String, Vec<u8>>,
+}
+
+/// Read an `.rtfd` bundle directory
+pub fn read_bundle_dir(dir: &path::Path) -> Result<Bundle> {
+    let document_path = dir.join(BUNDLE_DOCUMENT);
+    let document = fs::read(&document_path)
+        .with_context(|| format!("Error reading {}", document_path.display()))?;
+    let mut files = HashMap::new();
+    for entry in fs::read_dir(dir).with_context(|| "Error reading RTFD bundle")? {
+        let entry = entry.with_context(|| "Error reading RTFD bundle")?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name == BUNDLE_DOCUMENT || !entry.path().is_file() {
+            continue;
+        }
+        let data = fs::read(entry.path())
+            .with_context(|| format!("Error reading {}", entry.path().display()))?;
+        debug!(
+            "Found RTFD attachment file {} of {} bytes",
+            name,
+            data.len()
+        );
+        files.insert(name, data);
+    }
+    Ok(Bundle { document, files })
+}
+
+/// Whether `data` is a flattened RTFD bundle
+pub fn is_flat_rtfd(data: &[u8]) -> bool {
+    data.starts_with(FLAT_RTFD_SIGNATURE)
+}
+
+/// Unpack a flattened RTFD bundle
+///
+/// After the signature come a version and the number of files, then the
+/// length of each filename, the filenames, the length of each file, and the
+/// file contents, with all numbers as little-endian 32-bit integers.
+pub fn parse_flat_rtfd(data: &[u8]) -> Result<Bundle> {
+    let mut reader = ByteReader::new(data);
+    let truncated = || anyhow::anyhow!("Flattened RTFD bundle is truncated");
+    if reader.bytes(4) != Some(FLAT_RTFD_SIGNATURE.as_slice()) {
+        bail!("Not a flattened RTFD bundle");
+    }
+    let version = reader.u32().ok_or_else(truncated)?;
+    let count = reader.u32().ok_or_else(truncated)? as usize;
+    debug!(
+        "Flattened RTFD bundle version {} with {} files",
+        version, count
+    );
+    if count > reader.remaining() / 8 {
+        bail!(
+            "Flattened RTFD bundle claims {} files, more than it can hold",
+            count
+        );
+    }
+    let name_lengths = (0..count)
+        .map(|_| reader.u32().map(|len| len as usize))
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(truncated)?;
+    let names = name_lengths
+        .iter()
+        .map(|len| reader.bytes(*len).map(file_name))
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(truncated)?;
+    let data_lengths = (0..count)
+        .map(|_| reader.u32().map(|len| len as usize))
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(truncated)?;
+
+    let mut document = None;
+    let mut files = HashMap::new();
+    for (name, len) in names.into_iter().zip(data_lengths) {
+        let contents = reader.bytes(len).ok_or_else(truncated)?.to_vec();
+        match name.as_str() {
+            // The bundle directory itself
+            "" | "." => (),
+            BUNDLE_DOCUMENT => document = Some(contents),
+            _ => {
+                debug!("Found RTFD attachment file {} of {} bytes", name, len);
+                files.insert(name, contents);
+            }
+        }
+    }
+    match document {
+        Some(document) => Ok(Bundle { document, files }),
+        None => bail!("Flattened RTFD bundle has no {}", BUNDLE_DOCUMENT),
+    }
+}
+
+fn file_name(bytes: &[u8]) -> String {
+    let bytes = bytes.split(|b| *b == 0).next().unwrap_or_default();
+    String::from_utf8_lossy(bytes).into_owned()
+}
+
+/// A file attached to a Cocoa document with `\NeXTGraphic`
+#[derive(Clone, Debug)]
+pub struct Attachment {
+    /// The attachment's filename within the RTFD bundle
+    pub filename: String,
+    /// Display dimensions (`\width`/`\height`), in twips
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+}
+
+impl Attachment {
+    pub fn new(filename: &str, properties: &HashMap<String, Option<i32>>) -> Self {
+        let value = |name: &str| properties.get(name).copied().flatten();
+        Attachment {
+            filename: filename.trim().to_owned(),
+            width: value("width"),
+            height: value("height"),
+        }
+    }
+
+    /// The filename with any directory components removed, for copying the
+    /// attachment somewhere else
+    fn safe_filename(&self) -> Option<&str> {
+        self.filename
+            .rsplit(['\\', '/'])
+            .next()
+            .filter(|name| !name.is_empty() && *name != "." && *name != "..")
+    }
+
+    /// The displayed size of the attachment in millimeters
+    pub fn display_size_mm(&self) -> Option<(f64, f64)> {
+        let to_mm = |twips: i32| f64::from(twips) / 1440.0 * 25.4;
+        Some((to_mm(self.width?), to_mm(self.height?)))
+    }
+
+    /// The text standing in for the attachment in the document text
+    pub fn placeholder(&self, style: PlaceholderStyle) -> String {
+        match style {
+            PlaceholderStyle::Text => {
+                let mut placeholder = format!("[attachment: {}", self.filename);
+                if let Some((width, height)) = self.display_size_mm() {
+                    placeholder.push_str(&format!(" {:.0}x{:.0}mm", width, height));
+                }
+                placeholder.push(']');
+                placeholder
+            }
+            PlaceholderStyle::Markdown => format!(
+                "![{}]({})",
+                self.filename,
+                self.filename.replace(' ', "%20")
+            ),
+        }
+    }
+
+    fn to_json(&self, files: &HashMap<String, Vec<u8>>) -> serde_json::Value {
+        let to_points = |twips: Option<i32>| twips.map(|twips| f64::from(twips) / 20.0);
+        serde_json::json!({
+            "filename": self.filename,
+            "width": to_points(self.width),
+            "height": to_points(self.height),
+            "size": files.get(&self.filename).map(Vec::len),
+        })
+    }
+}
+
+/// Copy each attachment's file from the bundle into `dir`
+pub fn write_attachments(
+    attachments: &[Attachment],
+    files: &HashMap<String, Vec<u8>>,
+    dir: &str,
+) -> Result<()> {
+    let dir = path::Path::new(dir);
+    fs::create_dir_all(dir).with_context(|| "Error creating attachment directory")?;
+    for attachment in attachments {
+        let (filename, data) = match (attachment.safe_filename(), files.get(&attachment.filename)) {
+            (Some(filename), Some(data)) => (filename, data),
+            _ => {
+                warn!(
+                    "Attachment {} isn't in the RTFD bundle",
+                    attachment.filename
+                );
+                continue;
+            }
+        };
+        let path = dir.join(filename);
+        debug!("Writing attachment to {}...", path.display());
+        fs::write(&path, data)
+            .with_context(|| format!("Error writing attachment {}", path.display()))?;
+    }
+    Ok(())
+}
+
+/// Write the list of attachments as a JSON array, with their dimensions in points
+pub fn write_json<W: Write>(
+    attachments: &[Attachment],
+    files: &HashMap<String, Vec<u8>>,
+    writer: W,
+) -> Result<()> {
+    let list: Vec<serde_json::Value> = attachments
+        .iter()
+        .map(|attachment| attachment.to_json(files))
+        .collect();
+    serde_json::to_writer_pretty(writer, &list).context("Error writing attachment list")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flat_rtfd(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut data = FLAT_RTFD_SIGNATURE.to_vec();
+        data.extend(3u32.to_le_bytes());
+        data.extend((files.len() as u32).to_le_bytes());
+        for (name, _) in files {
+            data.extend((name.len() as u32 + 1).to_le_bytes());
+        }
+        for (name, _) in files {
+            data.extend(name.as_bytes());
+            data.push(0);
+        }
+        for (_, contents) in files {
+            data.extend((contents.len() as u32).to_le_bytes());
+        }
+        for (_, contents) in files {
+            data.extend(*contents);
+        }
+        data
+    }
+
+    #[test]
+    fn unpacks_flattened_bundles() {
+        let data = flat_rtfd(&[
+            (".", b""),
+            (BUNDLE_DOCUMENT, b"{\\rtf1 Text}"),
+            ("Pasted Graphic.png", b"PNG"),
+        ]);
+        assert!(is_flat_rtfd(&data));
+        let bundle = parse_flat_rtfd(&data).unwrap();
+        assert_eq!(bundle.document, b"{\\rtf1 Text}");
+        assert_eq!(bundle.files.len(), 1);
+        assert_eq!(bundle.files["Pasted Graphic.png"], b"PNG");
+    }
+
+    #[test]
+    fn rejects_malformed_bundles() {
+        assert!(!is_flat_rtfd(b"{\\rtf1}"));
+        assert!(parse_flat_rtfd(b"{\\rtf1}").is_err());
+        let data = flat_rtfd(&[(BUNDLE_DOCUMENT, b"{\\rtf1 Text}"), ("a.png", b"PNG")]);
+        for len in [4, 12, 20, data.len() - 1] {
+            assert!(parse_flat_rtfd(&data[..len]).is_err(), "cut at {}", len);
+        }
+        // A file count far beyond the size of the bundle
+        let mut huge = data.clone();
+        huge[8..12].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(parse_flat_rtfd(&huge).is_err());
+        assert!(parse_flat_rtfd(&flat_rtfd(&[("a.png", b"PNG")])).is_err());
+    }
+}
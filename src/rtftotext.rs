@@ -1,15 +1,194 @@
 use std::cell::RefCell;
 use std::collections::HashMap;
+use std::convert::TryFrom;
 use std::io::{Read, Write};
 use std::rc::Rc;
 
 use anyhow::{Context, Result};
-use log::{debug, info, warn};
+use log::{debug, info, trace, warn};
 
 use rtf_grimoire::tokenizer::parse_finished as parse_tokens;
 use rtf_grimoire::tokenizer::Token;
 
+use crate::annotations::{Annotation, AnnotationCollector, AnnotationMode};
+use crate::colors::{Color, ColorCollector};
+use crate::compressed;
+use crate::encodings::{self, Fallback};
+use crate::forms::{FormField, FormFieldCollector};
+use crate::lists::ListCollector;
+use crate::math::{self, MathCollector, MathStyle};
+use crate::objects::{EmbeddedObject, ObjectCollector};
+use crate::pictures::{Picture, PictureAlternative, PlaceholderStyle};
+use crate::revisions::{RevisionKind, RevisionMark, RevisionMode, RevisionTracker};
 use crate::rtf_control;
+use crate::rtfd::Attachment;
+use crate::sections::{PageStyle, Section, SectionCollector};
+use crate::shapes::{FrameKind, ShapeCollector};
+use crate::stylesheet::{first_set, StyleKind, Stylesheet};
+use crate::symbols::{self, FontTable, SymbolFont};
+
+/// Options controlling how a document is converted
+#[derive(Clone, Default)]
+pub struct ConvertOptions {
+    pub annotations: AnnotationMode,
+    pub revisions: RevisionMode,
+    /// Write hidden (`\v`, `\webhidden` and `\spv`) text instead of suppressing it
+    pub include_hidden: bool,
+    /// Write a placeholder into the text wherever a picture appears
+    pub image_placeholders: Option<PlaceholderStyle>,
+    /// Which of the duplicate copies Word writes of each picture (and shape) to keep
+    pub picture_alternative: PictureAlternative,
+    /// Write the text of text boxes and other shapes where the shape is anchored
+    pub include_shape_text: bool,
+    /// How equations are written
+    pub math: MathStyle,
+    /// Decode text that's valid UTF-8 as UTF-8, whatever codepage the document declares
+    pub assume_utf8: bool,
+    /// The encoding to decode text in, whatever codepage the document declares
+    pub forced_encoding: Option<&'static encoding_rs::Encoding>,
+    /// Where to find an encoding for documents that don't declare a usable codepage
+    pub fallback_encodings: Vec<Fallback>,
+}
+
+/// The result of processing a document's token stream
+pub struct Document {
+    pub text: String,
+    pub annotations: Vec<Annotation>,
+    pub paragraphs: Vec<Paragraph>,
+    pub runs: Vec<TextRun>,
+    pub stylesheet: Stylesheet,
+    pub pictures: Vec<Picture>,
+    pub objects: Vec<EmbeddedObject>,
+    pub form_fields: Vec<FormField>,
+    /// Files attached with `\NeXTGraphic`, as found in RTFD bundles
+    pub attachments: Vec<Attachment>,
+    /// The color table, indexed by `\cf`, with `None` for the automatic color
+    pub colors: Vec<Option<Color>>,
+    /// OpenOffice page styles from the `\pgdsctbl`
+    pub page_styles: Vec<PageStyle>,
+    pub sections: Vec<Section>,
+}
+
+impl Document {
+    pub fn paragraph_text(&self, paragraph: &Paragraph) -> &str {
+        &self.text[paragraph.start..paragraph.end]
+    }
+
+    /// The color of a run of text, if it has one other than the automatic color
+    pub fn run_color(&self, format: &CharacterFormat) -> Option<Color> {
+        let index = usize::try_from(format.color?).ok()?;
+        self.colors.get(index).copied().flatten()
+    }
+
+    pub fn paragraph_style_name(&self, paragraph: &Paragraph) -> Option<&str> {
+        self.stylesheet
+            .get(StyleKind::Paragraph, paragraph.style.unwrap_or(0))
+            .map(|style| style.name.as_str())
+    }
+
+    /// The zero-based heading level of a paragraph (0 is "heading 1"), from its
+    /// own `\outlinelevel` or else from its style
+    pub fn heading_level(&self, paragraph: &Paragraph) -> Option<i32> {
+        match paragraph.outline_level {
+            Some(level) if (0..9).contains(&level) => Some(level),
+            Some(_) => None,
+            None => self.stylesheet.outline_level(paragraph.style.unwrap_or(0)),
+        }
+    }
+}
+
+/// A paragraph of the document body, as a byte range of `Document::text`
+#[derive(Clone, Debug)]
+pub struct Paragraph {
+    pub start: usize,
+    pub end: usize,
+    /// The `\s` paragraph style in effect when the paragraph ended
+    pub style: Option<i32>,
+    /// The `\outlinelevel` set directly on the paragraph
+    pub outline_level: Option<i32>,
+    /// The effective `\li` left indent, in twips
+    pub left_indent: i32,
+    /// The effective `\fi` first line indent, relative to the left indent, in twips
+    pub first_line_indent: i32,
+    pub alignment: Alignment,
+}
+
+/// The effective alignment of a paragraph, from `\ql`, `\qc`, `\qr`, `\qj` or `\qd`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Alignment {
+    #[default]
+    Left,
+    Center,
+    Right,
+    Justified,
+    Distributed,
+}
+
+impl Alignment {
+    fn from_control_word(name: &str) -> Alignment {
+        match name {
+            "qc" => Alignment::Center,
+            "qr" => Alignment::Right,
+            "qj" => Alignment::Justified,
+            "qd" => Alignment::Distributed,
+            _ => Alignment::Left,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Alignment::Left => "left",
+            Alignment::Center => "center",
+            Alignment::Right => "right",
+            Alignment::Justified => "justified",
+            Alignment::Distributed => "distributed",
+        }
+    }
+}
+
+/// The effective character formatting of a run of text, after applying the
+/// document defaults, paragraph and character styles, and direct formatting
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CharacterFormat {
+    pub bold: bool,
+    pub italic: bool,
+    pub strike: bool,
+    /// The `\cf` color table index, unless it's the automatic color
+    pub color: Option<i32>,
+    /// The `\f` font table index
+    pub font: Option<i32>,
+    /// The `\fs` font size, in half-points
+    pub size: Option<i32>,
+}
+
+/// A run of document body text with uniform character formatting, as a
+/// byte range of `Document::text`
+#[derive(Clone, Debug)]
+pub struct TextRun {
+    pub start: usize,
+    pub end: usize,
+    pub format: CharacterFormat,
+}
+
+/// The paragraph and run structure of the document body, built up as the
+/// body text is written
+#[derive(Default)]
+pub struct Layout {
+    paragraphs: Vec<Paragraph>,
+    runs: Vec<TextRun>,
+}
+
+impl Layout {
+    fn add_run(&mut self, start: usize, end: usize, format: CharacterFormat) {
+        if start == end {
+            return;
+        }
+        match self.runs.last_mut() {
+            Some(last) if last.end == start && last.format == format => last.end = end,
+            _ => self.runs.push(TextRun { start, end, format }),
+        }
+    }
+}
 
 #[derive(Clone)]
 pub enum Destination {
@@ -25,6 +204,18 @@ impl Destination {
         }
     }
 
+    fn len(&self) -> usize {
+        self.as_bytes().len()
+    }
+
+    /// Remove and return everything written to the destination past `start`
+    fn split_off(&mut self, start: usize) -> Destination {
+        match self {
+            Destination::Text(text) => Destination::Text(text.split_off(start)),
+            Destination::Bytes(bytes) => Destination::Bytes(bytes.split_off(start)),
+        }
+    }
+
     fn append_text(&mut self, new_text: &str) {
         if let Destination::Text(string) = self {
             string.push_str(new_text);
@@ -47,26 +238,105 @@ impl Destination {
  */
 #[derive(Clone)]
 pub struct GroupState {
+    options: Rc<ConvertOptions>,
     destinations: Rc<RefCell<HashMap<String, Destination>>>,
+    revisions: Rc<RefCell<RevisionTracker>>,
+    layout: Rc<RefCell<Layout>>,
+    stylesheet: Rc<RefCell<Stylesheet>>,
     cur_destination: Option<String>,
+    // Length of the current destination at the point this group switched to it,
+    // so that its contents can be folded back out when the group ends
+    dest_start: Option<usize>,
     dest_encoding: Option<&'static encoding_rs::Encoding>,
+    // The encoding from the fallback chain, for when the document doesn't declare a usable one
+    fallback_encoding: Option<&'static encoding_rs::Encoding>,
+    // Whether the encoding is the fallback rather than one the document declared
+    uses_fallback: bool,
+    // The symbol fonts of the font table
+    fonts: Rc<RefCell<FontTable>>,
     values: HashMap<String, Option<i32>>,
     opt_ignore_next_control: bool,
+    // Number of fallback characters still to skip after a \u character
+    unicode_skip: usize,
+    // The first half of a \u surrogate pair, waiting for the second
+    high_surrogate: Option<u16>,
 }
 
 impl GroupState {
-    pub fn new(destinations: Rc<RefCell<HashMap<String, Destination>>>) -> Self {
+    pub fn new(
+        options: Rc<ConvertOptions>,
+        destinations: Rc<RefCell<HashMap<String, Destination>>>,
+        revisions: Rc<RefCell<RevisionTracker>>,
+        layout: Rc<RefCell<Layout>>,
+        stylesheet: Rc<RefCell<Stylesheet>>,
+        fallback_encoding: Option<&'static encoding_rs::Encoding>,
+        fonts: Rc<RefCell<FontTable>>,
+    ) -> Self {
         Self {
+            dest_encoding: options.forced_encoding.or(fallback_encoding),
+            fallback_encoding,
+            uses_fallback: options.forced_encoding.is_none(),
+            fonts,
+            options,
             destinations,
+            revisions,
+            layout,
+            stylesheet,
             cur_destination: None,
-            dest_encoding: None,
+            dest_start: None,
             values: HashMap::new(),
             opt_ignore_next_control: false,
+            unicode_skip: 0,
+            high_surrogate: None,
         }
     }
 
     pub fn set_codepage(&mut self, cp: u16) {
-        self.dest_encoding = codepage::to_encoding(cp);
+        if self.options.forced_encoding.is_some() {
+            trace!("Ignoring codepage {} in favor of the forced encoding", cp);
+            return;
+        }
+        self.uses_fallback = false;
+        self.dest_encoding = match codepage::to_encoding(cp) {
+            Some(encoding) => Some(encoding),
+            None => {
+                self.uses_fallback = true;
+                warn!(
+                    "Unknown codepage {}, falling back to {}",
+                    cp,
+                    self.fallback_encoding
+                        .map_or("no encoding", encoding_rs::Encoding::name)
+                );
+                self.fallback_encoding
+            }
+        };
+    }
+
+    /// Use the encoding from the fallback chain, or else codepage `cp`, for a
+    /// character set that leaves the codepage up to the reader, like `\ansi`
+    pub fn set_default_codepage(&mut self, cp: u16) {
+        match self.fallback_encoding {
+            Some(encoding) if self.options.forced_encoding.is_none() => {
+                self.dest_encoding = Some(encoding);
+                self.uses_fallback = true;
+            }
+            _ => self.set_codepage(cp),
+        }
+    }
+
+    /// Work through the fallback chain again now that the document has named
+    /// its default languages, and switch to the result if the document hasn't
+    /// declared an encoding of its own
+    pub fn update_fallback_encoding(&mut self) {
+        let languages: Vec<i32> = ["deflang", "adeflang"]
+            .iter()
+            .filter_map(|name| self.get_value(name))
+            .collect();
+        self.fallback_encoding =
+            encodings::fallback_encoding(&self.options.fallback_encodings, &languages);
+        if self.uses_fallback {
+            self.dest_encoding = self.fallback_encoding;
+        }
     }
 
     pub fn get_encoding(&mut self) -> Option<&'static encoding_rs::Encoding> {
@@ -108,13 +378,43 @@ impl GroupState {
                 }
             }
         }
+        self.dest_start = dest.get(name).map(Destination::len);
     }
 
     pub fn get_destination_name(&self) -> Option<String> {
         self.cur_destination.clone()
     }
 
+    /// Write text, or the byte of a `\'xx` escape, in the current font
     pub fn write(&mut self, bytes: &[u8]) {
+        self.write_bytes(bytes, true);
+    }
+
+    /// Whether `\'xx` escapes are decoded together rather than one at a
+    /// time, as a character in a multi-byte codepage takes several of them
+    pub fn decodes_escapes_together(&self) -> bool {
+        self.unicode_skip == 0
+            && self
+                .dest_encoding
+                .is_some_and(|encoding| !encoding.is_single_byte())
+    }
+
+    /// Write the characters a control word stands for, such as the tab of
+    /// `\tab` or the bullet of `\bullet`, which are the same in any font
+    pub fn write_ansi(&mut self, bytes: &[u8]) {
+        self.write_bytes(bytes, false);
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8], in_font: bool) {
+        // Each byte of text, or \'xx escape, counts as one fallback character
+        let skipped = self.unicode_skip.min(bytes.len());
+        self.unicode_skip -= skipped;
+        let bytes = &bytes[skipped..];
+        if bytes.is_empty() {
+            return;
+        }
+        // Text between the halves of a surrogate pair breaks it up
+        self.high_surrogate = None;
         let dest_name = match self.get_destination_name() {
             Some(name) => name,
             None => {
@@ -127,9 +427,21 @@ impl GroupState {
         };
         if let Some(dest) = (*self.destinations).borrow_mut().get_mut(&dest_name) {
             match dest {
+                Destination::Text(_) if self.is_suppressed_hidden(&dest_name) => {
+                    trace!("Suppressing hidden text in {}: {:?}", dest_name, bytes);
+                }
                 Destination::Text(_) => {
-                    if let Some(ref mut decoder) = self.dest_encoding {
-                        dest.append_text(&decoder.decode(bytes).0);
+                    let font = self.symbol_font(&dest_name).filter(|_| in_font);
+                    let text = match font {
+                        Some(font) => Some(font.decode(bytes).into()),
+                        None => encodings::decode_text(
+                            bytes,
+                            self.dest_encoding,
+                            self.options.assume_utf8,
+                        ),
+                    };
+                    if let Some(text) = text {
+                        self.append_document_text(&dest_name, dest, &text);
                     } else {
                         warn!(
                             "Writing to a text destination ({}) with no encoding set!",
@@ -144,6 +456,103 @@ impl GroupState {
         }
     }
 
+    /// Write the character of a `\uN` control word, skipping the fallback
+    /// characters (`\ucN`, 1 by default) that follow it
+    pub fn write_unicode(&mut self, value: i32) {
+        // Values above 32767 are written as negative numbers
+        let unit = (value as i64).rem_euclid(0x10000) as u16;
+        let c = match (self.high_surrogate.take(), unit) {
+            (_, 0xD800..=0xDBFF) => {
+                self.high_surrogate = Some(unit);
+                None
+            }
+            (Some(high), 0xDC00..=0xDFFF) => {
+                char::decode_utf16([high, unit]).next().and_then(Result::ok)
+            }
+            (_, 0xDC00..=0xDFFF) => None,
+            (_, unit) => char::from_u32(u32::from(unit)),
+        };
+        self.skip_unicode_fallback();
+
+        let dest_name = self.get_destination_name().unwrap_or_default();
+        let c = match (c, self.symbol_font(&dest_name)) {
+            (Some(c), Some(font)) => font.map_unicode(c),
+            (Some(c), None) => c,
+            (None, _) => return,
+        };
+        match (*self.destinations).borrow_mut().get_mut(&dest_name) {
+            Some(dest @ Destination::Text(_)) => {
+                if !self.is_suppressed_hidden(&dest_name) {
+                    self.append_document_text(&dest_name, dest, c.encode_utf8(&mut [0; 4]));
+                }
+            }
+            // Byte destinations hold encoded data, so leave the fallback in place
+            _ => self.unicode_skip = 0,
+        }
+    }
+
+    /// Skip the fallback characters the document writes after a `\u` character
+    pub fn skip_unicode_fallback(&mut self) {
+        self.unicode_skip = self.get_value("uc").unwrap_or(1).max(0) as usize;
+    }
+
+    /// Append decoded text to a text destination, through the revision
+    /// filter, recording the character formatting of document body text
+    fn append_document_text(&self, dest_name: &str, dest: &mut Destination, text: &str) {
+        let mark = if rtf_control::TABLE_DESTINATIONS.contains(&dest_name) {
+            None
+        } else {
+            self.get_revision_mark()
+        };
+        if let Some(text) = (*self.revisions).borrow_mut().filter(dest_name, mark, text) {
+            let start = dest.len();
+            dest.append_text(&text);
+            if dest_name == "rtf" {
+                let format = self.get_character_format();
+                (*self.layout)
+                    .borrow_mut()
+                    .add_run(start, dest.len(), format);
+            }
+        }
+    }
+
+    /// Append raw binary data (from `\bin`) to the current destination
+    pub fn write_binary(&mut self, data: &[u8]) {
+        let dest_name = self.get_destination_name().unwrap_or_default();
+        match (*self.destinations).borrow_mut().get_mut(&dest_name) {
+            Some(dest @ Destination::Bytes(_)) => dest.append_bytes(data),
+            _ => warn!(
+                "Discarding {} bytes of binary data outside of a byte destination ({})",
+                data.len(),
+                dest_name
+            ),
+        }
+    }
+
+    /// Append already-decoded text to the current destination
+    pub fn write_text(&mut self, text: &str) {
+        let dest_name = match self.get_destination_name() {
+            Some(name) => name,
+            None => {
+                warn!(
+                    "Document format error: Document text found outside of any document group: '{}'",
+                    text
+                );
+                return;
+            }
+        };
+        match (*self.destinations).borrow_mut().get_mut(&dest_name) {
+            Some(dest @ Destination::Text(_)) => dest.append_text(text),
+            Some(Destination::Bytes(_)) => {
+                warn!(
+                    "Discarding text written to a byte destination ({}): '{}'",
+                    dest_name, text
+                );
+            }
+            None => panic!("Programming error: specified destination {} doesn't exist after verifying its existence", dest_name),
+        }
+    }
+
     pub fn set_opt_ignore_next_control(&mut self) {
         self.opt_ignore_next_control = true;
     }
@@ -157,24 +566,207 @@ impl GroupState {
     pub fn set_value(&mut self, name: &str, value: Option<i32>) {
         self.values.insert(name.to_string(), value);
     }
+
+    /// Remove control words from this group's state, returning them to their defaults
+    pub fn clear_values(&mut self, names: &[&str]) {
+        for name in names {
+            self.values.remove(*name);
+        }
+    }
+
+    /// Control words whose values in this group differ from those in `parent`
+    pub fn values_set_since(&self, parent: &GroupState) -> HashMap<String, Option<i32>> {
+        self.values
+            .iter()
+            .filter(|(name, value)| parent.values.get(*name) != Some(value))
+            .map(|(name, value)| (name.clone(), *value))
+            .collect()
+    }
+
+    /// Mark the end of a paragraph of the document body text
+    pub fn end_paragraph(&mut self) {
+        if self.cur_destination.as_deref() != Some("rtf") {
+            return;
+        }
+        let end = (*self.destinations)
+            .borrow()
+            .get("rtf")
+            .map(Destination::len)
+            .unwrap_or(0);
+        let paragraphs = &mut (*self.layout).borrow_mut().paragraphs;
+        let start = paragraphs.last().map(|last| last.end).unwrap_or(0);
+        paragraphs.push(Paragraph {
+            start,
+            end,
+            style: self.get_value("s"),
+            outline_level: self.get_value("outlinelevel"),
+            left_indent: self.get_effective_value("li").flatten().unwrap_or(0),
+            first_line_indent: self.get_effective_value("fi").flatten().unwrap_or(0),
+            alignment: self.get_alignment(),
+        });
+    }
+
+    /// The symbol font that text written to `dest_name` is in, if it's in one
+    ///
+    /// Font names and other table entries are left alone, even where they set
+    /// a symbol font.
+    fn symbol_font(&self, dest_name: &str) -> Option<SymbolFont> {
+        if rtf_control::TABLE_DESTINATIONS.contains(&dest_name) {
+            return None;
+        }
+        let font = self.get_effective_value("f").flatten()?;
+        (*self.fonts).borrow().symbol_font(font)
+    }
+
+    /// The parameter of the most recent occurrence of a control word in this group
+    pub fn get_value(&self, name: &str) -> Option<i32> {
+        self.values.get(name).copied().flatten()
+    }
+
+    /// The effective setting of a formatting control word: direct formatting in
+    /// this group, then the character style, then the paragraph style, and finally
+    /// the document's `\defchp`/`\defpap` defaults
+    ///
+    /// Returns `None` if the control word isn't set anywhere.
+    pub fn get_effective_value(&self, name: &str) -> Option<Option<i32>> {
+        if let Some(value) = self.values.get(name) {
+            return Some(*value);
+        }
+        let stylesheet = (*self.stylesheet).borrow();
+        self.get_value("cs")
+            .and_then(|cs| stylesheet.lookup(StyleKind::Character, cs, name))
+            .or_else(|| {
+                stylesheet.lookup(StyleKind::Paragraph, self.get_value("s").unwrap_or(0), name)
+            })
+            .or_else(|| stylesheet.lookup_default(name))
+    }
+
+    /// The effective paragraph alignment: the alignment set directly on the
+    /// paragraph, then by its style, then by the document defaults
+    fn get_alignment(&self) -> Alignment {
+        let names = rtf_control::PARAGRAPH_ALIGNMENTS;
+        let stylesheet = (*self.stylesheet).borrow();
+        first_set(&self.values, names)
+            .or_else(|| {
+                stylesheet.lookup_first(
+                    StyleKind::Paragraph,
+                    self.get_value("s").unwrap_or(0),
+                    names,
+                )
+            })
+            .or_else(|| stylesheet.lookup_default_first(names))
+            .map(Alignment::from_control_word)
+            .unwrap_or_default()
+    }
+
+    /// Whether a toggle is on; `\b` and `\b1` turn a toggle on, `\b0` turns it off
+    pub fn get_toggle(&self, name: &str) -> bool {
+        match self.get_effective_value(name) {
+            Some(Some(0)) | None => false,
+            Some(_) => true,
+        }
+    }
+
+    fn get_character_format(&self) -> CharacterFormat {
+        CharacterFormat {
+            bold: self.get_toggle("b"),
+            italic: self.get_toggle("i"),
+            strike: self.get_toggle("strike") || self.get_toggle("striked"),
+            color: self
+                .get_effective_value("cf")
+                .flatten()
+                .filter(|index| *index > 0),
+            font: self.get_effective_value("f").flatten(),
+            size: self.get_effective_value("fs").flatten(),
+        }
+    }
+
+    fn is_suppressed_hidden(&self, dest_name: &str) -> bool {
+        !self.options.include_hidden
+            && !rtf_control::TABLE_DESTINATIONS.contains(&dest_name)
+            && (self.get_toggle("v") || self.get_toggle("webhidden") || self.get_toggle("spv"))
+    }
+
+    fn get_revision_mark(&self) -> Option<RevisionMark> {
+        if self.get_toggle("deleted") {
+            Some(RevisionMark {
+                kind: RevisionKind::Deletion,
+                author: self.get_value("revauthdel"),
+                date: self.get_value("revdttmdel"),
+            })
+        } else if self.get_toggle("revised") {
+            Some(RevisionMark {
+                kind: RevisionKind::Insertion,
+                author: self.get_value("revauth"),
+                date: self.get_value("revdttm"),
+            })
+        } else {
+            None
+        }
+    }
 }
 
 #[derive(Clone)]
 struct DocumentState {
+    options: Rc<ConvertOptions>,
     destinations: Rc<RefCell<HashMap<String, Destination>>>,
     group_stack: Vec<GroupState>,
+    annotations: AnnotationCollector,
+    revisions: Rc<RefCell<RevisionTracker>>,
+    layout: Rc<RefCell<Layout>>,
+    stylesheet: Rc<RefCell<Stylesheet>>,
+    pictures: Vec<Picture>,
+    attachments: Vec<Attachment>,
+    shapes: ShapeCollector,
+    objects: ObjectCollector,
+    form_fields: FormFieldCollector,
+    lists: ListCollector,
+    sections: SectionCollector,
+    colors: ColorCollector,
+    math: MathCollector,
+    // Escaped bytes in a multi-byte codepage, held until the next token so
+    // that characters split across escapes are decoded whole
+    escaped_bytes: Vec<u8>,
+    // Whether an attachment has just closed, so that the character Cocoa
+    // writes in its place can be left out
+    after_attachment: bool,
+    fallback_encoding: Option<&'static encoding_rs::Encoding>,
+    fonts: Rc<RefCell<FontTable>>,
 }
 
 impl DocumentState {
-    fn new() -> Self {
+    fn new(options: &ConvertOptions) -> Self {
         Self {
+            options: Rc::new(options.clone()),
             destinations: Rc::new(RefCell::new(HashMap::new())),
             group_stack: Vec::new(),
+            annotations: AnnotationCollector::default(),
+            revisions: Rc::new(RefCell::new(RevisionTracker::new(options.revisions))),
+            layout: Rc::new(RefCell::new(Layout::default())),
+            stylesheet: Rc::new(RefCell::new(Stylesheet::default())),
+            pictures: Vec::new(),
+            attachments: Vec::new(),
+            shapes: ShapeCollector::default(),
+            objects: ObjectCollector::default(),
+            form_fields: FormFieldCollector::default(),
+            lists: ListCollector::default(),
+            sections: SectionCollector::default(),
+            colors: ColorCollector::default(),
+            math: MathCollector::default(),
+            escaped_bytes: Vec::new(),
+            after_attachment: false,
+            fallback_encoding: None,
+            fonts: Rc::new(RefCell::new(FontTable::default())),
         }
     }
 
-    fn do_control_bin(&mut self, _data: &[u8], _word_is_optional: bool) {
-        // We don't support handling control bins
+    fn do_control_bin(&mut self, data: &[u8], _word_is_optional: bool) {
+        if let Some(group_state) = self.get_last_group_mut() {
+            group_state.set_value("bin", Some(data.len() as i32));
+            group_state.write_binary(data);
+        } else {
+            warn!("Document format error: Binary data found outside of any document group");
+        }
     }
 
     fn do_control_symbol(&mut self, symbol: char, word_is_optional: bool) {
@@ -204,12 +796,35 @@ impl DocumentState {
         if let Some(group_state) = self.get_last_group_mut() {
             if let Some(dest_handler) = rtf_control::DESTINATIONS.get(name) {
                 dest_handler(group_state, name, arg);
+                if group_state.dest_start.is_some()
+                    && group_state.cur_destination.as_deref() == Some(name)
+                {
+                    self.open_destination(name);
+                }
             } else if let Some(symbol_handler) = rtf_control::SYMBOLS.get(name) {
                 symbol_handler(group_state, name, arg);
+                if name == "sect" {
+                    let position = self.text_position();
+                    self.sections.end_section(position);
+                }
             } else if let Some(value_handler) = rtf_control::VALUES.get(name) {
                 value_handler(group_state, name, arg);
+                if name == "pgdscno" {
+                    self.sections.use_page_style(arg);
+                }
+                // Only the header's default languages speak for the whole
+                // document, not those of objects or shapes nested in the body
+                if matches!(name, "deflang" | "adeflang")
+                    && self.group_stack.len() == 1
+                    && self.text_position() == 0
+                {
+                    if let Some(group) = self.get_last_group_mut() {
+                        group.update_fallback_encoding();
+                    }
+                }
             } else if let Some(flag_handler) = rtf_control::FLAGS.get(name) {
                 flag_handler(group_state, name, arg);
+                self.open_field_part(name);
             } else if let Some(toggle_handler) = rtf_control::TOGGLES.get(name) {
                 toggle_handler(group_state, name, arg);
             } else if word_is_optional {
@@ -217,6 +832,11 @@ impl DocumentState {
             } else {
                 warn!("Unsupported/illegal control word \\{}", name);
             }
+            if self.in_destination(ColorCollector::handles_destination) {
+                self.colors.control_word(name, arg);
+            } else if self.in_destination(FontTable::handles_destination) {
+                (*self.fonts).borrow_mut().control_word(name, arg);
+            }
         } else {
             warn!(
                 "Document format error: Control word found outside of any document group: '\\{}'",
@@ -225,6 +845,147 @@ impl DocumentState {
         }
     }
 
+    /// Note the start of a destination whose contents are collected once it's folded
+    fn open_destination(&mut self, name: &str) {
+        match name {
+            "shp" => self.shapes.open(FrameKind::Shape),
+            "pict" => self.shapes.open(FrameKind::Picture),
+            _ if MathCollector::handles_destination(name) => {
+                // Collecting the enclosing text can move the start of a
+                // destination nested inside one of the same name
+                self.take_math_text();
+                let start = (*self.destinations)
+                    .borrow()
+                    .get(name)
+                    .map(Destination::len)
+                    .unwrap_or(0);
+                if let Some(group) = self.get_last_group_mut() {
+                    group.dest_start = Some(start);
+                }
+                self.math.open(name, start);
+            }
+            _ => (),
+        }
+    }
+
+    /// Move the text written so far to the innermost math destination into
+    /// its node, so that it stays in order with the destinations nested in it
+    fn take_math_text(&mut self) {
+        let (name, start) = match self.math.current() {
+            Some((name, start)) => (name.to_owned(), start),
+            None => return,
+        };
+        if let Some(text) = self.take_destination_text(&name, start) {
+            self.math.push_text(text);
+        }
+    }
+
+    /// Note the start of a field, or of its result, so that form fields can
+    /// be matched up with their values when the group closes
+    fn open_field_part(&mut self, name: &str) {
+        let depth = self.group_stack.len();
+        match name {
+            "field" => self.form_fields.open_field(depth),
+            "fldrslt" => {
+                let destination = match self
+                    .get_last_group()
+                    .and_then(GroupState::get_destination_name)
+                {
+                    Some(destination) => destination,
+                    None => return,
+                };
+                let start = match (*self.destinations).borrow().get(&destination) {
+                    Some(Destination::Text(text)) => text.len(),
+                    _ => return,
+                };
+                self.form_fields.open_result(depth, &destination, start);
+            }
+            _ => (),
+        }
+    }
+
+    /// Close any field or field result that belonged to the group that has just
+    /// ended, writing the value of a checkbox or dropdown in place of its field
+    fn close_field_parts(&mut self) {
+        let depth = self.group_stack.len();
+        if let Some((destination, start)) = self.form_fields.close_result(depth) {
+            let text = match (*self.destinations).borrow().get(&destination) {
+                Some(Destination::Text(text)) => text.get(start..).unwrap_or_default().to_owned(),
+                _ => String::new(),
+            };
+            self.form_fields.set_result_text(&text);
+        }
+        if let Some(text) = self.form_fields.close_field(depth) {
+            self.write_to_content_destination(&text);
+        }
+    }
+
+    /// Add text from a shape to the shape enclosing it, or if it's the
+    /// outermost shape, write it on its own line where the shape is anchored
+    fn write_shape_text(&mut self, text: &str) {
+        if text.is_empty() || self.shapes.append_text(text) {
+            return;
+        }
+        self.write_to_content_destination(&format!("\n{}", text));
+    }
+
+    /// Write text into the innermost enclosing destination that holds text,
+    /// for content found inside byte destinations like shapes and pictures
+    fn write_to_content_destination(&mut self, text: &str) {
+        let destinations = self.destinations.clone();
+        let is_text_destination = |group: &&mut GroupState| {
+            group
+                .get_destination_name()
+                .and_then(|name| {
+                    (*destinations)
+                        .borrow()
+                        .get(&name)
+                        .map(|dest| matches!(dest, Destination::Text(_)))
+                })
+                .unwrap_or(false)
+        };
+        match self.group_stack.iter_mut().rev().find(is_text_destination) {
+            Some(group) => group.write_text(text),
+            None => warn!("No text destination to write '{}' to", text),
+        }
+    }
+
+    /// Add a decoded picture, returning its index
+    ///
+    /// A picture with the same data as an earlier one is only kept once, so
+    /// repeated logos and the like are extracted once and share a placeholder.
+    fn add_picture(&mut self, picture: Picture) -> usize {
+        let repeat = self
+            .pictures
+            .iter()
+            .position(|seen| seen.format == picture.format && seen.data == picture.data);
+        if let Some(index) = repeat {
+            debug!("Picture repeats picture {}", index + 1);
+            return index;
+        }
+        self.pictures.push(picture);
+        self.pictures.len() - 1
+    }
+
+    fn write_picture_placeholders(&mut self, indexes: &[usize]) {
+        let style = match self.options.image_placeholders {
+            Some(style) => style,
+            None => return,
+        };
+        let placeholders: String = indexes
+            .iter()
+            .map(|index| self.pictures[*index].placeholder(*index, style))
+            .collect();
+        self.write_to_content_destination(&placeholders);
+    }
+
+    /// Whether the current destination is one that `handles_destination` accepts
+    fn in_destination(&self, handles_destination: fn(&str) -> bool) -> bool {
+        self.get_last_group()
+            .and_then(|group| group.cur_destination.as_deref())
+            .is_some_and(handles_destination)
+    }
+
     fn write_to_current_destination(&mut self, bytes: &[u8]) {
         if let Some(group) = self.get_last_group_mut() {
             group.write(bytes);
@@ -239,22 +1000,337 @@ impl DocumentState {
 
     fn start_group(&mut self) {
         if let Some(last_group) = self.get_last_group() {
-            self.group_stack.push(last_group.clone());
+            let mut group = last_group.clone();
+            // The new group inherits the destination, but it's the parent that owns it
+            group.dest_start = None;
+            group.unicode_skip = 0;
+            self.group_stack.push(group);
         } else {
             debug!("Creating initial group...");
-            self.group_stack
-                .push(GroupState::new(self.destinations.clone()));
+            self.group_stack.push(GroupState::new(
+                self.options.clone(),
+                self.destinations.clone(),
+                self.revisions.clone(),
+                self.layout.clone(),
+                self.stylesheet.clone(),
+                self.fallback_encoding,
+                self.fonts.clone(),
+            ));
         }
     }
 
     fn end_group(&mut self) {
-        if let Some(_group) = self.group_stack.pop() {
-            // TODO: destination-folding support (tables, etc)
+        if let Some(group) = self.group_stack.pop() {
+            match (group.get_destination_name(), group.dest_start) {
+                (Some(name), Some(start)) => self.fold_destination(&name, start, &group),
+                (Some(name), None) if name == "stylesheet" => self.fold_table_entry(&name, &group),
+                (Some(name), None) if name == "formfield" => {
+                    let properties = self.group_properties(&group);
+                    self.form_fields.add_properties(properties);
+                }
+                _ => (),
+            }
+            self.close_field_parts();
         } else {
             warn!("Document format error: End group count exceeds number start groups");
         }
     }
 
+    /// Hand the contents of a destination that has just closed to whatever
+    /// collects that kind of destination, removing them from the destination
+    /// so that the next occurrence starts out empty
+    fn fold_destination(&mut self, name: &str, start: usize, group: &GroupState) {
+        match name {
+            "defchp" | "defpap" => {
+                let properties = self.group_properties(group);
+                (*self.stylesheet)
+                    .borrow_mut()
+                    .add_defaults(name, properties);
+            }
+            "pict" => {
+                let properties = self.group_properties(group);
+                let frame = self.shapes.close(FrameKind::Picture);
+                let contents = self.take_destination_contents(name, start);
+                if self.is_discarded_alternative(group) {
+                    debug!("Skipping alternate copy of picture");
+                    return;
+                }
+                if let Some(Destination::Bytes(data)) = contents {
+                    let mut picture = Picture::new(&data, &properties);
+                    if let Some(frame) = frame {
+                        picture.properties = frame.properties;
+                    }
+                    let index = self.add_picture(picture);
+                    // Pictures inside a shape are reported along with the shape's properties
+                    if !self.shapes.add_picture(index) {
+                        self.write_picture_placeholders(&[index]);
+                    }
+                }
+            }
+            "fldinst" => {
+                let instruction = self.take_destination_text(name, start).unwrap_or_default();
+                if let Some(text) = symbols::symbol_field_text(&instruction) {
+                    debug!("SYMBOL field inserts {:?}", text);
+                    self.form_fields.set_symbol(text);
+                }
+            }
+            "NeXTGraphic" => {
+                let properties = self.group_properties(group);
+                let filename = match self.take_destination_text(name, start) {
+                    Some(filename) => filename,
+                    None => return,
+                };
+                let attachment = Attachment::new(&filename, &properties);
+                debug!("Found attachment {}", attachment.filename);
+                self.after_attachment = true;
+                if let Some(style) = self.options.image_placeholders {
+                    self.write_to_content_destination(&attachment.placeholder(style));
+                }
+                self.attachments.push(attachment);
+            }
+            "shp" => {
+                if let Some(frame) = self.shapes.close(FrameKind::Shape) {
+                    for index in &frame.pictures {
+                        for (key, value) in &frame.properties {
+                            self.pictures[*index]
+                                .properties
+                                .entry(key.clone())
+                                .or_insert_with(|| value.clone());
+                        }
+                    }
+                    let text = frame.display_text();
+                    debug!(
+                        "Closing shape of type {:?} with {} bytes of text",
+                        frame.properties.get("shapeType"),
+                        text.len()
+                    );
+                    if !self.shapes.in_shape() {
+                        self.write_picture_placeholders(&frame.pictures);
+                    }
+                    // Shapes with neither text nor pictures are decorations
+                    if self.options.include_shape_text {
+                        self.write_shape_text(&text);
+                    }
+                }
+            }
+            "shptxt" | "dptxbxtext" => {
+                let contents = match self.take_destination_text(name, start) {
+                    Some(contents) => contents,
+                    None => return,
+                };
+                if !self.options.include_shape_text || self.is_discarded_alternative(group) {
+                    return;
+                }
+                self.write_shape_text(&contents);
+            }
+            "result" => {
+                // The last rendering of an object, shown in place of it
+                if let Some(contents) = self.take_destination_text(name, start) {
+                    self.write_to_content_destination(&contents);
+                }
+            }
+            _ if ObjectCollector::handles_destination(name) => {
+                let contents = match self.take_destination_contents(name, start) {
+                    Some(Destination::Bytes(bytes)) => bytes,
+                    Some(Destination::Text(text)) => text.into_bytes(),
+                    None => return,
+                };
+                self.objects.fold(name, &contents);
+            }
+            _ if FormFieldCollector::handles_destination(name) => {
+                let properties = self.group_properties(group);
+                if let Some(contents) = self.take_destination_text(name, start) {
+                    self.form_fields.fold(name, &contents, &properties);
+                }
+            }
+            _ if MathCollector::handles_destination(name) => {
+                self.take_math_text();
+                if let Some(equation) = self.math.close(name) {
+                    let text = math::render(&equation, self.options.math);
+                    if !text.is_empty() {
+                        self.write_to_content_destination(&text);
+                    }
+                }
+            }
+            _ if ColorCollector::handles_destination(name) => self.colors.fold(name),
+            _ if ListCollector::handles_destination(name) => {
+                let properties = self.group_properties(group);
+                if let Some(contents) = self.take_destination_text(name, start) {
+                    self.lists.fold(name, &contents, &properties);
+                }
+            }
+            "listtext" => {
+                // The marker as the writer rendered it, which is replaced by
+                // one built from the list's Cocoa \levelmarker if it has one
+                self.take_destination_contents(name, start);
+                let ls = match group.get_value("ls") {
+                    Some(ls) => ls,
+                    None => return,
+                };
+                let ilvl = group.get_value("ilvl").unwrap_or(0);
+                if let Some(marker) = self.lists.next_marker(ls, ilvl) {
+                    let indent = "    ".repeat(ilvl.max(0) as usize);
+                    self.write_to_content_destination(&format!("{}{} ", indent, marker));
+                }
+            }
+            _ if SectionCollector::handles_destination(name) => {
+                let properties = self.group_properties(group);
+                let in_page_style = self
+                    .group_stack
+                    .iter()
+                    .any(|group| group.cur_destination.as_deref() == Some("pgdsc"));
+                if let Some(contents) = self.take_destination_text(name, start) {
+                    self.sections
+                        .fold(name, &contents, &properties, in_page_style);
+                }
+            }
+            "sn" | "sv" | "sp" => {
+                if let Some(contents) = self.take_destination_text(name, start) {
+                    self.shapes.fold(name, &contents);
+                }
+            }
+            "revtbl" => {
+                if let Some(contents) = self.take_destination_text(name, start) {
+                    (*self.revisions).borrow_mut().set_authors(&contents);
+                }
+            }
+            _ if AnnotationCollector::handles_destination(name) => {
+                let contents = match self.take_destination_text(name, start) {
+                    Some(contents) => contents,
+                    None => return,
+                };
+                let position = self.text_position();
+                if let Some(text) =
+                    self.annotations
+                        .fold(name, &contents, position, self.options.annotations)
+                {
+                    if let Some(group) = self.get_last_group_mut() {
+                        group.write_text(&text);
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    /// Whether a picture or shape text that has just closed is a copy of one
+    /// that's being kept in another form, based on the picture alternative option
+    fn is_discarded_alternative(&self, group: &GroupState) -> bool {
+        let within = |destination: &str| {
+            self.group_stack
+                .iter()
+                .any(|group| group.cur_destination.as_deref() == Some(destination))
+        };
+        match self.options.picture_alternative {
+            PictureAlternative::ShpPict => group.get_toggle("nonshppict") || within("shprslt"),
+            PictureAlternative::NonShpPict => within("shppict") || within("shpinst"),
+        }
+    }
+
+    /// The control words set by a group that has just closed, excluding those
+    /// it inherited from its parent
+    fn group_properties(&self, group: &GroupState) -> HashMap<String, Option<i32>> {
+        match self.get_last_group() {
+            Some(parent) => group.values_set_since(parent),
+            None => group.values.clone(),
+        }
+    }
+
+    /// Handle a table entry group (such as a single style in the stylesheet)
+    /// that has just closed
+    ///
+    /// Each entry removes its text from the table destination as it's folded,
+    /// so everything past the table's starting point belongs to this entry.
+    fn fold_table_entry(&mut self, name: &str, entry: &GroupState) {
+        let parent = match self.get_last_group() {
+            Some(parent) => parent,
+            None => return,
+        };
+        let start = match parent.dest_start {
+            Some(start) => start,
+            None => return,
+        };
+        let properties = entry.values_set_since(parent);
+        let contents = match self.take_destination_text(name, start) {
+            Some(contents) => contents,
+            None => return,
+        };
+        (*self.stylesheet)
+            .borrow_mut()
+            .add_entry(&contents, properties);
+    }
+
+    fn take_destination_text(&mut self, name: &str, start: usize) -> Option<String> {
+        match self.take_destination_contents(name, start)? {
+            Destination::Text(text) => Some(text),
+            Destination::Bytes(bytes) => Some(String::from_utf8_lossy(&bytes).into_owned()),
+        }
+    }
+
+    fn take_destination_contents(&mut self, name: &str, start: usize) -> Option<Destination> {
+        let closer = (*self.revisions).borrow_mut().close(name);
+        let mut destinations = (*self.destinations).borrow_mut();
+        let dest = destinations.get_mut(name)?;
+        if let Some(closer) = closer {
+            dest.append_text(&closer);
+        }
+        Some(dest.split_off(start))
+    }
+
+    /// The current length of the document body text
+    fn text_position(&self) -> usize {
+        (*self.destinations)
+            .borrow()
+            .get("rtf")
+            .map(Destination::len)
+            .unwrap_or(0)
+    }
+
+    fn finish(self) -> Document {
+        if let Some(closer) = (*self.revisions).borrow_mut().close("rtf") {
+            if let Some(dest) = (*self.destinations).borrow_mut().get_mut("rtf") {
+                dest.append_text(&closer);
+            }
+        }
+        let text = match (*self.destinations).borrow_mut().remove("rtf") {
+            Some(Destination::Text(text)) => text,
+            Some(Destination::Bytes(bytes)) => String::from_utf8_lossy(&bytes).into_owned(),
+            None => String::new(),
+        };
+        let annotations = self.annotations.finish(&text);
+        let (page_styles, sections) = self.sections.finish(text.len());
+        let Layout {
+            mut paragraphs,
+            runs,
+        } = self.layout.take();
+        let last_end = paragraphs.last().map(|last| last.end).unwrap_or(0);
+        if last_end < text.len() {
+            paragraphs.push(Paragraph {
+                start: last_end,
+                end: text.len(),
+                style: None,
+                outline_level: None,
+                left_indent: 0,
+                first_line_indent: 0,
+                alignment: Alignment::default(),
+            });
+        }
+        Document {
+            text,
+            annotations,
+            paragraphs,
+            runs,
+            stylesheet: self.stylesheet.take(),
+            pictures: self.pictures,
+            attachments: self.attachments,
+            colors: self.colors.finish(),
+            page_styles,
+            sections,
+            objects: self.objects.finish(),
+            form_fields: self.form_fields.finish(),
+        }
+    }
+
     fn get_last_group_mut(&mut self) -> Option<&mut GroupState> {
         self.group_stack.last_mut()
     }
@@ -263,7 +1339,73 @@ impl DocumentState {
         self.group_stack.last()
     }
 
+    /// Whether `token` is the character Cocoa writes in place of an
+    /// attachment right after it, which the attachment's placeholder replaces
+    ///
+    /// That's `\'ac` (`¬`), or the object replacement character, or a
+    /// footnote mark in some older documents.
+    fn is_attachment_character(&mut self, token: &Token) -> bool {
+        if !self.after_attachment {
+            return false;
+        }
+        let (name, arg) = match token {
+            // The character may come after a change of the fallback count
+            Token::ControlWord { name, .. } if name == "uc" => return false,
+            Token::ControlWord { name, arg } => (name.as_str(), *arg),
+            _ => ("", None),
+        };
+        self.after_attachment = false;
+        match (name, arg) {
+            ("'", Some(0xac)) | ("chftn", _) => true,
+            ("u", Some(0xfffc) | Some(-4)) => {
+                if let Some(group) = self.get_last_group_mut() {
+                    group.skip_unicode_fallback();
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Hold back a `\'xx` escape in a multi-byte codepage, where it may be
+    /// half of a character, returning whether it was held
+    fn hold_escaped_byte(&mut self, token: &Token) -> bool {
+        let byte = match token {
+            Token::ControlWord {
+                name,
+                arg: Some(arg),
+            } if name == "'" => (*arg & 0xFF) as u8,
+            _ => return false,
+        };
+        let multi_byte = self
+            .get_last_group()
+            .is_some_and(GroupState::decodes_escapes_together);
+        if multi_byte {
+            self.escaped_bytes.push(byte);
+        }
+        multi_byte
+    }
+
+    fn flush_escaped_bytes(&mut self) {
+        if !self.escaped_bytes.is_empty() {
+            let bytes = std::mem::take(&mut self.escaped_bytes);
+            self.write_to_current_destination(&bytes);
+        }
+    }
+
     fn process_token(&mut self, token: &Token) {
+        if self.is_attachment_character(token) {
+            trace!("Leaving out the character standing in for an attachment");
+            return;
+        }
+        if self.hold_escaped_byte(token) {
+            return;
+        }
+        // Text may hold the second byte of an escaped character, the rest must
+        // come after the escaped characters
+        if !matches!(token, Token::Text(_)) {
+            self.flush_escaped_bytes();
+        }
         let word_is_optional = self
             .get_last_group_mut()
             .map(|group| group.get_and_clear_ignore_next_control())
@@ -274,7 +1416,20 @@ impl DocumentState {
             Token::ControlSymbol(c) => self.do_control_symbol(*c, word_is_optional),
             Token::ControlWord { name, arg } => self.do_control_word(name, *arg, word_is_optional),
             Token::ControlBin(data) => self.do_control_bin(data, word_is_optional),
-            Token::Text(bytes) => self.write_to_current_destination(bytes),
+            Token::Text(bytes) => {
+                if self.in_destination(ColorCollector::handles_destination) {
+                    self.colors.text(bytes);
+                } else if self.in_destination(FontTable::handles_destination) {
+                    (*self.fonts).borrow_mut().text(bytes);
+                }
+                if self.escaped_bytes.is_empty() {
+                    self.write_to_current_destination(bytes)
+                } else {
+                    let mut text = std::mem::take(&mut self.escaped_bytes);
+                    text.extend_from_slice(bytes);
+                    self.write_to_current_destination(&text)
+                }
+            }
             Token::StartGroup => self.start_group(),
             Token::EndGroup => self.end_group(),
             _ => (),
@@ -288,13 +1443,18 @@ pub fn tokenize<R: Read>(mut reader: R) -> Result<Vec<Token>> {
     reader
         .read_to_end(&mut data)
         .context("Error reading from input file")?;
+    if compressed::is_compressed_rtf(&data) {
+        debug!("Decompressing compressed RTF.");
+        data = compressed::decompress(&data).context("Error decompressing compressed RTF")?;
+    }
 
     debug!("Parsing into token stream.");
     parse_tokens(&data).map_err(|e| anyhow::anyhow!("Error parsing RTF tokens: {}", e))
 }
 
-pub fn write_plaintext<W: Write>(token_stream: &[Token], mut writer: W) -> Result<()> {
-    let mut state = DocumentState::new();
+pub fn parse_document(token_stream: &[Token], options: &ConvertOptions) -> Document {
+    let mut state = DocumentState::new(options);
+    state.fallback_encoding = encodings::fallback_encoding(&options.fallback_encodings, &[]);
 
     debug!("Iterating over token stream.");
     for token in token_stream.iter().filter(|c| c != &&Token::Newline) {
@@ -302,11 +1462,320 @@ pub fn write_plaintext<W: Write>(token_stream: &[Token], mut writer: W) -> Resul
     }
     debug!("Finished token stream iteration.");
 
-    if let Some(dest) = (*state.destinations).borrow().get("rtf") {
-        debug!("Writing rtf1 content...");
-        writer
-            .write(dest.as_bytes())
-            .context("Error writing to output file")?;
-    }
+    state.finish()
+}
+
+pub fn write_plaintext<W: Write>(document: &Document, mut writer: W) -> Result<()> {
+    debug!("Writing rtf1 content...");
+    writer
+        .write(document.text.as_bytes())
+        .context("Error writing to output file")?;
     Ok(())
 }
+
+/// Write the document's paragraphs, with their style names and heading levels, as a JSON list
+pub fn write_paragraphs_json<W: Write>(document: &Document, writer: W) -> Result<()> {
+    let list: Vec<serde_json::Value> = document
+        .paragraphs
+        .iter()
+        .map(|paragraph| {
+            serde_json::json!({
+                "start": paragraph.start,
+                "end": paragraph.end,
+                "style": paragraph.style.unwrap_or(0),
+                "style_name": document.paragraph_style_name(paragraph),
+                "heading_level": document.heading_level(paragraph).map(|level| level + 1),
+                "alignment": paragraph.alignment.name(),
+                "left_indent": paragraph.left_indent,
+                "first_line_indent": paragraph.first_line_indent,
+                "text": document.paragraph_text(paragraph).trim_end(),
+            })
+        })
+        .collect();
+    serde_json::to_writer_pretty(writer, &list).context("Error writing paragraph list")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(rtf: &str) -> Document {
+        parse_with(rtf.as_bytes(), &ConvertOptions::default())
+    }
+
+    fn parse_with(rtf: &[u8], options: &ConvertOptions) -> Document {
+        let tokens = tokenize(rtf).unwrap();
+        parse_document(&tokens, options)
+    }
+
+    #[test]
+    fn reads_color_tables() {
+        let document = parse(
+            r"{\rtf1\ansi{\colortbl;\red255\green0\blue0;\red0\green0\blue255;}{\*\expandedcolortbl;;\csgray\c50000;}Text\par}",
+        );
+        let color = |red, green, blue| Some(Color { red, green, blue });
+        assert_eq!(
+            document.colors,
+            vec![None, color(255, 0, 0), color(128, 128, 128)]
+        );
+        assert_eq!(document.text, "Text\n");
+    }
+
+    #[test]
+    fn symbol_font_leaves_control_characters_alone() {
+        let text = parse(
+            r"{\rtf1\ansi{\fonttbl{\f0 Arial;}{\f1\fcharset2 Wingdings;}}{\f1 \'fc\tab\bullet \'fc\par}Next line\par}",
+        )
+        .text;
+        assert_eq!(text, "\u{2714}\t\u{2022}\u{2714}\nNext line\n");
+    }
+
+    #[test]
+    fn falls_back_to_the_header_language_codepage() {
+        let options = ConvertOptions {
+            fallback_encodings: vec![
+                Fallback::Language,
+                Fallback::Encoding(encoding_rs::WINDOWS_1252),
+            ],
+            ..ConvertOptions::default()
+        };
+        let russian = parse_with(br"{\rtf1\deflang1049 \'cf\'f0\'e8\par}", &options);
+        assert_eq!(russian.text, "\u{41f}\u{440}\u{438}\n");
+        // A declared codepage wins over the language
+        let declared = parse_with(br"{\rtf1\ansi\ansicpg1252\deflang1049 \'cf\par}", &options);
+        assert_eq!(declared.text, "\u{cf}\n");
+        // Languages of nested objects or later in the body don't count
+        let nested = parse_with(
+            br"{\rtf1 \'cf{\object{\*\objdata\deflang1049}}\deflang1049 \'cf\par}",
+            &options,
+        );
+        assert_eq!(nested.text, "\u{cf}\u{cf}\n");
+    }
+
+    #[test]
+    fn reads_symbol_fonts_from_the_font_table() {
+        // Alternative names don't make a font a symbol font, and old writers
+        // leave the entries out of groups
+        let text = parse(
+            r"{\rtf1\ansi{\fonttbl{\f0 Arial{\*\falt Symbol};}\f1 Symbol;\f2\fcharset2 Marlett;}\f0 a\f1 a\f2 a\par}",
+        )
+        .text;
+        assert_eq!(text, "a\u{3b1}\u{f061}\n");
+    }
+
+    #[test]
+    fn skips_unicode_fallback_characters() {
+        assert_eq!(parse(r"{\rtf1\ansi a\u8212?b\par}").text, "a\u{2014}b\n");
+        assert_eq!(
+            parse(r"{\rtf1\ansi\uc0 a\u8212 b\par}").text,
+            "a\u{2014}b\n"
+        );
+        assert_eq!(
+            parse(r"{\rtf1\ansi\uc2 a\u8212--b\u8211\'81\'5cc\par}").text,
+            "a\u{2014}b\u{2013}c\n"
+        );
+        // The skip count is scoped to the group
+        assert_eq!(
+            parse(r"{\rtf1\ansi{\uc2 \u8212--}\u8211-c\par}").text,
+            "\u{2014}\u{2013}c\n"
+        );
+        // A fallback that ends at the group doesn't eat the text after it
+        assert_eq!(parse(r"{\rtf1\ansi{\u8212}b\par}").text, "\u{2014}b\n");
+    }
+
+    #[test]
+    fn reads_negative_and_surrogate_unicode_values() {
+        // Values above 32767 are written as negative 16-bit numbers
+        assert_eq!(parse(r"{\rtf1\ansi\u-3913?\par}").text, "\u{f0b7}\n");
+        assert_eq!(
+            parse(r"{\rtf1\ansi\u-10179?\u-8694?\par}").text,
+            "\u{1f60a}\n"
+        );
+        assert_eq!(
+            parse(r"{\rtf1\ansi\u55357?\u56842?\par}").text,
+            "\u{1f60a}\n"
+        );
+        // A surrogate without its other half can't be written, so it's dropped
+        assert_eq!(parse(r"{\rtf1\ansi\u-10179?x\u-8694?\par}").text, "x\n");
+        assert_eq!(
+            parse(r"{\rtf1\ansi\u-10179?\u8212?\par}").text,
+            "\u{2014}\n"
+        );
+    }
+
+    #[test]
+    fn leaves_out_the_character_standing_in_for_an_attachment() {
+        let text = |character: &str| {
+            let document = parse(&format!(
+                r"{{\rtf1\ansi Before{{{{\NeXTGraphic a.png \width20 \height20}}{}}}After\par}}",
+                character
+            ));
+            assert_eq!(document.attachments.len(), 1);
+            document.text
+        };
+        assert_eq!(text(r"\'ac"), "BeforeAfter\n");
+        assert_eq!(text(r"\uc0\u65532 "), "BeforeAfter\n");
+        assert_eq!(text(r"\u-4?"), "BeforeAfter\n");
+        assert_eq!(text(r"\chftn "), "BeforeAfter\n");
+        // Without one, nothing else is lost
+        assert_eq!(text("x"), "BeforexAfter\n");
+        assert_eq!(text(r"\'e9"), "Before\u{e9}After\n");
+    }
+
+    #[test]
+    fn writes_field_results_in_place() {
+        let text = parse(
+            r#"{\rtf1\ansi See {\field{\*\fldinst{HYPERLINK "https://example.com/"}}{\fldrslt{\ul example}}} on page {\field{\*\fldinst PAGE \\* MERGEFORMAT }{\fldrslt 3}}.\par}"#,
+        )
+        .text;
+        assert_eq!(text, "See example on page 3.\n");
+    }
+
+    #[test]
+    fn detects_raw_utf8_text() {
+        let options = ConvertOptions::default();
+        let text = |rtf: &[u8], options: &ConvertOptions| parse_with(rtf, options).text;
+        assert_eq!(
+            text(b"{\\rtf1\\ansi caf\xc3\xa9\\par}", &options),
+            "caf\u{e9}\n"
+        );
+        // Bytes that aren't valid UTF-8, and escaped bytes, keep the codepage
+        assert_eq!(
+            text(b"{\\rtf1\\ansi caf\xe9 \xc3\\par}", &options),
+            "caf\u{e9} \u{c3}\n"
+        );
+        assert_eq!(
+            text(b"{\\rtf1\\ansi caf\\'c3\\'a9\\par}", &options),
+            "caf\u{c3}\u{a9}\n"
+        );
+        // Multi-byte codepages are only taken as UTF-8 when asked to
+        let shift_jis = b"{\\rtf1\\ansi\\ansicpg932 \xe6\x97\xa5\\par}";
+        assert_ne!(text(shift_jis, &options), "\u{65e5}\n");
+        let assume_utf8 = ConvertOptions {
+            assume_utf8: true,
+            ..ConvertOptions::default()
+        };
+        assert_eq!(text(shift_jis, &assume_utf8), "\u{65e5}\n");
+    }
+
+    #[test]
+    fn decodes_double_byte_escapes_together() {
+        let options = ConvertOptions {
+            fallback_encodings: vec![
+                Fallback::Language,
+                Fallback::Encoding(encoding_rs::WINDOWS_1252),
+            ],
+            ..ConvertOptions::default()
+        };
+        let text = |rtf: &[u8]| parse_with(rtf, &options).text;
+        assert_eq!(
+            text(b"{\\rtf1\\ansi\\deflang1041 \\'93\\'fa\\par}"),
+            "\u{65e5}\n"
+        );
+        // The second byte of a character may be written as it is
+        assert_eq!(
+            text(b"{\\rtf1\\ansi\\ansicpg932 \\'83e\\'83X\\'83g\\par}"),
+            "\u{30c6}\u{30b9}\u{30c8}\n"
+        );
+        // Fallback bytes after a \u character are still skipped one by one
+        assert_eq!(
+            text(b"{\\rtf1\\ansi\\ansicpg932\\uc2 \\u26085\\'93\\'fa\\'96\\'7b\\par}"),
+            "\u{65e5}\u{672c}\n"
+        );
+    }
+
+    #[test]
+    fn resolves_formatting_through_styles() {
+        let document = parse(concat!(
+            r"{\rtf1\ansi{\*\defchp\fs20}{\*\defpap\qj}",
+            r"{\stylesheet{\f1\fs22\li720 Normal;}",
+            r"{\s1\sbasedon0\qc\b\fs32 heading 1;}",
+            r"{\s2\sbasedon1\ql\fi-360 Quote;}",
+            r"{\*\cs10\additive\i\f2 Emphasis;}",
+            r"{\s3\sbasedon4\qr Loop A;}{\s4\sbasedon3\li100 Loop B;}}",
+            r"\pard\s1 Title\par",
+            r"\pard\s2 Quoted {\cs10 stressed} {\b0\fs24 plain}\par",
+            r"\pard\s3 Cycle\par",
+            r"\pard\li0\qr Direct\par}"
+        ));
+        let layout: Vec<_> = document
+            .paragraphs
+            .iter()
+            .map(|p| (p.alignment, p.left_indent, p.first_line_indent))
+            .collect();
+        assert_eq!(
+            layout,
+            [
+                (Alignment::Center, 720, 0),
+                (Alignment::Left, 720, -360),
+                (Alignment::Right, 100, 0),
+                (Alignment::Right, 0, 0),
+            ]
+        );
+
+        let format_of = |text: &str| {
+            let start = document.text.find(text).unwrap();
+            document
+                .runs
+                .iter()
+                .find(|run| run.start <= start && start < run.end)
+                .unwrap()
+                .format
+        };
+        let title = format_of("Title");
+        assert!(title.bold && !title.italic);
+        assert_eq!((title.font, title.size), (Some(1), Some(32)));
+        let stressed = format_of("stressed");
+        assert!(stressed.bold && stressed.italic);
+        assert_eq!((stressed.font, stressed.size), (Some(2), Some(32)));
+        let plain = format_of("plain");
+        assert!(!plain.bold);
+        assert_eq!((plain.font, plain.size), (Some(1), Some(24)));
+        // The loop's styles inherit from each other, but not from Normal
+        let cycle = format_of("Cycle");
+        assert_eq!((cycle.font, cycle.size), (None, Some(20)));
+    }
+
+    #[test]
+    fn applies_revision_marks() {
+        let rtf = concat!(
+            r"{\rtf1\ansi{\*\revtbl {Unknown;}{Alice;}{Bob;}}",
+            r"Keep {\deleted\revauthdel1 this}{\revised\revauth2 that} {\revised\revauth7 too}\par}"
+        )
+        .as_bytes();
+        let text = |revisions| {
+            let options = ConvertOptions {
+                revisions,
+                ..ConvertOptions::default()
+            };
+            parse_with(rtf, &options).text
+        };
+        assert_eq!(text(RevisionMode::Accept), "Keep that too\n");
+        assert_eq!(text(RevisionMode::Reject), "Keep this \n");
+        assert_eq!(
+            text(RevisionMode::Diff),
+            "Keep [-Alice: this-][+Bob: that+] [+Unknown: too+]\n"
+        );
+    }
+
+    #[test]
+    fn suppresses_hidden_text() {
+        let rtf = concat!(
+            r"{\rtf1\ansi Shown {\v hidden }after, {\webhidden web }{\spv special }",
+            r"{\v off \v0 on} end\par}"
+        )
+        .as_bytes();
+        assert_eq!(
+            parse_with(rtf, &ConvertOptions::default()).text,
+            "Shown after, on end\n"
+        );
+        let options = ConvertOptions {
+            include_hidden: true,
+            ..ConvertOptions::default()
+        };
+        assert_eq!(
+            parse_with(rtf, &options).text,
+            "Shown hidden after, web special off on end\n"
+        );
+    }
+}
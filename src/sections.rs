@@ -0,0 +1,232 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+
+use anyhow::{Context, Result};
+use log::debug;
+
+/// An OpenOffice page style, from a `\pgdsc` entry of the `\pgdsctbl`
+#[derive(Clone, Debug, Default)]
+pub struct PageStyle {
+    pub number: i32,
+    pub name: String,
+    /// The page style of the pages after the first (`\pgdscnxt`)
+    pub next: Option<i32>,
+    /// Header and footer text, by destination name, such as `headerf` for the first page
+    pub headers: BTreeMap<String, String>,
+}
+
+/// A section of the document body, as a byte range of `Document::text`
+#[derive(Clone, Debug, Default)]
+pub struct Section {
+    pub start: usize,
+    pub end: usize,
+    /// The number of the page style the section uses (`\pgdscno`)
+    pub page_style: Option<i32>,
+    /// The section's own header and footer text, by destination name
+    pub headers: BTreeMap<String, String>,
+}
+
+/// Gathers page styles and section headers and footers as their
+/// destinations are closed, and splits the body into sections at each `\sect`
+#[derive(Clone, Default)]
+pub struct SectionCollector {
+    page_styles: Vec<PageStyle>,
+    pending_headers: BTreeMap<String, String>,
+    sections: Vec<Section>,
+    current: Section,
+}
+
+impl SectionCollector {
+    pub fn handles_destination(name: &str) -> bool {
+        is_header_or_footer(name) || name == "pgdsc"
+    }
+
+    /// Handle a closed header, footer or page style, where `in_page_style`
+    /// says whether a header or footer belongs to a page style being defined
+    /// rather than to the current section
+    pub fn fold(
+        &mut self,
+        name: &str,
+        contents: &str,
+        properties: &HashMap<String, Option<i32>>,
+        in_page_style: bool,
+    ) {
+        let value = |name: &str| properties.get(name).copied().flatten();
+        if name == "pgdsc" {
+            let style = PageStyle {
+                number: value("pgdsc").unwrap_or(self.page_styles.len() as i32),
+                name: contents.trim().trim_end_matches(';').trim().to_owned(),
+                next: value("pgdscnxt"),
+                headers: std::mem::take(&mut self.pending_headers),
+            };
+            debug!("Page style {} is {:?}", style.number, style.name);
+            self.page_styles.push(style);
+            return;
+        }
+        let text = contents.trim().to_owned();
+        if in_page_style {
+            self.pending_headers.insert(name.to_owned(), text);
+        } else {
+            self.current.headers.insert(name.to_owned(), text);
+        }
+    }
+
+    /// Note the page style (`\pgdscno`) of the current section
+    pub fn use_page_style(&mut self, number: Option<i32>) {
+        self.current.page_style = number;
+    }
+
+    /// End the current section at `position` in the body text; the next one
+    /// keeps its page style unless it sets its own
+    pub fn end_section(&mut self, position: usize) {
+        let next = Section {
+            start: position,
+            page_style: self.current.page_style,
+            ..Section::default()
+        };
+        let mut section = std::mem::replace(&mut self.current, next);
+        section.end = position;
+        self.sections.push(section);
+    }
+
+    pub fn finish(mut self, end: usize) -> (Vec<PageStyle>, Vec<Section>) {
+        if self.current.start < end || self.sections.is_empty() {
+            self.end_section(end);
+        }
+        (self.page_styles, self.sections)
+    }
+}
+
+fn is_header_or_footer(name: &str) -> bool {
+    matches!(
+        name,
+        "header" | "headerl" | "headerr" | "headerf" | "footer" | "footerl" | "footerr" | "footerf"
+    )
+}
+
+/// Write each section's range, page style, headers and footers as a JSON
+/// array, taking each header and footer from the page style unless the
+/// section has its own
+pub fn write_json<W: Write>(
+    sections: &[Section],
+    page_styles: &[PageStyle],
+    writer: W,
+) -> Result<()> {
+    let find_style = |number: Option<i32>| {
+        number.and_then(|number| page_styles.iter().find(|style| style.number == number))
+    };
+    let list: Vec<serde_json::Value> = sections
+        .iter()
+        .map(|section| {
+            let style = find_style(section.page_style);
+            let mut headers = style.map(|style| style.headers.clone()).unwrap_or_default();
+            headers.extend(section.headers.clone());
+            serde_json::json!({
+                "start": section.start,
+                "end": section.end,
+                "page_style": style.map(|style| &style.name),
+                "next_page_style": find_style(style.and_then(|style| style.next))
+                    .map(|style| &style.name),
+                "headers": headers,
+            })
+        })
+        .collect();
+    serde_json::to_writer_pretty(writer, &list).context("Error writing section list")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rtftotext::{parse_document, tokenize, ConvertOptions, Document};
+
+    fn parse(rtf: &str) -> Document {
+        let tokens = tokenize(rtf.as_bytes()).unwrap();
+        parse_document(&tokens, &ConvertOptions::default())
+    }
+
+    const LIBREOFFICE: &str = concat!(
+        r"{\rtf1\ansi{\*\pgdsctbl",
+        r"{\pgdsc0\pgdscuse195\pgdscnxt0 Default Style;}",
+        r"{\pgdsc1\pgdscuse195\pgdscnxt0{\header \pard First header\par}",
+        r"{\footer \pard Page footer\par} Title Page;}}",
+        r"\pgdscno1 Cover\sect \pgdscno0 Body{\footer Own footer\par} text\sect More\par}"
+    );
+
+    #[test]
+    fn reads_the_page_style_table() {
+        let document = parse(LIBREOFFICE);
+        let styles: Vec<_> = document
+            .page_styles
+            .iter()
+            .map(|style| (style.number, style.name.as_str(), style.next))
+            .collect();
+        assert_eq!(
+            styles,
+            [(0, "Default Style", Some(0)), (1, "Title Page", Some(0))]
+        );
+        assert!(document.page_styles[0].headers.is_empty());
+        let headers: Vec<_> = document.page_styles[1]
+            .headers
+            .iter()
+            .map(|(name, text)| (name.as_str(), text.as_str()))
+            .collect();
+        assert_eq!(
+            headers,
+            [("footer", "Page footer"), ("header", "First header")]
+        );
+        // Page style headers and footers stay out of the body text
+        assert_eq!(document.text, "Cover\n\nBody text\n\nMore\n");
+    }
+
+    #[test]
+    fn writes_sections_with_their_page_styles() {
+        let document = parse(LIBREOFFICE);
+        let mut output = Vec::new();
+        write_json(&document.sections, &document.page_styles, &mut output).unwrap();
+        let list: serde_json::Value = serde_json::from_slice(&output).unwrap();
+        assert_eq!(
+            list,
+            serde_json::json!([
+                {
+                    "start": 0,
+                    "end": 7,
+                    "page_style": "Title Page",
+                    "next_page_style": "Default Style",
+                    "headers": {"header": "First header", "footer": "Page footer"},
+                },
+                {
+                    "start": 7,
+                    "end": 18,
+                    "page_style": "Default Style",
+                    "next_page_style": "Default Style",
+                    "headers": {"footer": "Own footer"},
+                },
+                {
+                    "start": 18,
+                    "end": 23,
+                    "page_style": "Default Style",
+                    "next_page_style": "Default Style",
+                    "headers": {},
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn reports_one_section_without_section_breaks() {
+        let document = parse(r"{\rtf1\ansi {\headerf Cover header\par}Text\par}");
+        let mut output = Vec::new();
+        write_json(&document.sections, &document.page_styles, &mut output).unwrap();
+        let list: serde_json::Value = serde_json::from_slice(&output).unwrap();
+        assert_eq!(
+            list,
+            serde_json::json!([{
+                "start": 0,
+                "end": 5,
+                "page_style": null,
+                "next_page_style": null,
+                "headers": {"headerf": "Cover header"},
+            }])
+        );
+    }
+}
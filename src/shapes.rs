@@ -0,0 +1,165 @@
+use std::collections::HashMap;
+
+use log::{debug, warn};
+
+/// The property bag of a shape or picture, from its `{\sp{\sn NAME}{\sv VALUE}}` groups
+pub type ShapeProperties = HashMap<String, String>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FrameKind {
+    Shape,
+    Picture,
+}
+
+/// A shape or picture whose destination is still open
+#[derive(Clone, Debug)]
+pub struct ShapeFrame {
+    pub kind: FrameKind,
+    /// The indexes of the pictures inside this shape, including those of the
+    /// shapes grouped inside it
+    pub pictures: Vec<usize>,
+    pub properties: ShapeProperties,
+    /// Text from the shape's `\shptxt`, and from any shapes grouped inside it
+    pub text: String,
+}
+
+impl ShapeFrame {
+    /// The text displayed by the shape: its text box contents, or the text of
+    /// a WordArt shape, which is kept in its properties instead
+    pub fn display_text(&self) -> String {
+        let mut text = self.text.clone();
+        if let Some(wordart) = self.properties.get("gtextUNICODE") {
+            if !text.is_empty() && !text.ends_with('\n') {
+                text.push('\n');
+            }
+            text.push_str(wordart);
+        }
+        if !text.is_empty() && !text.ends_with('\n') {
+            text.push('\n');
+        }
+        text
+    }
+}
+
+/// Collects `\sp` properties into the innermost open shape or picture
+#[derive(Clone, Default)]
+pub struct ShapeCollector {
+    frames: Vec<ShapeFrame>,
+    name: Option<String>,
+    value: Option<String>,
+}
+
+impl ShapeCollector {
+    pub fn open(&mut self, kind: FrameKind) {
+        self.frames.push(ShapeFrame {
+            kind,
+            pictures: Vec::new(),
+            properties: ShapeProperties::new(),
+            text: String::new(),
+        });
+    }
+
+    pub fn close(&mut self, kind: FrameKind) -> Option<ShapeFrame> {
+        match self.frames.last() {
+            Some(frame) if frame.kind == kind => {
+                let frame = self.frames.pop()?;
+                // A group of shapes holds the pictures of each shape in it
+                if let Some(parent) = self.innermost_shape() {
+                    parent.pictures.extend(&frame.pictures);
+                }
+                Some(frame)
+            }
+            _ => {
+                warn!("Closing a {:?} that was never opened", kind);
+                None
+            }
+        }
+    }
+
+    /// Whether a shape encloses the current position, in which case its
+    /// pictures are reported when the shape closes
+    pub fn in_shape(&self) -> bool {
+        self.frames
+            .iter()
+            .any(|frame| frame.kind == FrameKind::Shape)
+    }
+
+    fn innermost_shape(&mut self) -> Option<&mut ShapeFrame> {
+        self.frames
+            .iter_mut()
+            .rev()
+            .find(|frame| frame.kind == FrameKind::Shape)
+    }
+
+    /// Record a picture as belonging to the innermost open shape, returning
+    /// false if there isn't one
+    pub fn add_picture(&mut self, index: usize) -> bool {
+        match self.innermost_shape() {
+            Some(frame) => {
+                frame.pictures.push(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Add text to the innermost open shape, returning false if there isn't one
+    pub fn append_text(&mut self, text: &str) -> bool {
+        match self.innermost_shape() {
+            Some(frame) => {
+                frame.text.push_str(text);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Handle the contents of a completed `\sn`, `\sv` or `\sp` destination
+    pub fn fold(&mut self, name: &str, contents: &str) {
+        match name {
+            "sn" => self.name = Some(contents.trim().to_owned()),
+            "sv" => self.value = Some(contents.trim().to_owned()),
+            "sp" => {
+                let name = self.name.take();
+                let value = self.value.take().unwrap_or_default();
+                match (name, self.frames.last_mut()) {
+                    (Some(name), Some(frame)) => {
+                        debug!("{:?} property {} = {:?}", frame.kind, name, value);
+                        frame.properties.insert(name, value);
+                    }
+                    (Some(name), None) => {
+                        warn!("Shape property {} found outside of any shape", name)
+                    }
+                    (None, _) => warn!("Shape property with no name"),
+                }
+            }
+            _ => panic!(
+                "Programmer error: {} was routed to the shape collector without a handler",
+                name
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn collects_shape_properties_and_text() {
+        let mut shapes = ShapeCollector::default();
+        shapes.open(FrameKind::Shape);
+        for (name, value) in [("shapeType", "136"), ("gtextUNICODE", "Word Art")] {
+            shapes.fold("sn", name);
+            shapes.fold("sv", value);
+            shapes.fold("sp", "");
+        }
+        assert!(shapes.append_text("Caption"));
+        let frame = shapes.close(FrameKind::Shape).unwrap();
+        assert_eq!(frame.properties["shapeType"], "136");
+        assert_eq!(frame.display_text(), "Caption\nWord Art\n");
+        // Nothing is left open for more text
+        assert!(!shapes.append_text("Lost"));
+        assert!(shapes.close(FrameKind::Shape).is_none());
+    }
+}
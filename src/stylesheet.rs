@@ -0,0 +1,355 @@
+use std::collections::HashMap;
+
+use log::{debug, warn};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum StyleKind {
+    Paragraph,
+    Character,
+    Section,
+    Table,
+}
+
+/// A single `\stylesheet` entry
+#[derive(Clone, Debug)]
+pub struct Style {
+    pub number: i32,
+    pub name: String,
+    pub based_on: Option<i32>,
+    pub outline_level: Option<i32>,
+    /// Every control word set by the style definition, including its formatting
+    pub properties: HashMap<String, Option<i32>>,
+}
+
+/// The styles defined by a document's `\stylesheet`
+#[derive(Clone, Debug, Default)]
+pub struct Stylesheet {
+    styles: HashMap<(StyleKind, i32), Style>,
+    /// Formatting from the `\defchp` and `\defpap` destinations
+    defaults: HashMap<String, Option<i32>>,
+}
+
+impl Stylesheet {
+    /// Add a style from the name text and the control words of a stylesheet entry group
+    pub fn add_entry(&mut self, name: &str, properties: HashMap<String, Option<i32>>) {
+        let (kind, number) = if let Some(number) = properties.get("cs") {
+            (StyleKind::Character, number.unwrap_or(0))
+        } else if let Some(number) = properties.get("ds") {
+            (StyleKind::Section, number.unwrap_or(0))
+        } else if let Some(number) = properties.get("ts") {
+            (StyleKind::Table, number.unwrap_or(0))
+        } else {
+            // Entries without a style number are the default paragraph style, \s0
+            (
+                StyleKind::Paragraph,
+                properties.get("s").copied().flatten().unwrap_or(0),
+            )
+        };
+        let name = name.trim().trim_end_matches(';').trim().to_owned();
+        if name.is_empty() {
+            warn!("Ignoring unnamed {:?} style {}", kind, number);
+            return;
+        }
+        debug!("Adding {:?} style {}: '{}'", kind, number, name);
+        let style = Style {
+            number,
+            name,
+            based_on: properties.get("sbasedon").copied().flatten(),
+            outline_level: properties.get("outlinelevel").copied().flatten(),
+            properties,
+        };
+        self.styles.insert((kind, number), style);
+    }
+
+    /// Record the document's default character (`\defchp`) or paragraph
+    /// (`\defpap`) properties
+    pub fn add_defaults(&mut self, name: &str, properties: HashMap<String, Option<i32>>) {
+        debug!("Adding {} default properties: {:?}", name, properties);
+        self.defaults.extend(properties);
+    }
+
+    /// The setting of a control word in a style, inherited through `\sbasedon`
+    pub fn lookup(&self, kind: StyleKind, number: i32, name: &str) -> Option<Option<i32>> {
+        self.lineage(kind, number)
+            .iter()
+            .find_map(|style| style.properties.get(name).copied())
+    }
+
+    /// The setting of a control word in the document defaults
+    pub fn lookup_default(&self, name: &str) -> Option<Option<i32>> {
+        self.defaults.get(name).copied()
+    }
+
+    /// Which of a set of mutually exclusive control words is set in a style,
+    /// inherited through `\sbasedon`
+    pub fn lookup_first<'a>(
+        &self,
+        kind: StyleKind,
+        number: i32,
+        names: &[&'a str],
+    ) -> Option<&'a str> {
+        self.lineage(kind, number)
+            .iter()
+            .find_map(|style| first_set(&style.properties, names))
+    }
+
+    /// Which of a set of mutually exclusive control words is set in the document defaults
+    pub fn lookup_default_first<'a>(&self, names: &[&'a str]) -> Option<&'a str> {
+        first_set(&self.defaults, names)
+    }
+
+    pub fn get(&self, kind: StyleKind, number: i32) -> Option<&Style> {
+        self.styles.get(&(kind, number))
+    }
+
+    /// The chain of styles starting at the given style and following its
+    /// `\sbasedon` ancestors, nearest first
+    pub fn lineage(&self, kind: StyleKind, number: i32) -> Vec<&Style> {
+        let mut chain: Vec<&Style> = Vec::new();
+        let mut next = Some(number);
+        while let Some(number) = next {
+            let style = match self.get(kind, number) {
+                Some(style) => style,
+                None => break,
+            };
+            if chain.iter().any(|seen| seen.number == number) {
+                warn!("Style {} is based on itself", number);
+                break;
+            }
+            chain.push(style);
+            // \sbasedon222 means "based on no style"
+            next = style.based_on.filter(|based_on| *based_on != 222);
+        }
+        chain
+    }
+
+    /// The outline level of a paragraph style, inherited through `\sbasedon`,
+    /// falling back to the level implied by built-in "heading N" style names
+    pub fn outline_level(&self, number: i32) -> Option<i32> {
+        let lineage = self.lineage(StyleKind::Paragraph, number);
+        lineage
+            .iter()
+            .find_map(|style| style.outline_level)
+            .or_else(|| {
+                lineage
+                    .first()
+                    .and_then(|style| heading_name_level(&style.name))
+            })
+            // Level 9 is body text
+            .filter(|level| (0..9).contains(level))
+    }
+}
+
+/// The first of `names` that is set in `properties`
+pub fn first_set<'a>(
+    properties: &HashMap<String, Option<i32>>,
+    names: &[&'a str],
+) -> Option<&'a str> {
+    names
+        .iter()
+        .copied()
+        .find(|name| properties.contains_key(*name))
+}
+
+fn heading_name_level(name: &str) -> Option<i32> {
+    let name = name.to_ascii_lowercase();
+    let level = name.strip_prefix("heading")?.trim().parse::<i32>().ok()?;
+    Some(level - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rtftotext::{
+        parse_document, tokenize, write_paragraphs_json, ConvertOptions, Document,
+    };
+
+    fn parse(rtf: &str) -> Document {
+        let tokens = tokenize(rtf.as_bytes()).unwrap();
+        parse_document(&tokens, &ConvertOptions::default())
+    }
+
+    fn properties(words: &[(&str, Option<i32>)]) -> HashMap<String, Option<i32>> {
+        words
+            .iter()
+            .map(|(name, value)| (name.to_string(), *value))
+            .collect()
+    }
+
+    #[test]
+    fn inherits_through_based_on_chains() {
+        let mut stylesheet = Stylesheet::default();
+        stylesheet.add_entry("Normal;", properties(&[("fs", Some(22)), ("qj", None)]));
+        stylesheet.add_entry(
+            "Heading;",
+            properties(&[
+                ("s", Some(1)),
+                ("sbasedon", Some(0)),
+                ("b", None),
+                ("qc", None),
+            ]),
+        );
+        stylesheet.add_entry(
+            "Subheading;",
+            properties(&[("s", Some(2)), ("sbasedon", Some(1)), ("fs", Some(28))]),
+        );
+        stylesheet.add_defaults("defchp", properties(&[("f", Some(3))]));
+
+        let alignments = ["ql", "qc", "qr", "qj"];
+        assert_eq!(
+            stylesheet.lookup(StyleKind::Paragraph, 2, "fs"),
+            Some(Some(28))
+        );
+        assert_eq!(stylesheet.lookup(StyleKind::Paragraph, 2, "b"), Some(None));
+        assert_eq!(
+            stylesheet.lookup(StyleKind::Paragraph, 1, "fs"),
+            Some(Some(22))
+        );
+        assert_eq!(stylesheet.lookup(StyleKind::Paragraph, 2, "f"), None);
+        assert_eq!(stylesheet.lookup_default("f"), Some(Some(3)));
+        assert_eq!(
+            stylesheet.lookup_first(StyleKind::Paragraph, 2, &alignments),
+            Some("qc")
+        );
+        assert_eq!(
+            stylesheet.lookup_first(StyleKind::Paragraph, 0, &alignments),
+            Some("qj")
+        );
+        assert_eq!(
+            stylesheet.lookup_first(StyleKind::Character, 2, &alignments),
+            None
+        );
+    }
+
+    #[test]
+    fn stops_at_based_on_cycles() {
+        let mut stylesheet = Stylesheet::default();
+        stylesheet.add_entry(
+            "First;",
+            properties(&[("s", Some(1)), ("sbasedon", Some(2)), ("b", None)]),
+        );
+        stylesheet.add_entry(
+            "Second;",
+            properties(&[("s", Some(2)), ("sbasedon", Some(1)), ("i", None)]),
+        );
+        stylesheet.add_entry(
+            "Alone;",
+            properties(&[("s", Some(3)), ("sbasedon", Some(3))]),
+        );
+
+        let names = |number| -> Vec<String> {
+            stylesheet
+                .lineage(StyleKind::Paragraph, number)
+                .iter()
+                .map(|style| style.name.clone())
+                .collect()
+        };
+        assert_eq!(names(1), ["First", "Second"]);
+        assert_eq!(names(3), ["Alone"]);
+        assert_eq!(stylesheet.lookup(StyleKind::Paragraph, 1, "i"), Some(None));
+        assert_eq!(stylesheet.lookup(StyleKind::Paragraph, 2, "b"), Some(None));
+        assert_eq!(stylesheet.lookup(StyleKind::Paragraph, 3, "b"), None);
+        assert_eq!(stylesheet.outline_level(1), None);
+    }
+
+    #[test]
+    fn parses_stylesheet_entries() {
+        let document = parse(concat!(
+            r"{\rtf1\ansi{\stylesheet{\ql\fs22\snext0 Normal;}",
+            r"{\s1\sbasedon0\snext0\outlinelevel0\b heading 1 ;}",
+            r"{\*\cs10\additive\i Emphasis;}",
+            r"{\*\ds20 Section Style;}{\*\ts30\tsrowd Table Grid;}",
+            r"{\s2 ;}}Text\par}"
+        ));
+        let stylesheet = &document.stylesheet;
+
+        let heading = stylesheet.get(StyleKind::Paragraph, 1).unwrap();
+        assert_eq!(heading.name, "heading 1");
+        assert_eq!(heading.based_on, Some(0));
+        assert_eq!(heading.outline_level, Some(0));
+        assert_eq!(heading.properties.get("snext"), Some(&Some(0)));
+        assert_eq!(heading.properties.get("b"), Some(&None));
+        let normal = stylesheet.get(StyleKind::Paragraph, 0).unwrap();
+        assert_eq!(normal.name, "Normal");
+        assert_eq!(normal.properties.get("fs"), Some(&Some(22)));
+
+        let name = |kind, number| {
+            stylesheet
+                .get(kind, number)
+                .map(|style| style.name.as_str())
+        };
+        assert_eq!(name(StyleKind::Character, 10), Some("Emphasis"));
+        assert_eq!(name(StyleKind::Section, 20), Some("Section Style"));
+        assert_eq!(name(StyleKind::Table, 30), Some("Table Grid"));
+        assert_eq!(name(StyleKind::Paragraph, 10), None);
+        // Unnamed styles are left out
+        assert_eq!(name(StyleKind::Paragraph, 2), None);
+    }
+
+    #[test]
+    fn finds_outline_levels() {
+        let mut stylesheet = Stylesheet::default();
+        stylesheet.add_entry("heading 2;", properties(&[("s", Some(2))]));
+        stylesheet.add_entry(
+            "Chapter;",
+            properties(&[("s", Some(3)), ("outlinelevel", Some(0))]),
+        );
+        stylesheet.add_entry(
+            "Chapter Continued;",
+            properties(&[("s", Some(4)), ("sbasedon", Some(3))]),
+        );
+        stylesheet.add_entry(
+            "Body;",
+            properties(&[("s", Some(5)), ("outlinelevel", Some(9))]),
+        );
+        stylesheet.add_entry(
+            "Heading 3 Plain;",
+            properties(&[("s", Some(6)), ("sbasedon", Some(2))]),
+        );
+        assert_eq!(stylesheet.outline_level(2), Some(1));
+        assert_eq!(stylesheet.outline_level(3), Some(0));
+        assert_eq!(stylesheet.outline_level(4), Some(0));
+        assert_eq!(stylesheet.outline_level(5), None);
+        // Heading names aren't inherited, only explicit levels are
+        assert_eq!(stylesheet.outline_level(6), None);
+        assert_eq!(stylesheet.outline_level(7), None);
+    }
+
+    #[test]
+    fn writes_heading_styles_to_the_paragraph_list() {
+        let document = parse(concat!(
+            r"{\rtf1\ansi{\stylesheet{Normal;}{\s1\sbasedon0 heading 1;}",
+            r"{\s2\sbasedon0\outlinelevel1 Section;}{\s3\sbasedon2 Subsection;}}",
+            r"\pard\s1 Title\par\pard\s3 Part\par\pard Body\par",
+            r"\pard\s2\outlinelevel9 Demoted\par}"
+        ));
+        let mut output = Vec::new();
+        write_paragraphs_json(&document, &mut output).unwrap();
+        let list: serde_json::Value = serde_json::from_slice(&output).unwrap();
+        let summary: Vec<_> = list
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|paragraph| {
+                (
+                    paragraph["text"].as_str().unwrap().to_owned(),
+                    paragraph["style"].as_i64().unwrap(),
+                    paragraph["style_name"].as_str().map(str::to_owned),
+                    paragraph["heading_level"].as_i64(),
+                )
+            })
+            .collect();
+        let row = |text: &str, style, name: &str, level| {
+            (text.to_owned(), style, Some(name.to_owned()), level)
+        };
+        assert_eq!(
+            summary,
+            [
+                row("Title", 1, "heading 1", Some(1)),
+                row("Part", 3, "Subsection", Some(2)),
+                row("Body", 0, "Normal", None),
+                row("Demoted", 2, "Section", None),
+            ]
+        );
+    }
+}
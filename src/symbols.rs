@@ -0,0 +1,834 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+use log::debug;
+
+/// The `\fcharset` of fonts whose characters are symbols rather than text
+const SYMBOL_CHARSET: i32 = 2;
+
+/// Where Windows puts the characters of a symbol font in the Unicode
+/// private use area, and where Word writes them with `\u`
+const PRIVATE_USE_BASE: u32 = 0xF000;
+
+/// A font whose character codes stand for symbols, which need translating to
+/// the Unicode characters they show
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SymbolFont {
+    Symbol,
+    Wingdings,
+    Webdings,
+    ZapfDingbats,
+    /// Any other `\fcharset2` font, whose symbols are left in the private use area
+    Other,
+}
+
+impl SymbolFont {
+    /// Recognize a symbol font by its name, whatever its `\fcharset`
+    fn from_name(name: &str) -> Option<Self> {
+        let name: String = name
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect::<String>()
+            .to_ascii_lowercase();
+        match name.as_str() {
+            "symbol" => Some(SymbolFont::Symbol),
+            "wingdings" => Some(SymbolFont::Wingdings),
+            "webdings" => Some(SymbolFont::Webdings),
+            "zapfdingbats" | "itczapfdingbats" | "monotypesorts" => Some(SymbolFont::ZapfDingbats),
+            _ => None,
+        }
+    }
+
+    fn table(&self) -> Option<&'static [char; 224]> {
+        match self {
+            SymbolFont::Symbol => Some(&SYMBOL),
+            SymbolFont::Wingdings => Some(&WINGDINGS),
+            SymbolFont::Webdings => Some(&WEBDINGS),
+            SymbolFont::ZapfDingbats => Some(&ZAPF_DINGBATS),
+            SymbolFont::Other => None,
+        }
+    }
+
+    /// The Unicode character for a character code of the font, or the
+    /// private use character Windows gives it if there's no better one
+    ///
+    /// Control characters and the space are the same in every font.
+    pub fn map(&self, code: u8) -> char {
+        if code <= b' ' {
+            return char::from(code);
+        }
+        let mapped = code
+            .checked_sub(0x20)
+            .and_then(|index| self.table()?.get(usize::from(index)))
+            .filter(|c| **c != '\0');
+        match mapped {
+            Some(c) => *c,
+            None => char::from_u32(PRIVATE_USE_BASE + u32::from(code)).unwrap_or('\u{FFFD}'),
+        }
+    }
+
+    pub fn decode(&self, bytes: &[u8]) -> String {
+        bytes.iter().map(|code| self.map(*code)).collect()
+    }
+
+    /// Translate a `\u` character, if it's one of the private use characters
+    /// standing in for the font's symbols
+    pub fn map_unicode(&self, c: char) -> char {
+        match u32::from(c).checked_sub(PRIVATE_USE_BASE) {
+            Some(code @ 0x20..=0xff) => self.map(code as u8),
+            _ => c,
+        }
+    }
+}
+
+/// One `;`-terminated entry of the font table, as it's read
+#[derive(Default)]
+struct Entry {
+    number: Option<i32>,
+    charset: Option<i32>,
+    name: String,
+}
+
+impl Entry {
+    fn symbol_font(&self) -> Option<SymbolFont> {
+        SymbolFont::from_name(self.name.trim())
+            .or_else(|| (self.charset == Some(SYMBOL_CHARSET)).then_some(SymbolFont::Other))
+    }
+}
+
+/// Gathers the symbol fonts of the document's `\fonttbl` as its control
+/// words and `;` separators are written
+///
+/// Fonts count as symbol fonts by name, or by their `\fcharset2`. Alternative
+/// names and the other nested destinations of an entry are left out, as
+/// they're destinations of their own.
+#[derive(Default)]
+pub struct FontTable {
+    entry: Entry,
+    symbol_fonts: HashMap<i32, SymbolFont>,
+}
+
+impl FontTable {
+    pub fn handles_destination(name: &str) -> bool {
+        name == "fonttbl"
+    }
+
+    /// Note a control word in the font table
+    pub fn control_word(&mut self, name: &str, arg: Option<i32>) {
+        match name {
+            "f" => self.entry.number = arg,
+            "fcharset" => self.entry.charset = arg,
+            _ => (),
+        }
+    }
+
+    /// Note text in the font table, where each `;` ends an entry
+    pub fn text(&mut self, text: &[u8]) {
+        for part in text.split_inclusive(|b| *b == b';') {
+            match part.strip_suffix(b";") {
+                Some(name) => {
+                    self.entry.name.push_str(&String::from_utf8_lossy(name));
+                    let entry = std::mem::take(&mut self.entry);
+                    if let (Some(number), Some(font)) = (entry.number, entry.symbol_font()) {
+                        debug!("Font {} ({}) is a symbol font", number, entry.name.trim());
+                        self.symbol_fonts.insert(number, font);
+                    }
+                }
+                None => self.entry.name.push_str(&String::from_utf8_lossy(part)),
+            }
+        }
+    }
+
+    /// The symbol font with font number `number`, if it is one
+    pub fn symbol_font(&self, number: i32) -> Option<SymbolFont> {
+        self.symbol_fonts.get(&number).copied()
+    }
+}
+
+/// The character a `SYMBOL` field inserts, from its instructions, such as
+/// `SYMBOL 252 \f "Wingdings" \s 10`
+///
+/// The character code is decimal or `0x` hexadecimal, taken from the named
+/// symbol font, or as Unicode with `\u`, or else as a Windows-1252 character.
+pub fn symbol_field_text(instruction: &str) -> Option<String> {
+    let mut words = instruction.split_whitespace();
+    if !words.next()?.eq_ignore_ascii_case("SYMBOL") {
+        return None;
+    }
+    let code = words.next()?;
+    let code = match code.strip_prefix("0x").or_else(|| code.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+        None => code.parse::<u32>().ok()?,
+    };
+
+    let rest: Vec<&str> = words.collect();
+    let switch = |name: &str| rest.iter().position(|word| word.eq_ignore_ascii_case(name));
+    let font = switch("\\f").and_then(|index| {
+        let name = rest[index + 1..].join(" ");
+        let name = match name.strip_prefix('"') {
+            Some(quoted) => quoted.split('"').next().unwrap_or_default().to_owned(),
+            None => name
+                .split_whitespace()
+                .next()
+                .unwrap_or_default()
+                .to_owned(),
+        };
+        SymbolFont::from_name(&name)
+    });
+
+    let c = match (font, u8::try_from(code)) {
+        (Some(font), Ok(code)) => font.map(code),
+        _ if switch("\\u").is_some() => char::from_u32(code)?,
+        (_, Ok(code)) => encoding_rs::WINDOWS_1252
+            .decode_without_bom_handling(&[code])
+            .0
+            .chars()
+            .next()?,
+        _ => char::from_u32(code)?,
+    };
+    Some(c.to_string())
+}
+
+/// Unicode characters of the Symbol font, from 0x20
+const SYMBOL: [char; 224] = [
+    // 0x20
+    ' ', '!', '\u{2200}', '#', '\u{2203}', '%', '&', '\u{220B}', // 0x28
+    '(', ')', '\u{2217}', '+', ',', '\u{2212}', '.', '/', // 0x30
+    '0', '1', '2', '3', '4', '5', '6', '7', // 0x38
+    '8', '9', ':', ';', '<', '=', '>', '?', // 0x40
+    '\u{2245}', '\u{0391}', '\u{0392}', '\u{03A7}', '\u{0394}', '\u{0395}', '\u{03A6}', '\u{0393}',
+    // 0x48
+    '\u{0397}', '\u{0399}', '\u{03D1}', '\u{039A}', '\u{039B}', '\u{039C}', '\u{039D}', '\u{039F}',
+    // 0x50
+    '\u{03A0}', '\u{0398}', '\u{03A1}', '\u{03A3}', '\u{03A4}', '\u{03A5}', '\u{03C2}', '\u{03A9}',
+    // 0x58
+    '\u{039E}', '\u{03A8}', '\u{0396}', '[', '\u{2234}', ']', '\u{22A5}', '_',
+    // 0x60
+    '\u{203E}', '\u{03B1}', '\u{03B2}', '\u{03C7}', '\u{03B4}', '\u{03B5}', '\u{03C6}', '\u{03B3}',
+    // 0x68
+    '\u{03B7}', '\u{03B9}', '\u{03D5}', '\u{03BA}', '\u{03BB}', '\u{03BC}', '\u{03BD}', '\u{03BF}',
+    // 0x70
+    '\u{03C0}', '\u{03B8}', '\u{03C1}', '\u{03C3}', '\u{03C4}', '\u{03C5}', '\u{03D6}', '\u{03C9}',
+    // 0x78
+    '\u{03BE}', '\u{03C8}', '\u{03B6}', '{', '|', '}', '\u{223C}', '\0', // 0x80
+    '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', // 0x88
+    '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', // 0x90
+    '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', // 0x98
+    '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', // 0xA0
+    '\u{20AC}', '\u{03D2}', '\u{2032}', '\u{2264}', '\u{2044}', '\u{221E}', '\u{0192}', '\u{2663}',
+    // 0xA8
+    '\u{2666}', '\u{2665}', '\u{2660}', '\u{2194}', '\u{2190}', '\u{2191}', '\u{2192}', '\u{2193}',
+    // 0xB0
+    '\u{00B0}', '\u{00B1}', '\u{2033}', '\u{2265}', '\u{00D7}', '\u{221D}', '\u{2202}', '\u{2022}',
+    // 0xB8
+    '\u{00F7}', '\u{2260}', '\u{2261}', '\u{2248}', '\u{2026}', '\u{23D0}', '\u{23AF}', '\u{21B5}',
+    // 0xC0
+    '\u{2135}', '\u{2111}', '\u{211C}', '\u{2118}', '\u{2297}', '\u{2295}', '\u{2205}', '\u{2229}',
+    // 0xC8
+    '\u{222A}', '\u{2283}', '\u{2287}', '\u{2284}', '\u{2282}', '\u{2286}', '\u{2208}', '\u{2209}',
+    // 0xD0
+    '\u{2220}', '\u{2207}', '\u{00AE}', '\u{00A9}', '\u{2122}', '\u{220F}', '\u{221A}', '\u{22C5}',
+    // 0xD8
+    '\u{00AC}', '\u{2227}', '\u{2228}', '\u{21D4}', '\u{21D0}', '\u{21D1}', '\u{21D2}', '\u{21D3}',
+    // 0xE0
+    '\u{25CA}', '\u{2329}', '\u{00AE}', '\u{00A9}', '\u{2122}', '\u{2211}', '\u{239B}', '\u{239C}',
+    // 0xE8
+    '\u{239D}', '\u{23A1}', '\u{23A2}', '\u{23A3}', '\u{23A7}', '\u{23A8}', '\u{23A9}', '\u{23AA}',
+    // 0xF0
+    '\0', '\u{232A}', '\u{222B}', '\u{2320}', '\u{23AE}', '\u{2321}', '\u{239E}', '\u{239F}',
+    // 0xF8
+    '\u{23A0}', '\u{23A4}', '\u{23A5}', '\u{23A6}', '\u{23AB}', '\u{23AC}', '\u{23AD}', '\0',
+];
+
+/// Unicode characters of the Wingdings font, from 0x20
+const WINGDINGS: [char; 224] = [
+    // 0x20
+    ' ',
+    '\u{1F589}',
+    '\u{2702}',
+    '\u{2701}',
+    '\u{1F453}',
+    '\u{1F56D}',
+    '\u{1F56E}',
+    '\u{1F56F}',
+    // 0x28
+    '\u{1F57F}',
+    '\u{2706}',
+    '\u{1F582}',
+    '\u{1F583}',
+    '\u{1F4EA}',
+    '\u{1F4EB}',
+    '\u{1F4EC}',
+    '\u{1F4ED}',
+    // 0x30
+    '\u{1F4C1}',
+    '\u{1F4C2}',
+    '\u{1F4C4}',
+    '\u{1F5CF}',
+    '\u{1F5D0}',
+    '\u{1F5C4}',
+    '\u{231B}',
+    '\u{1F5AE}',
+    // 0x38
+    '\u{1F5B0}',
+    '\u{1F5B2}',
+    '\u{1F5B3}',
+    '\u{1F5B4}',
+    '\u{1F5AB}',
+    '\u{1F5AC}',
+    '\u{2707}',
+    '\u{270D}',
+    // 0x40
+    '\u{1F58E}',
+    '\u{270C}',
+    '\u{1F44C}',
+    '\u{1F44D}',
+    '\u{1F44E}',
+    '\u{261C}',
+    '\u{261E}',
+    '\u{261D}',
+    // 0x48
+    '\u{261F}',
+    '\u{1F590}',
+    '\u{263A}',
+    '\u{1F610}',
+    '\u{2639}',
+    '\u{1F4A3}',
+    '\u{2620}',
+    '\u{1F3F3}',
+    // 0x50
+    '\u{1F3F1}',
+    '\u{2708}',
+    '\u{263C}',
+    '\u{1F4A7}',
+    '\u{2744}',
+    '\u{1F546}',
+    '\u{271E}',
+    '\u{1F548}',
+    // 0x58
+    '\u{2720}',
+    '\u{2721}',
+    '\u{262A}',
+    '\u{262F}',
+    '\u{0950}',
+    '\u{2638}',
+    '\u{2648}',
+    '\u{2649}',
+    // 0x60
+    '\u{264A}',
+    '\u{264B}',
+    '\u{264C}',
+    '\u{264D}',
+    '\u{264E}',
+    '\u{264F}',
+    '\u{2650}',
+    '\u{2651}',
+    // 0x68
+    '\u{2652}',
+    '\u{2653}',
+    '\u{1F670}',
+    '\u{1F675}',
+    '\u{25CF}',
+    '\u{1F53E}',
+    '\u{25A0}',
+    '\u{25A1}',
+    // 0x70
+    '\u{1F790}',
+    '\u{2751}',
+    '\u{2752}',
+    '\u{2B27}',
+    '\u{29EB}',
+    '\u{25C6}',
+    '\u{2756}',
+    '\u{2B25}',
+    // 0x78
+    '\u{2327}',
+    '\u{2BB9}',
+    '\u{2318}',
+    '\u{1F3F5}',
+    '\u{1F3F6}',
+    '\u{1F676}',
+    '\u{1F677}',
+    '\0',
+    // 0x80
+    '\u{24EA}',
+    '\u{2460}',
+    '\u{2461}',
+    '\u{2462}',
+    '\u{2463}',
+    '\u{2464}',
+    '\u{2465}',
+    '\u{2466}',
+    // 0x88
+    '\u{2467}',
+    '\u{2468}',
+    '\u{2469}',
+    '\u{24FF}',
+    '\u{2776}',
+    '\u{2777}',
+    '\u{2778}',
+    '\u{2779}',
+    // 0x90
+    '\u{277A}',
+    '\u{277B}',
+    '\u{277C}',
+    '\u{277D}',
+    '\u{277E}',
+    '\u{277F}',
+    '\u{1F662}',
+    '\u{1F660}',
+    // 0x98
+    '\u{1F661}',
+    '\u{1F663}',
+    '\u{1F65E}',
+    '\u{1F65C}',
+    '\u{1F65D}',
+    '\u{1F65F}',
+    '\u{00B7}',
+    '\u{2022}',
+    // 0xA0
+    '\u{25AA}',
+    '\u{26AA}',
+    '\u{1F786}',
+    '\u{1F788}',
+    '\u{25C9}',
+    '\u{25CE}',
+    '\u{1F53F}',
+    '\u{25AA}',
+    // 0xA8
+    '\u{25FB}',
+    '\u{1F7C2}',
+    '\u{2726}',
+    '\u{2605}',
+    '\u{2736}',
+    '\u{2734}',
+    '\u{2739}',
+    '\u{2735}',
+    // 0xB0
+    '\u{2BD0}',
+    '\u{2316}',
+    '\u{27E1}',
+    '\u{2311}',
+    '\u{2BD1}',
+    '\u{272A}',
+    '\u{2730}',
+    '\u{1F550}',
+    // 0xB8
+    '\u{1F551}',
+    '\u{1F552}',
+    '\u{1F553}',
+    '\u{1F554}',
+    '\u{1F555}',
+    '\u{1F556}',
+    '\u{1F557}',
+    '\u{1F558}',
+    // 0xC0
+    '\u{1F559}',
+    '\u{1F55A}',
+    '\u{1F55B}',
+    '\u{2BB0}',
+    '\u{2BB1}',
+    '\u{2BB2}',
+    '\u{2BB3}',
+    '\u{2BB4}',
+    // 0xC8
+    '\u{2BB5}',
+    '\u{2BB6}',
+    '\u{2BB7}',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    // 0xD0
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\u{232B}',
+    '\u{2326}',
+    '\u{2B98}',
+    // 0xD8
+    '\u{27A2}',
+    '\u{2B99}',
+    '\u{2B9B}',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\u{1F850}',
+    // 0xE0
+    '\u{1F852}',
+    '\u{1F851}',
+    '\u{1F853}',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    // 0xE8
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\u{21E6}',
+    // 0xF0
+    '\u{21E8}',
+    '\u{21E7}',
+    '\u{21E9}',
+    '\u{2B04}',
+    '\u{21F3}',
+    '\0',
+    '\0',
+    '\0',
+    // 0xF8
+    '\0',
+    '\0',
+    '\0',
+    '\u{2718}',
+    '\u{2714}',
+    '\u{2612}',
+    '\u{2611}',
+    '\0',
+];
+
+/// Unicode characters of the Webdings font, from 0x20
+const WEBDINGS: [char; 224] = [
+    // 0x20
+    ' ',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    // 0x28
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    // 0x30
+    '\u{1F5D5}',
+    '\u{1F5D6}',
+    '\u{1F5D7}',
+    '\u{25C0}',
+    '\u{25B6}',
+    '\u{25B2}',
+    '\u{25BC}',
+    '\0',
+    // 0x38
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    // 0x40
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    // 0x48
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    // 0x50
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    // 0x58
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    // 0x60
+    '\0',
+    '\u{2714}',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    // 0x68
+    '\0',
+    '\u{1F6C8}',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    // 0x70
+    '\0',
+    '\0',
+    '\u{1F5D9}',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    // 0x78
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    // 0x80
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    // 0x88
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    // 0x90
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    // 0x98
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    // 0xA0
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    // 0xA8
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    // 0xB0
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    // 0xB8
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    // 0xC0
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    // 0xC8
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    // 0xD0
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    // 0xD8
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    // 0xE0
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    // 0xE8
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    // 0xF0
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    // 0xF8
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+    '\0',
+];
+
+/// Unicode characters of the Zapf Dingbats font, from 0x20
+const ZAPF_DINGBATS: [char; 224] = [
+    // 0x20
+    ' ', '\u{2701}', '\u{2702}', '\u{2703}', '\u{2704}', '\u{260E}', '\u{2706}', '\u{2707}',
+    // 0x28
+    '\u{2708}', '\u{2709}', '\u{261B}', '\u{261E}', '\u{270C}', '\u{270D}', '\u{270E}', '\u{270F}',
+    // 0x30
+    '\u{2710}', '\u{2711}', '\u{2712}', '\u{2713}', '\u{2714}', '\u{2715}', '\u{2716}', '\u{2717}',
+    // 0x38
+    '\u{2718}', '\u{2719}', '\u{271A}', '\u{271B}', '\u{271C}', '\u{271D}', '\u{271E}', '\u{271F}',
+    // 0x40
+    '\u{2720}', '\u{2721}', '\u{2722}', '\u{2723}', '\u{2724}', '\u{2725}', '\u{2726}', '\u{2727}',
+    // 0x48
+    '\u{2605}', '\u{2729}', '\u{272A}', '\u{272B}', '\u{272C}', '\u{272D}', '\u{272E}', '\u{272F}',
+    // 0x50
+    '\u{2730}', '\u{2731}', '\u{2732}', '\u{2733}', '\u{2734}', '\u{2735}', '\u{2736}', '\u{2737}',
+    // 0x58
+    '\u{2738}', '\u{2739}', '\u{273A}', '\u{273B}', '\u{273C}', '\u{273D}', '\u{273E}', '\u{273F}',
+    // 0x60
+    '\u{2740}', '\u{2741}', '\u{2742}', '\u{2743}', '\u{2744}', '\u{2745}', '\u{2746}', '\u{2747}',
+    // 0x68
+    '\u{2748}', '\u{2749}', '\u{274A}', '\u{274B}', '\u{25CF}', '\u{274D}', '\u{25A0}', '\u{274F}',
+    // 0x70
+    '\u{2750}', '\u{2751}', '\u{2752}', '\u{25B2}', '\u{25BC}', '\u{25C6}', '\u{2756}', '\u{25D7}',
+    // 0x78
+    '\u{2758}', '\u{2759}', '\u{275A}', '\u{275B}', '\u{275C}', '\u{275D}', '\u{275E}', '\0',
+    // 0x80
+    '\u{2768}', '\u{2769}', '\u{276A}', '\u{276B}', '\u{276C}', '\u{276D}', '\u{276E}', '\u{276F}',
+    // 0x88
+    '\u{2770}', '\u{2771}', '\u{2772}', '\u{2773}', '\u{2774}', '\u{2775}', '\0', '\0',
+    // 0x90
+    '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', // 0x98
+    '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', // 0xA0
+    '\0', '\u{2761}', '\u{2762}', '\u{2763}', '\u{2764}', '\u{2765}', '\u{2766}', '\u{2767}',
+    // 0xA8
+    '\u{2663}', '\u{2666}', '\u{2665}', '\u{2660}', '\u{2460}', '\u{2461}', '\u{2462}', '\u{2463}',
+    // 0xB0
+    '\u{2464}', '\u{2465}', '\u{2466}', '\u{2467}', '\u{2468}', '\u{2469}', '\u{2776}', '\u{2777}',
+    // 0xB8
+    '\u{2778}', '\u{2779}', '\u{277A}', '\u{277B}', '\u{277C}', '\u{277D}', '\u{277E}', '\u{277F}',
+    // 0xC0
+    '\u{2780}', '\u{2781}', '\u{2782}', '\u{2783}', '\u{2784}', '\u{2785}', '\u{2786}', '\u{2787}',
+    // 0xC8
+    '\u{2788}', '\u{2789}', '\u{278A}', '\u{278B}', '\u{278C}', '\u{278D}', '\u{278E}', '\u{278F}',
+    // 0xD0
+    '\u{2790}', '\u{2791}', '\u{2792}', '\u{2793}', '\u{2794}', '\u{2192}', '\u{2194}', '\u{2195}',
+    // 0xD8
+    '\u{2798}', '\u{2799}', '\u{279A}', '\u{279B}', '\u{279C}', '\u{279D}', '\u{279E}', '\u{279F}',
+    // 0xE0
+    '\u{27A0}', '\u{27A1}', '\u{27A2}', '\u{27A3}', '\u{27A4}', '\u{27A5}', '\u{27A6}', '\u{27A7}',
+    // 0xE8
+    '\u{27A8}', '\u{27A9}', '\u{27AA}', '\u{27AB}', '\u{27AC}', '\u{27AD}', '\u{27AE}', '\u{27AF}',
+    // 0xF0
+    '\0', '\u{27B1}', '\u{27B2}', '\u{27B3}', '\u{27B4}', '\u{27B5}', '\u{27B6}', '\u{27B7}',
+    // 0xF8
+    '\u{27B8}', '\u{27B9}', '\u{27BA}', '\u{27BB}', '\u{27BC}', '\u{27BD}', '\u{27BE}', '\0',
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_symbol_characters() {
+        assert_eq!(
+            SymbolFont::Symbol.decode(b"abg"),
+            "\u{03B1}\u{03B2}\u{03B3}"
+        );
+        assert_eq!(SymbolFont::Wingdings.map(0xfc), '\u{2714}');
+        assert_eq!(SymbolFont::Wingdings.map_unicode('\u{F0FC}'), '\u{2714}');
+    }
+
+    #[test]
+    fn keeps_spaces_and_control_characters() {
+        for font in [SymbolFont::Symbol, SymbolFont::Other] {
+            assert_eq!(font.decode(b" \t\n"), " \t\n");
+        }
+        assert_eq!(SymbolFont::Other.map(b'P'), '\u{F050}');
+    }
+}
@@ -0,0 +1,360 @@
+use anyhow::{bail, Result};
+use log::{debug, warn};
+
+use crate::bytes::{ansi_string, utf16_string, ByteReader};
+use crate::containers::{self, SourceDocument};
+
+/// The signature at the start of every TNEF stream (MS-OXTNEF 2.1.3.1)
+const TNEF_SIGNATURE: u32 = 0x223E_9F78;
+
+const LEVEL_MESSAGE: u8 = 0x01;
+const LEVEL_ATTACHMENT: u8 = 0x02;
+
+/// attAttachRenddata, which starts each attachment's attributes
+const ATT_ATTACH_RENDDATA: u32 = 0x0006_9002;
+/// attMAPIProps, the message's MAPI properties
+const ATT_MAPI_PROPS: u32 = 0x0006_9003;
+/// attAttachment, an attachment's MAPI properties
+const ATT_ATTACHMENT: u32 = 0x0006_9005;
+/// attAttachData, the contents of a file attachment
+const ATT_ATTACH_DATA: u32 = 0x0006_800F;
+/// attAttachTitle, an attachment's short filename
+const ATT_ATTACH_TITLE: u32 = 0x0001_8010;
+
+/// PR_RTF_COMPRESSED, the message body as compressed RTF
+const PR_RTF_COMPRESSED: u16 = 0x1009;
+/// PR_ATTACH_DATA_BIN or PR_ATTACH_DATA_OBJ, depending on the property type
+const PR_ATTACH_DATA: u16 = 0x3701;
+const PR_ATTACH_LONG_FILENAME: u16 = 0x3707;
+const PR_ATTACH_MIME_TAG: u16 = 0x370E;
+
+const PT_STRING8: u16 = 0x001E;
+const PT_UNICODE: u16 = 0x001F;
+const PT_BINARY: u16 = 0x0102;
+const PT_OBJECT: u16 = 0x000D;
+const MV_FLAG: u16 = 0x1000;
+
+/// Whether `data` is a TNEF stream, as found in `winmail.dat` attachments
+pub fn is_tnef(data: &[u8]) -> bool {
+    ByteReader::new(data).u32() == Some(TNEF_SIGNATURE)
+}
+
+/// A MAPI property from an attribute's property list
+struct MapiProperty {
+    id: u16,
+    prop_type: u16,
+    values: Vec<Vec<u8>>,
+}
+
+/// The size of each value of a fixed size property type, padded to 4 bytes
+fn fixed_size(prop_type: u16) -> Option<usize> {
+    match prop_type {
+        0x0001..=0x0004 | 0x000A | 0x000B => Some(4),
+        0x0005..=0x0007 | 0x0014 | 0x0040 => Some(8),
+        0x0048 => Some(16),
+        _ => None,
+    }
+}
+
+fn padded(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+/// Parse a MAPI property list (MS-OXTNEF 2.1.3.5), stopping at the first
+/// property that can't be read
+fn parse_mapi_properties(data: &[u8]) -> Vec<MapiProperty> {
+    let mut reader = ByteReader::new(data);
+    let mut properties = Vec::new();
+    let count = reader.u32().unwrap_or(0);
+    for _ in 0..count {
+        match parse_mapi_property(&mut reader) {
+            Some(property) => properties.push(property),
+            None => {
+                warn!(
+                    "Error reading TNEF MAPI properties, kept {} of {}",
+                    properties.len(),
+                    count
+                );
+                break;
+            }
+        }
+    }
+    properties
+}
+
+fn parse_mapi_property(reader: &mut ByteReader) -> Option<MapiProperty> {
+    let prop_type = reader.u16()?;
+    let id = reader.u16()?;
+    // Named properties are followed by their property set and name
+    if id >= 0x8000 {
+        reader.bytes(16)?;
+        match reader.u32()? {
+            0 => {
+                reader.u32()?;
+            }
+            _ => {
+                let len = reader.u32()? as usize;
+                reader.bytes(padded(len))?;
+            }
+        }
+    }
+
+    let base_type = prop_type & !MV_FLAG;
+    let values = match (fixed_size(base_type), prop_type & MV_FLAG != 0) {
+        (Some(size), false) => vec![reader.bytes(size)?.to_vec()],
+        (Some(size), true) => {
+            let count = reader.u32()?;
+            (0..count)
+                .map(|_| reader.bytes(size).map(<[u8]>::to_vec))
+                .collect::<Option<_>>()?
+        }
+        // Variable size values are always counted, even when single-valued
+        (None, _) => {
+            let count = reader.u32()?;
+            (0..count)
+                .map(|_| {
+                    let len = reader.u32()? as usize;
+                    let value = reader.bytes(padded(len))?;
+                    Some(value[..len].to_vec())
+                })
+                .collect::<Option<_>>()?
+        }
+    };
+    Some(MapiProperty {
+        id,
+        prop_type,
+        values,
+    })
+}
+
+fn find_property(properties: &[MapiProperty], id: u16, prop_type: u16) -> Option<&[u8]> {
+    properties
+        .iter()
+        .find(|property| property.id == id && property.prop_type == prop_type)
+        .and_then(|property| property.values.first())
+        .map(Vec::as_slice)
+}
+
+fn string_property(properties: &[MapiProperty], id: u16) -> Option<String> {
+    find_property(properties, id, PT_UNICODE)
+        .map(utf16_string)
+        .or_else(|| find_property(properties, id, PT_STRING8).map(ansi_string))
+}
+
+/// The attributes of one attachment, gathered until the next one starts
+#[derive(Default)]
+struct Attachment {
+    title: Option<String>,
+    data: Option<Vec<u8>>,
+    properties: Vec<MapiProperty>,
+}
+
+impl Attachment {
+    fn filename(&self) -> Option<String> {
+        string_property(&self.properties, PR_ATTACH_LONG_FILENAME)
+            .or_else(|| self.title.clone())
+            .filter(|name| !name.is_empty())
+    }
+}
+
+/// Collect the RTF body and RTF attachments of a TNEF stream, including
+/// those of any messages attached to it, labelling each after `label_prefix`
+pub fn extract_documents(
+    data: &[u8],
+    label_prefix: &str,
+    documents: &mut Vec<SourceDocument>,
+) -> Result<()> {
+    let mut reader = ByteReader::new(data);
+    if reader.u32() != Some(TNEF_SIGNATURE) {
+        bail!("Not a TNEF stream");
+    }
+    let key = reader.u16();
+    debug!("TNEF stream with attachment key {:?}", key);
+
+    let mut body = None;
+    let mut attachments: Vec<Attachment> = Vec::new();
+    while reader.remaining() > 0 {
+        let (level, id, value) = match read_attribute(&mut reader) {
+            Some(attribute) => attribute,
+            None => {
+                warn!(
+                    "TNEF stream is truncated, ignoring its last {} bytes",
+                    reader.remaining()
+                );
+                break;
+            }
+        };
+        match (level, id) {
+            (LEVEL_MESSAGE, ATT_MAPI_PROPS) => {
+                let properties = parse_mapi_properties(value);
+                if let Some(rtf) = find_property(&properties, PR_RTF_COMPRESSED, PT_BINARY) {
+                    debug!("Found compressed RTF body in TNEF message properties");
+                    body = Some(rtf.to_vec());
+                }
+            }
+            (LEVEL_ATTACHMENT, ATT_ATTACH_RENDDATA) => attachments.push(Attachment::default()),
+            (LEVEL_ATTACHMENT, _) if attachments.is_empty() => {
+                warn!("TNEF attachment attribute {:08x} before any attachment", id)
+            }
+            (LEVEL_ATTACHMENT, ATT_ATTACH_TITLE) => {
+                attachments.last_mut().unwrap().title = Some(ansi_string(value));
+            }
+            (LEVEL_ATTACHMENT, ATT_ATTACH_DATA) => {
+                attachments.last_mut().unwrap().data = Some(value.to_vec());
+            }
+            (LEVEL_ATTACHMENT, ATT_ATTACHMENT) => {
+                attachments.last_mut().unwrap().properties = parse_mapi_properties(value);
+            }
+            _ => debug!("Skipping TNEF attribute {:08x} at level {}", id, level),
+        }
+    }
+
+    if let Some(data) = body {
+        documents.push(SourceDocument::new(&format!("{}body", label_prefix), data));
+    }
+    for (index, attachment) in attachments.iter().enumerate() {
+        let filename = attachment.filename();
+        let label = containers::attachment_label(label_prefix, index, filename.as_deref());
+
+        // An attached message is an object holding a TNEF stream of its own,
+        // after the 16 byte interface identifier
+        if let Some(object) = find_property(&attachment.properties, PR_ATTACH_DATA, PT_OBJECT) {
+            let message = object.get(16..).unwrap_or_default();
+            if is_tnef(message) {
+                debug!("Attachment {} is an attached message", index + 1);
+                extract_documents(message, &format!("{}/", label), documents)?;
+                continue;
+            }
+        }
+
+        let data = match attachment
+            .data
+            .as_deref()
+            .or_else(|| find_property(&attachment.properties, PR_ATTACH_DATA, PT_BINARY))
+        {
+            Some(data) => data,
+            None => continue,
+        };
+        let mime_type = string_property(&attachment.properties, PR_ATTACH_MIME_TAG);
+        if containers::is_rtf(data) {
+            debug!("Attachment {} is RTF", index + 1);
+            documents.push(SourceDocument::new(&label, data.to_vec()));
+        } else if containers::is_named_rtf(filename.as_deref(), mime_type.as_deref()) {
+            warn!("Skipping {}, which is named as RTF but isn't", label);
+        }
+    }
+    Ok(())
+}
+
+/// Read one attribute (MS-OXTNEF 2.1.3.3), returning its level, ID and value
+fn read_attribute<'a>(reader: &mut ByteReader<'a>) -> Option<(u8, u32, &'a [u8])> {
+    let level = reader.bytes(1)?[0];
+    let id = reader.u32()?;
+    let len = reader.u32()? as usize;
+    let value = reader.bytes(len)?;
+    let checksum = reader.u16()?;
+    let actual = value
+        .iter()
+        .fold(0u16, |sum, byte| sum.wrapping_add(u16::from(*byte)));
+    if checksum != actual {
+        warn!("TNEF attribute {:08x} has a bad checksum", id);
+    }
+    Some((level, id, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attribute(level: u8, id: u32, value: &[u8]) -> Vec<u8> {
+        let checksum = value
+            .iter()
+            .fold(0u16, |sum, byte| sum.wrapping_add(u16::from(*byte)));
+        let mut data = vec![level];
+        data.extend(id.to_le_bytes());
+        data.extend((value.len() as u32).to_le_bytes());
+        data.extend(value);
+        data.extend(checksum.to_le_bytes());
+        data
+    }
+
+    /// A property list of variable size properties, each with one value
+    fn properties(properties: &[(u16, u16, &[u8])]) -> Vec<u8> {
+        let mut data = (properties.len() as u32).to_le_bytes().to_vec();
+        for (id, prop_type, value) in properties {
+            data.extend(prop_type.to_le_bytes());
+            data.extend(id.to_le_bytes());
+            data.extend(1u32.to_le_bytes());
+            data.extend((value.len() as u32).to_le_bytes());
+            data.extend(*value);
+            data.resize(padded(data.len()), 0);
+        }
+        data
+    }
+
+    fn stream(attributes: &[Vec<u8>]) -> Vec<u8> {
+        let mut data = TNEF_SIGNATURE.to_le_bytes().to_vec();
+        data.extend(1u16.to_le_bytes());
+        data.extend(attributes.concat());
+        data
+    }
+
+    fn extract(data: &[u8]) -> Vec<SourceDocument> {
+        let mut documents = Vec::new();
+        extract_documents(data, "", &mut documents).unwrap();
+        documents
+    }
+
+    #[test]
+    fn finds_the_body_and_rtf_attachments() {
+        let filename: Vec<u8> = "n\u{f6}tes.rtf\0"
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes)
+            .collect();
+        let data = stream(&[
+            attribute(
+                LEVEL_MESSAGE,
+                ATT_MAPI_PROPS,
+                &properties(&[(PR_RTF_COMPRESSED, PT_BINARY, b"{\\rtf1 Body}")]),
+            ),
+            attribute(LEVEL_ATTACHMENT, ATT_ATTACH_RENDDATA, &[0; 14]),
+            attribute(LEVEL_ATTACHMENT, ATT_ATTACH_TITLE, b"NOTES.RTF\0"),
+            attribute(LEVEL_ATTACHMENT, ATT_ATTACH_DATA, b"{\\rtf1 Notes}"),
+            attribute(
+                LEVEL_ATTACHMENT,
+                ATT_ATTACHMENT,
+                &properties(&[(PR_ATTACH_LONG_FILENAME, PT_UNICODE, &filename)]),
+            ),
+            attribute(LEVEL_ATTACHMENT, ATT_ATTACH_RENDDATA, &[0; 14]),
+            attribute(LEVEL_ATTACHMENT, ATT_ATTACH_TITLE, b"PHOTO.JPG\0"),
+            attribute(LEVEL_ATTACHMENT, ATT_ATTACH_DATA, b"\xff\xd8\xff"),
+        ]);
+        assert!(is_tnef(&data));
+        let documents = extract(&data);
+        let labels: Vec<&str> = documents.iter().map(|doc| doc.label.as_str()).collect();
+        assert_eq!(labels, ["body", "attachment 1 (n\u{f6}tes.rtf)"]);
+        assert_eq!(documents[0].data, b"{\\rtf1 Body}");
+        assert_eq!(documents[1].data, b"{\\rtf1 Notes}");
+    }
+
+    #[test]
+    fn keeps_what_it_can_of_malformed_streams() {
+        assert!(!is_tnef(b"{\\rtf1}"));
+        assert!(extract_documents(b"\x78\x9f", "", &mut Vec::new()).is_err());
+
+        // A property list that claims more properties than it holds
+        let mut props = properties(&[(PR_RTF_COMPRESSED, PT_BINARY, b"{\\rtf1 Body}")]);
+        props[0] = 3;
+        let mut data = stream(&[
+            attribute(LEVEL_MESSAGE, ATT_MAPI_PROPS, &props),
+            attribute(LEVEL_ATTACHMENT, ATT_ATTACH_DATA, b"{\\rtf1 Orphan}"),
+            attribute(LEVEL_ATTACHMENT, ATT_ATTACH_RENDDATA, &[0; 14]),
+            attribute(LEVEL_ATTACHMENT, ATT_ATTACH_DATA, b"{\\rtf1 Cut off}"),
+        ]);
+        // Cut the last attribute off part way through its value
+        data.truncate(data.len() - 6);
+        let documents = extract(&data);
+        let labels: Vec<&str> = documents.iter().map(|doc| doc.label.as_str()).collect();
+        assert_eq!(labels, ["body"]);
+    }
+}
@@ -0,0 +1,113 @@
+/// An ASCII stand-in for a character an output encoding can't represent,
+/// for the typographic punctuation, symbols and accented Latin letters that
+/// turn up in documents
+pub fn transliterate(c: char) -> Option<&'static str> {
+    let text = match c {
+        // Quotes, dashes and other punctuation
+        '\u{2018}' | '\u{2019}' | '\u{201A}' | '\u{201B}' | '\u{2032}' => "'",
+        '\u{201C}' | '\u{201D}' | '\u{201E}' | '\u{201F}' | '\u{2033}' => "\"",
+        '\u{2039}' => "<",
+        '\u{203A}' => ">",
+        '\u{00AB}' => "<<",
+        '\u{00BB}' => ">>",
+        '\u{2010}' | '\u{2011}' | '\u{2012}' | '\u{2013}' | '\u{2212}' => "-",
+        '\u{2014}' | '\u{2015}' => "--",
+        '\u{2026}' => "...",
+        '\u{2022}' | '\u{2023}' | '\u{2043}' | '\u{2219}' => "*",
+        '\u{00B7}' => ".",
+        '\u{00A1}' => "!",
+        '\u{00BF}' => "?",
+        // Spaces, and characters with no width
+        '\u{00A0}' | '\u{2000}'..='\u{200A}' | '\u{202F}' | '\u{205F}' | '\u{3000}' => " ",
+        '\u{00AD}' | '\u{200B}' | '\u{200C}' | '\u{200D}' | '\u{2060}' | '\u{FEFF}' => "",
+        // Symbols
+        '\u{00A9}' => "(C)",
+        '\u{00AE}' => "(R)",
+        '\u{2122}' => "(TM)",
+        '\u{20AC}' => "EUR",
+        '\u{00A3}' => "GBP",
+        '\u{00A5}' => "JPY",
+        '\u{00A2}' => "c",
+        '\u{00BC}' => "1/4",
+        '\u{00BD}' => "1/2",
+        '\u{00BE}' => "3/4",
+        '\u{00B1}' => "+/-",
+        '\u{00D7}' => "x",
+        '\u{00F7}' => "/",
+        '\u{2260}' => "!=",
+        '\u{2264}' => "<=",
+        '\u{2265}' => ">=",
+        '\u{2190}' => "<-",
+        '\u{2192}' => "->",
+        '\u{21D2}' => "=>",
+        // Latin letters, without their accents
+        'À' | 'Á' | 'Â' | 'Ã' | 'Ä' | 'Å' | 'Ā' | 'Ă' | 'Ą' => "A",
+        'Æ' => "AE",
+        'Ç' | 'Ć' | 'Ĉ' | 'Ċ' | 'Č' => "C",
+        'È' | 'É' | 'Ê' | 'Ë' | 'Ē' | 'Ĕ' | 'Ė' | 'Ę' | 'Ě' => "E",
+        'Ì' | 'Í' | 'Î' | 'Ï' | 'Ĩ' | 'Ī' | 'Ĭ' | 'Į' | 'İ' => "I",
+        'Ð' | 'Ď' | 'Đ' => "D",
+        'Ñ' | 'Ń' | 'Ņ' | 'Ň' | 'Ŋ' => "N",
+        'Ò' | 'Ó' | 'Ô' | 'Õ' | 'Ö' | 'Ø' | 'Ō' | 'Ŏ' | 'Ő' => "O",
+        'Ù' | 'Ú' | 'Û' | 'Ü' | 'Ũ' | 'Ū' | 'Ŭ' | 'Ů' | 'Ű' | 'Ų' => "U",
+        'Ý' | 'Ŷ' | 'Ÿ' => "Y",
+        'Þ' => "TH",
+        'ß' => "ss",
+        'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'ā' | 'ă' | 'ą' => "a",
+        'æ' => "ae",
+        'ç' | 'ć' | 'ĉ' | 'ċ' | 'č' => "c",
+        'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ĕ' | 'ė' | 'ę' | 'ě' => "e",
+        'ì' | 'í' | 'î' | 'ï' | 'ĩ' | 'ī' | 'ĭ' | 'į' | 'ı' => "i",
+        'ð' | 'ď' | 'đ' => "d",
+        'ñ' | 'ń' | 'ņ' | 'ň' | 'ŋ' => "n",
+        'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' | 'ō' | 'ŏ' | 'ő' => "o",
+        'ù' | 'ú' | 'û' | 'ü' | 'ũ' | 'ū' | 'ŭ' | 'ů' | 'ű' | 'ų' => "u",
+        'ý' | 'ÿ' | 'ŷ' => "y",
+        'þ' => "th",
+        'Ĝ' | 'Ğ' | 'Ġ' | 'Ģ' => "G",
+        'ĝ' | 'ğ' | 'ġ' | 'ģ' => "g",
+        'Ĥ' | 'Ħ' => "H",
+        'ĥ' | 'ħ' => "h",
+        'Ĳ' => "IJ",
+        'ĳ' => "ij",
+        'Ĵ' => "J",
+        'ĵ' => "j",
+        'Ķ' => "K",
+        'ķ' => "k",
+        'ĸ' => "q",
+        'Ĺ' | 'Ļ' | 'Ľ' | 'Ŀ' | 'Ł' => "L",
+        'ĺ' | 'ļ' | 'ľ' | 'ŀ' | 'ł' => "l",
+        'ŉ' => "'n",
+        'Œ' => "OE",
+        'œ' => "oe",
+        'Ŕ' | 'Ŗ' | 'Ř' => "R",
+        'ŕ' | 'ŗ' | 'ř' => "r",
+        'Ś' | 'Ŝ' | 'Ş' | 'Š' => "S",
+        'ś' | 'ŝ' | 'ş' | 'š' | 'ſ' => "s",
+        'Ţ' | 'Ť' | 'Ŧ' => "T",
+        'ţ' | 'ť' | 'ŧ' => "t",
+        'Ŵ' => "W",
+        'ŵ' => "w",
+        'Ź' | 'Ż' | 'Ž' => "Z",
+        'ź' | 'ż' | 'ž' => "z",
+        _ => return None,
+    };
+    Some(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transliterates_to_ascii() {
+        let text: String =
+            "\u{201C}Cr\u{e8}me br\u{fb}l\u{e9}e\u{201D} \u{2014} \u{a9}\u{a0}\u{c6}on"
+                .chars()
+                .map(|c| transliterate(c).map_or(c.to_string(), str::to_owned))
+                .collect();
+        assert_eq!(text, "\"Creme brulee\" -- (C) AEon");
+        assert!(transliterate('\u{65e5}').is_none());
+        assert!(transliterate('a').is_none());
+    }
+}